@@ -0,0 +1 @@
+ALTER TABLE `list_item` DROP COLUMN `position`;
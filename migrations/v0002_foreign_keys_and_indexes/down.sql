@@ -0,0 +1,94 @@
+-- Children are rebuilt before their parents, so that no table dropped is still referenced.
+
+CREATE TABLE `user_account_old`
+(
+    `user_id`     INTEGER           NOT NULL,
+    `account_id`  INTEGER           NOT NULL,
+    PRIMARY KEY (user_id, account_id)
+);
+INSERT INTO `user_account_old` SELECT * FROM `user_account`;
+DROP TABLE `user_account`;
+ALTER TABLE `user_account_old` RENAME TO `user_account`;
+
+CREATE TABLE `list_item_attribute_old`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `list_item_id`  INTEGER           NOT NULL,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     REAL,
+    `integer_val`   INTEGER,
+    `text_val`      TEXT
+);
+INSERT INTO `list_item_attribute_old` SELECT * FROM `list_item_attribute`;
+DROP TABLE `list_item_attribute`;
+ALTER TABLE `list_item_attribute_old` RENAME TO `list_item_attribute`;
+
+CREATE TABLE `list_item_old`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL,
+    `created`       VARCHAR           NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `name`          TEXT              NOT NULL,
+    `modified`      VARCHAR           NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `source`        TEXT              NOT NULL,
+    `position`      INTEGER           NOT NULL DEFAULT 0
+);
+INSERT INTO `list_item_old` SELECT * FROM `list_item`;
+DROP TABLE `list_item`;
+ALTER TABLE `list_item_old` RENAME TO `list_item`;
+
+CREATE TABLE `item_list_account_old`
+(
+    `item_list_id`     INTEGER           NOT NULL,
+    `account_id`       INTEGER           NOT NULL,
+    PRIMARY KEY (item_list_id, account_id)
+);
+INSERT INTO `item_list_account_old` SELECT * FROM `item_list_account`;
+DROP TABLE `item_list_account`;
+ALTER TABLE `item_list_account_old` RENAME TO `item_list_account`;
+
+CREATE TABLE `item_list_attribute_old`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     REAL,
+    `integer_val`   INTEGER,
+    `text_val`      TEXT
+);
+INSERT INTO `item_list_attribute_old` SELECT * FROM `item_list_attribute`;
+DROP TABLE `item_list_attribute`;
+ALTER TABLE `item_list_attribute_old` RENAME TO `item_list_attribute`;
+
+CREATE TABLE `item_list_old`
+(
+    `id`              INTEGER           NOT NULL PRIMARY KEY,
+    `owner_user_id`   INTEGER           NOT NULL,
+    `created`         VARCHAR           NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `deleted`         BOOL              NOT NULL DEFAULT FALSE,
+    `folder`          TEXT              NOT NULL DEFAULT 'default',
+    `access`          TEXT              NOT NULL DEFAULT 'Public',
+    `list_type`       TEXT              NOT NULL DEFAULT 'Standard',
+    `name`            TEXT              NOT NULL,
+    `modified`        VARCHAR           NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO `item_list_old` SELECT * FROM `item_list`;
+DROP TABLE `item_list`;
+ALTER TABLE `item_list_old` RENAME TO `item_list`;
+
+CREATE TABLE `account_old`
+(
+    `id`                  INTEGER           NOT NULL PRIMARY KEY,
+    `account_type_id`     INTEGER           NOT NULL,
+    `account_source_id`   TEXT              NOT NULL,
+    UNIQUE(account_type_id, account_source_id)
+);
+INSERT INTO `account_old` SELECT * FROM `account`;
+DROP TABLE `account`;
+ALTER TABLE `account_old` RENAME TO `account`;
@@ -0,0 +1,110 @@
+-- SQLite cannot add constraints to a table, so each table that gets foreign keys is rebuilt, parents
+-- before children, leaving behind rows whose parent is gone.
+
+CREATE TABLE `account_new`
+(
+    `id`                  INTEGER           NOT NULL PRIMARY KEY,
+    `account_type_id`     INTEGER           NOT NULL REFERENCES `account_type` (`id`),
+    `account_source_id`   TEXT              NOT NULL,
+    UNIQUE(account_type_id, account_source_id)
+);
+INSERT INTO `account_new` SELECT * FROM `account`
+    WHERE `account_type_id` IN (SELECT `id` FROM `account_type`);
+DROP TABLE `account`;
+ALTER TABLE `account_new` RENAME TO `account`;
+
+CREATE TABLE `item_list_new`
+(
+    `id`              INTEGER           NOT NULL PRIMARY KEY,
+    `owner_user_id`   INTEGER           NOT NULL REFERENCES `user` (`id`),
+    `created`         VARCHAR           NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `deleted`         BOOL              NOT NULL DEFAULT FALSE,
+    `folder`          TEXT              NOT NULL DEFAULT 'default',
+    `access`          TEXT              NOT NULL DEFAULT 'Public',
+    `list_type`       TEXT              NOT NULL DEFAULT 'Standard',
+    `name`            TEXT              NOT NULL,
+    `modified`        VARCHAR           NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO `item_list_new` SELECT * FROM `item_list`
+    WHERE `owner_user_id` IN (SELECT `id` FROM `user`);
+DROP TABLE `item_list`;
+ALTER TABLE `item_list_new` RENAME TO `item_list`;
+CREATE INDEX `item_list_owner_user_id` ON `item_list` (`owner_user_id`);
+
+CREATE TABLE `item_list_attribute_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     REAL,
+    `integer_val`   INTEGER,
+    `text_val`      TEXT
+);
+INSERT INTO `item_list_attribute_new` SELECT * FROM `item_list_attribute`
+    WHERE `item_list_id` IN (SELECT `id` FROM `item_list`);
+DROP TABLE `item_list_attribute`;
+ALTER TABLE `item_list_attribute_new` RENAME TO `item_list_attribute`;
+CREATE INDEX `item_list_attribute_item_list_id_name` ON `item_list_attribute` (`item_list_id`, `name`);
+
+CREATE TABLE `item_list_account_new`
+(
+    `item_list_id`     INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `account_id`       INTEGER           NOT NULL REFERENCES `account` (`id`) ON DELETE CASCADE,
+    PRIMARY KEY (item_list_id, account_id)
+);
+INSERT INTO `item_list_account_new` SELECT * FROM `item_list_account`
+    WHERE `item_list_id` IN (SELECT `id` FROM `item_list`)
+    AND `account_id` IN (SELECT `id` FROM `account`);
+DROP TABLE `item_list_account`;
+ALTER TABLE `item_list_account_new` RENAME TO `item_list_account`;
+CREATE INDEX `item_list_account_account_id` ON `item_list_account` (`account_id`);
+
+CREATE TABLE `list_item_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `created`       VARCHAR           NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `name`          TEXT              NOT NULL,
+    `modified`      VARCHAR           NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `source`        TEXT              NOT NULL,
+    `position`      INTEGER           NOT NULL DEFAULT 0
+);
+INSERT INTO `list_item_new` SELECT * FROM `list_item`
+    WHERE `item_list_id` IN (SELECT `id` FROM `item_list`);
+DROP TABLE `list_item`;
+ALTER TABLE `list_item_new` RENAME TO `list_item`;
+CREATE INDEX `list_item_item_list_id_position` ON `list_item` (`item_list_id`, `position`);
+
+CREATE TABLE `list_item_attribute_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `list_item_id`  INTEGER           NOT NULL REFERENCES `list_item` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     REAL,
+    `integer_val`   INTEGER,
+    `text_val`      TEXT
+);
+INSERT INTO `list_item_attribute_new` SELECT * FROM `list_item_attribute`
+    WHERE `list_item_id` IN (SELECT `id` FROM `list_item`);
+DROP TABLE `list_item_attribute`;
+ALTER TABLE `list_item_attribute_new` RENAME TO `list_item_attribute`;
+CREATE INDEX `list_item_attribute_list_item_id_name` ON `list_item_attribute` (`list_item_id`, `name`);
+
+CREATE TABLE `user_account_new`
+(
+    `user_id`     INTEGER           NOT NULL REFERENCES `user` (`id`) ON DELETE CASCADE,
+    `account_id`  INTEGER           NOT NULL REFERENCES `account` (`id`) ON DELETE CASCADE,
+    PRIMARY KEY (user_id, account_id)
+);
+INSERT INTO `user_account_new` SELECT * FROM `user_account`
+    WHERE `user_id` IN (SELECT `id` FROM `user`)
+    AND `account_id` IN (SELECT `id` FROM `account`);
+DROP TABLE `user_account`;
+ALTER TABLE `user_account_new` RENAME TO `user_account`;
+CREATE INDEX `user_account_account_id` ON `user_account` (`account_id`);
@@ -0,0 +1,12 @@
+UPDATE `item_list_attribute`
+SET `text_val` = 'PRICE: _' || `price_amount` || ' _' || COALESCE(`price_source`, '')
+WHERE `type` = 'Price' AND `price_amount` IS NOT NULL;
+
+UPDATE `list_item_attribute`
+SET `text_val` = 'PRICE: _' || `price_amount` || ' _' || COALESCE(`price_source`, '')
+WHERE `type` = 'Price' AND `price_amount` IS NOT NULL;
+
+ALTER TABLE `item_list_attribute` DROP COLUMN `price_amount`;
+ALTER TABLE `item_list_attribute` DROP COLUMN `price_source`;
+ALTER TABLE `list_item_attribute` DROP COLUMN `price_amount`;
+ALTER TABLE `list_item_attribute` DROP COLUMN `price_source`;
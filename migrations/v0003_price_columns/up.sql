@@ -0,0 +1,22 @@
+-- Prices get their own columns instead of being written into `text_val` as
+-- "PRICE: _{amount} _{source}".  Amounts are kept as decimal text so that every digit and the scale
+-- survive the round trip.
+
+ALTER TABLE `item_list_attribute` ADD COLUMN `price_amount` TEXT;
+ALTER TABLE `item_list_attribute` ADD COLUMN `price_source` TEXT;
+ALTER TABLE `list_item_attribute` ADD COLUMN `price_amount` TEXT;
+ALTER TABLE `list_item_attribute` ADD COLUMN `price_source` TEXT;
+
+-- The amount runs from after "PRICE: _" to the first " _", and the source is everything after it.
+-- Prices not in that form are left in `text_val` rather than guessed at.
+UPDATE `item_list_attribute`
+SET `price_amount` = substr(`text_val`, 9, instr(substr(`text_val`, 9), ' _') - 1),
+    `price_source` = substr(`text_val`, 9 + instr(substr(`text_val`, 9), ' _') + 1),
+    `text_val`     = NULL
+WHERE `type` = 'Price' AND `text_val` LIKE 'PRICE: \_% \_%' ESCAPE '\';
+
+UPDATE `list_item_attribute`
+SET `price_amount` = substr(`text_val`, 9, instr(substr(`text_val`, 9), ' _') - 1),
+    `price_source` = substr(`text_val`, 9 + instr(substr(`text_val`, 9), ' _') + 1),
+    `text_val`     = NULL
+WHERE `type` = 'Price' AND `text_val` LIKE 'PRICE: \_% \_%' ESCAPE '\';
@@ -0,0 +1,37 @@
+CREATE TABLE `item_list_attribute_old`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     REAL,
+    `integer_val`   INTEGER,
+    `text_val`      TEXT,
+    `price_amount`  TEXT,
+    `price_source`  TEXT
+);
+INSERT INTO `item_list_attribute_old` SELECT * FROM `item_list_attribute`;
+DROP TABLE `item_list_attribute`;
+ALTER TABLE `item_list_attribute_old` RENAME TO `item_list_attribute`;
+CREATE INDEX `item_list_attribute_item_list_id_name` ON `item_list_attribute` (`item_list_id`, `name`);
+
+CREATE TABLE `list_item_attribute_old`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `list_item_id`  INTEGER           NOT NULL REFERENCES `list_item` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     REAL,
+    `integer_val`   INTEGER,
+    `text_val`      TEXT,
+    `price_amount`  TEXT,
+    `price_source`  TEXT
+);
+INSERT INTO `list_item_attribute_old` SELECT * FROM `list_item_attribute`;
+DROP TABLE `list_item_attribute`;
+ALTER TABLE `list_item_attribute_old` RENAME TO `list_item_attribute`;
+CREATE INDEX `list_item_attribute_list_item_id_name` ON `list_item_attribute` (`list_item_id`, `name`);
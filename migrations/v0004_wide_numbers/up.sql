@@ -0,0 +1,40 @@
+-- SQLite already keeps every REAL as 8 bytes and every INTEGER in up to 8, so nothing is lost in
+-- the copy; the tables are rebuilt only so that their declared types say so.
+
+CREATE TABLE `item_list_attribute_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     DOUBLE,
+    `integer_val`   BIGINT,
+    `text_val`      TEXT,
+    `price_amount`  TEXT,
+    `price_source`  TEXT
+);
+INSERT INTO `item_list_attribute_new` SELECT * FROM `item_list_attribute`;
+DROP TABLE `item_list_attribute`;
+ALTER TABLE `item_list_attribute_new` RENAME TO `item_list_attribute`;
+CREATE INDEX `item_list_attribute_item_list_id_name` ON `item_list_attribute` (`item_list_id`, `name`);
+
+CREATE TABLE `list_item_attribute_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `list_item_id`  INTEGER           NOT NULL REFERENCES `list_item` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     DOUBLE,
+    `integer_val`   BIGINT,
+    `text_val`      TEXT,
+    `price_amount`  TEXT,
+    `price_source`  TEXT
+);
+INSERT INTO `list_item_attribute_new` SELECT * FROM `list_item_attribute`;
+DROP TABLE `list_item_attribute`;
+ALTER TABLE `list_item_attribute_new` RENAME TO `list_item_attribute`;
+CREATE INDEX `list_item_attribute_list_item_id_name` ON `list_item_attribute` (`list_item_id`, `name`);
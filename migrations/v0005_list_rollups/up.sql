@@ -0,0 +1,18 @@
+-- Every existing list starts out stale, so its rollups are computed the first time they are read.
+ALTER TABLE `item_list` ADD COLUMN `rollups_stale` BOOL NOT NULL DEFAULT TRUE;
+
+CREATE TABLE `item_list_rollup`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `function`      TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `float_val`     DOUBLE,
+    `integer_val`   BIGINT,
+    `price_amount`  TEXT,
+    `price_source`  TEXT,
+    `total_lines`   BIGINT            NOT NULL,
+    `total_units`   BIGINT            NOT NULL
+);
+CREATE INDEX `item_list_rollup_item_list_id` ON `item_list_rollup` (`item_list_id`);
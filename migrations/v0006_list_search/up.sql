@@ -0,0 +1,15 @@
+-- One row per list, its rowid being the list's id.  `items` holds the names of the list's items
+-- and `text` the values of the Text attributes of the list and its items.
+CREATE VIRTUAL TABLE `list_search` USING fts5(`name`, `items`, `text`);
+
+INSERT INTO `list_search` (`rowid`, `name`, `items`, `text`)
+SELECT `l`.`id`,
+       `l`.`name`,
+       COALESCE((SELECT group_concat(`i`.`name`, char(10)) FROM `list_item` `i` WHERE `i`.`item_list_id` = `l`.`id`), ''),
+       COALESCE((SELECT group_concat(`a`.`text_val`, char(10)) FROM `item_list_attribute` `a`
+                 WHERE `a`.`item_list_id` = `l`.`id` AND `a`.`type` = 'Text'), '')
+           || char(10) ||
+       COALESCE((SELECT group_concat(`a`.`text_val`, char(10)) FROM `list_item_attribute` `a`
+                 JOIN `list_item` `i` ON `i`.`id` = `a`.`list_item_id`
+                 WHERE `i`.`item_list_id` = `l`.`id` AND `a`.`type` = 'Text'), '')
+FROM `item_list` `l`;
@@ -0,0 +1,3 @@
+-- Raised by every change, so a client can tell whether what it read is still current.
+ALTER TABLE `item_list` ADD COLUMN `version` BIGINT NOT NULL DEFAULT 1;
+ALTER TABLE `list_item` ADD COLUMN `version` BIGINT NOT NULL DEFAULT 1;
@@ -0,0 +1,112 @@
+-- Amounts are kept as a whole number of millionths, which SQLite compares exactly.  They are read
+-- from their text digit by digit rather than through REAL, which would round large amounts.
+--
+-- `digits` is the amount without its sign, and `units` the amount in millionths.  Only an amount
+-- of digits with an optional fraction is read, and CAST's overflow to REAL marks one too large to
+-- keep.  A legacy price whose amount is missing, unreadable, out of range or has more than six
+-- decimal places would lose its value, so it becomes a Text attribute holding the price as it was
+-- written, "PRICE: _{amount} _{source}", rather than being truncated or read as 0.
+
+CREATE TABLE `item_list_attribute_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     DOUBLE,
+    `integer_val`   BIGINT,
+    `text_val`      TEXT,
+    `price_amount`  BIGINT,
+    `price_source`  TEXT
+);
+INSERT INTO `item_list_attribute_new`
+SELECT `id`, `item_list_id`, `name`,
+       CASE WHEN `type` = 'Price' AND `units` IS NULL THEN 'Text' ELSE `type` END,
+       `bool_val`, `timestamp_val`, `float_val`, `integer_val`,
+       CASE WHEN `type` = 'Price' AND `units` IS NULL
+            THEN COALESCE(`text_val`, 'PRICE: _' || `price_amount` || ' _' || COALESCE(`price_source`, ''))
+            ELSE `text_val` END,
+       `units`,
+       CASE WHEN `units` IS NOT NULL THEN `price_source` END
+FROM (SELECT *, CASE WHEN typeof(`raw_units`) = 'integer' THEN `raw_units` END AS `units`
+      FROM (SELECT *,
+                   CASE WHEN `digits` GLOB '[0-9]*' AND `digits` NOT GLOB '*[^0-9.]*' AND `digits` NOT GLOB '*.*.*' AND `digits` NOT GLOB '*.'
+                         AND (instr(`digits`, '.') = 0 OR length(`digits`) - instr(`digits`, '.') <= 6)
+                    THEN CASE WHEN `price_amount` GLOB '-*' THEN -1 ELSE 1 END
+                         * (CAST(`digits` AS INTEGER) * 1000000
+                            + CAST(substr(CASE WHEN instr(`digits`, '.') = 0 THEN '' ELSE substr(`digits`, instr(`digits`, '.') + 1) END
+                                          || '000000', 1, 6) AS INTEGER)) END AS `raw_units`
+            FROM (SELECT *, substr(`price_amount`, 1 + (`price_amount` GLOB '-*')) AS `digits` FROM `item_list_attribute`)));
+DROP TABLE `item_list_attribute`;
+ALTER TABLE `item_list_attribute_new` RENAME TO `item_list_attribute`;
+CREATE INDEX `item_list_attribute_item_list_id_name` ON `item_list_attribute` (`item_list_id`, `name`);
+
+CREATE TABLE `list_item_attribute_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `list_item_id`  INTEGER           NOT NULL REFERENCES `list_item` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     DOUBLE,
+    `integer_val`   BIGINT,
+    `text_val`      TEXT,
+    `price_amount`  BIGINT,
+    `price_source`  TEXT
+);
+INSERT INTO `list_item_attribute_new`
+SELECT `id`, `list_item_id`, `name`,
+       CASE WHEN `type` = 'Price' AND `units` IS NULL THEN 'Text' ELSE `type` END,
+       `bool_val`, `timestamp_val`, `float_val`, `integer_val`,
+       CASE WHEN `type` = 'Price' AND `units` IS NULL
+            THEN COALESCE(`text_val`, 'PRICE: _' || `price_amount` || ' _' || COALESCE(`price_source`, ''))
+            ELSE `text_val` END,
+       `units`,
+       CASE WHEN `units` IS NOT NULL THEN `price_source` END
+FROM (SELECT *, CASE WHEN typeof(`raw_units`) = 'integer' THEN `raw_units` END AS `units`
+      FROM (SELECT *,
+                   CASE WHEN `digits` GLOB '[0-9]*' AND `digits` NOT GLOB '*[^0-9.]*' AND `digits` NOT GLOB '*.*.*' AND `digits` NOT GLOB '*.'
+                         AND (instr(`digits`, '.') = 0 OR length(`digits`) - instr(`digits`, '.') <= 6)
+                    THEN CASE WHEN `price_amount` GLOB '-*' THEN -1 ELSE 1 END
+                         * (CAST(`digits` AS INTEGER) * 1000000
+                            + CAST(substr(CASE WHEN instr(`digits`, '.') = 0 THEN '' ELSE substr(`digits`, instr(`digits`, '.') + 1) END
+                                          || '000000', 1, 6) AS INTEGER)) END AS `raw_units`
+            FROM (SELECT *, substr(`price_amount`, 1 + (`price_amount` GLOB '-*')) AS `digits` FROM `list_item_attribute`)));
+DROP TABLE `list_item_attribute`;
+ALTER TABLE `list_item_attribute_new` RENAME TO `list_item_attribute`;
+CREATE INDEX `list_item_attribute_list_item_id_name` ON `list_item_attribute` (`list_item_id`, `name`);
+
+-- Rollups are derived from the items, so a stored average keeps only its first six decimal places,
+-- and a price rollup that cannot be read is dropped for `rebuild-rollups` to compute again.
+CREATE TABLE `item_list_rollup_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `function`      TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `float_val`     DOUBLE,
+    `integer_val`   BIGINT,
+    `price_amount`  BIGINT,
+    `price_source`  TEXT,
+    `total_lines`   BIGINT            NOT NULL,
+    `total_units`   BIGINT            NOT NULL
+);
+INSERT INTO `item_list_rollup_new`
+SELECT `id`, `item_list_id`, `name`, `function`, `type`, `float_val`, `integer_val`, `units`, `price_source`,
+       `total_lines`, `total_units`
+FROM (SELECT *, CASE WHEN typeof(`raw_units`) = 'integer' THEN `raw_units` END AS `units`
+      FROM (SELECT *,
+                   CASE WHEN `digits` GLOB '[0-9]*' AND `digits` NOT GLOB '*[^0-9.]*' AND `digits` NOT GLOB '*.*.*' AND `digits` NOT GLOB '*.'
+                    THEN CASE WHEN `price_amount` GLOB '-*' THEN -1 ELSE 1 END
+                         * (CAST(`digits` AS INTEGER) * 1000000
+                            + CAST(substr(CASE WHEN instr(`digits`, '.') = 0 THEN '' ELSE substr(`digits`, instr(`digits`, '.') + 1) END
+                                          || '000000', 1, 6) AS INTEGER)) END AS `raw_units`
+            FROM (SELECT *, substr(`price_amount`, 1 + (`price_amount` GLOB '-*')) AS `digits` FROM `item_list_rollup`)))
+WHERE `type` <> 'Price' OR `units` IS NOT NULL;
+DROP TABLE `item_list_rollup`;
+ALTER TABLE `item_list_rollup_new` RENAME TO `item_list_rollup`;
+CREATE INDEX `item_list_rollup_item_list_id` ON `item_list_rollup` (`item_list_id`);
@@ -0,0 +1,4 @@
+ALTER TABLE `item_list_rollup` DROP COLUMN `float_lines`;
+ALTER TABLE `item_list_rollup` DROP COLUMN `float_total`;
+ALTER TABLE `item_list_rollup` DROP COLUMN `exact_total`;
+ALTER TABLE `item_list_rollup` DROP COLUMN `currency`;
@@ -0,0 +1,9 @@
+-- What each rollup of numbers was evaluated from, so that it can be changed item by item: the
+-- currency it is for, the sum (or least or greatest) of its exact values and of every value as a
+-- Float, and how many of its values are Floats.
+ALTER TABLE `item_list_rollup` ADD COLUMN `currency` TEXT;
+ALTER TABLE `item_list_rollup` ADD COLUMN `exact_total` BIGINT;
+ALTER TABLE `item_list_rollup` ADD COLUMN `float_total` DOUBLE;
+ALTER TABLE `item_list_rollup` ADD COLUMN `float_lines` BIGINT NOT NULL DEFAULT 0;
+-- The stored rollups have none of these yet, so they are recomputed the first time they are read.
+UPDATE `item_list` SET `rollups_stale` = TRUE;
@@ -0,0 +1,76 @@
+-- Amounts are kept as their text, so that they keep the decimal places they were written with,
+-- in columns with the `amount` collation, which the application registers on every connection to
+-- compare them by value.  The whole numbers of millionths they were kept as are written without
+-- trailing zeros, as they have been read.
+
+CREATE TABLE `item_list_attribute_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     DOUBLE,
+    `integer_val`   BIGINT,
+    `text_val`      TEXT,
+    `price_amount`  TEXT COLLATE amount,
+    `price_source`  TEXT
+);
+INSERT INTO `item_list_attribute_new`
+SELECT `id`, `item_list_id`, `name`, `type`, `bool_val`, `timestamp_val`, `float_val`, `integer_val`, `text_val`,
+       rtrim(rtrim(CASE WHEN `price_amount` < 0 THEN '-' ELSE '' END || abs(`price_amount` / 1000000) || '.'
+                   || substr('00000' || abs(`price_amount` % 1000000), -6), '0'), '.'),
+       `price_source`
+FROM `item_list_attribute`;
+DROP TABLE `item_list_attribute`;
+ALTER TABLE `item_list_attribute_new` RENAME TO `item_list_attribute`;
+CREATE INDEX `item_list_attribute_item_list_id_name` ON `item_list_attribute` (`item_list_id`, `name`);
+
+CREATE TABLE `list_item_attribute_new`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `list_item_id`  INTEGER           NOT NULL REFERENCES `list_item` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `bool_val`      BOOL,
+    `timestamp_val` VARCHAR,
+    `float_val`     DOUBLE,
+    `integer_val`   BIGINT,
+    `text_val`      TEXT,
+    `price_amount`  TEXT COLLATE amount,
+    `price_source`  TEXT
+);
+INSERT INTO `list_item_attribute_new`
+SELECT `id`, `list_item_id`, `name`, `type`, `bool_val`, `timestamp_val`, `float_val`, `integer_val`, `text_val`,
+       rtrim(rtrim(CASE WHEN `price_amount` < 0 THEN '-' ELSE '' END || abs(`price_amount` / 1000000) || '.'
+                   || substr('00000' || abs(`price_amount` % 1000000), -6), '0'), '.'),
+       `price_source`
+FROM `list_item_attribute`;
+DROP TABLE `list_item_attribute`;
+ALTER TABLE `list_item_attribute_new` RENAME TO `list_item_attribute`;
+CREATE INDEX `list_item_attribute_list_item_id_name` ON `list_item_attribute` (`list_item_id`, `name`);
+
+-- Rollups are derived from the items, and averages were kept to six decimal places, so they are
+-- dropped and recomputed the first time they are read.
+DROP TABLE `item_list_rollup`;
+CREATE TABLE `item_list_rollup`
+(
+    `id`            INTEGER           NOT NULL PRIMARY KEY,
+    `item_list_id`  INTEGER           NOT NULL REFERENCES `item_list` (`id`) ON DELETE CASCADE,
+    `name`          TEXT              NOT NULL,
+    `function`      TEXT              NOT NULL,
+    `type`          TEXT              NOT NULL,
+    `float_val`     DOUBLE,
+    `integer_val`   BIGINT,
+    `price_amount`  TEXT COLLATE amount,
+    `price_source`  TEXT,
+    `total_lines`   BIGINT            NOT NULL,
+    `total_units`   BIGINT            NOT NULL,
+    `currency`      TEXT,
+    `exact_total`   TEXT COLLATE amount,
+    `float_total`   DOUBLE,
+    `float_lines`   BIGINT            NOT NULL DEFAULT 0
+);
+CREATE INDEX `item_list_rollup_item_list_id` ON `item_list_rollup` (`item_list_id`);
+UPDATE `item_list` SET `rollups_stale` = TRUE;
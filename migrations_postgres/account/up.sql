@@ -0,0 +1,15 @@
+CREATE TABLE account_type
+(
+    id        SERIAL            NOT NULL PRIMARY KEY,
+    name      TEXT              NOT NULL,
+    source    TEXT              NOT NULL,
+    UNIQUE(name, source)
+);
+
+CREATE TABLE account
+(
+    id                  SERIAL            NOT NULL PRIMARY KEY,
+    account_type_id     INTEGER           NOT NULL,
+    account_source_id   TEXT              NOT NULL,
+    UNIQUE(account_type_id, account_source_id)
+);
@@ -0,0 +1,3 @@
+DROP TABLE item_list;
+DROP TABLE item_list_attribute;
+DROP TABLE item_list_account;
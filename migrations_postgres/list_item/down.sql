@@ -0,0 +1,2 @@
+DROP TABLE list_item;
+DROP TABLE list_item_attribute;
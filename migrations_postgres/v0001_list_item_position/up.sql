@@ -0,0 +1 @@
+ALTER TABLE list_item ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
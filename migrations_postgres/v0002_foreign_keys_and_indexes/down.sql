@@ -0,0 +1,21 @@
+DROP INDEX user_account_account_id;
+ALTER TABLE user_account DROP CONSTRAINT user_account_account_id_fkey;
+ALTER TABLE user_account DROP CONSTRAINT user_account_user_id_fkey;
+
+DROP INDEX list_item_attribute_list_item_id_name;
+ALTER TABLE list_item_attribute DROP CONSTRAINT list_item_attribute_list_item_id_fkey;
+
+DROP INDEX list_item_item_list_id_position;
+ALTER TABLE list_item DROP CONSTRAINT list_item_item_list_id_fkey;
+
+DROP INDEX item_list_account_account_id;
+ALTER TABLE item_list_account DROP CONSTRAINT item_list_account_account_id_fkey;
+ALTER TABLE item_list_account DROP CONSTRAINT item_list_account_item_list_id_fkey;
+
+DROP INDEX item_list_attribute_item_list_id_name;
+ALTER TABLE item_list_attribute DROP CONSTRAINT item_list_attribute_item_list_id_fkey;
+
+DROP INDEX item_list_owner_user_id;
+ALTER TABLE item_list DROP CONSTRAINT item_list_owner_user_id_fkey;
+
+ALTER TABLE account DROP CONSTRAINT account_account_type_id_fkey;
@@ -0,0 +1,32 @@
+-- Rows whose parent is gone are removed before the constraints are added.
+
+DELETE FROM account WHERE account_type_id NOT IN (SELECT id FROM account_type);
+ALTER TABLE account ADD FOREIGN KEY (account_type_id) REFERENCES account_type (id);
+
+DELETE FROM item_list WHERE owner_user_id NOT IN (SELECT id FROM "user");
+ALTER TABLE item_list ADD FOREIGN KEY (owner_user_id) REFERENCES "user" (id);
+CREATE INDEX item_list_owner_user_id ON item_list (owner_user_id);
+
+DELETE FROM item_list_attribute WHERE item_list_id NOT IN (SELECT id FROM item_list);
+ALTER TABLE item_list_attribute ADD FOREIGN KEY (item_list_id) REFERENCES item_list (id) ON DELETE CASCADE;
+CREATE INDEX item_list_attribute_item_list_id_name ON item_list_attribute (item_list_id, name);
+
+DELETE FROM item_list_account WHERE item_list_id NOT IN (SELECT id FROM item_list)
+    OR account_id NOT IN (SELECT id FROM account);
+ALTER TABLE item_list_account ADD FOREIGN KEY (item_list_id) REFERENCES item_list (id) ON DELETE CASCADE;
+ALTER TABLE item_list_account ADD FOREIGN KEY (account_id) REFERENCES account (id) ON DELETE CASCADE;
+CREATE INDEX item_list_account_account_id ON item_list_account (account_id);
+
+DELETE FROM list_item WHERE item_list_id NOT IN (SELECT id FROM item_list);
+ALTER TABLE list_item ADD FOREIGN KEY (item_list_id) REFERENCES item_list (id) ON DELETE CASCADE;
+CREATE INDEX list_item_item_list_id_position ON list_item (item_list_id, position);
+
+DELETE FROM list_item_attribute WHERE list_item_id NOT IN (SELECT id FROM list_item);
+ALTER TABLE list_item_attribute ADD FOREIGN KEY (list_item_id) REFERENCES list_item (id) ON DELETE CASCADE;
+CREATE INDEX list_item_attribute_list_item_id_name ON list_item_attribute (list_item_id, name);
+
+DELETE FROM user_account WHERE user_id NOT IN (SELECT id FROM "user")
+    OR account_id NOT IN (SELECT id FROM account);
+ALTER TABLE user_account ADD FOREIGN KEY (user_id) REFERENCES "user" (id) ON DELETE CASCADE;
+ALTER TABLE user_account ADD FOREIGN KEY (account_id) REFERENCES account (id) ON DELETE CASCADE;
+CREATE INDEX user_account_account_id ON user_account (account_id);
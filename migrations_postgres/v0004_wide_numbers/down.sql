@@ -0,0 +1,4 @@
+ALTER TABLE item_list_attribute ALTER COLUMN float_val TYPE REAL;
+ALTER TABLE item_list_attribute ALTER COLUMN integer_val TYPE INTEGER;
+ALTER TABLE list_item_attribute ALTER COLUMN float_val TYPE REAL;
+ALTER TABLE list_item_attribute ALTER COLUMN integer_val TYPE INTEGER;
@@ -0,0 +1,4 @@
+ALTER TABLE item_list_attribute ALTER COLUMN float_val TYPE DOUBLE PRECISION;
+ALTER TABLE item_list_attribute ALTER COLUMN integer_val TYPE BIGINT;
+ALTER TABLE list_item_attribute ALTER COLUMN float_val TYPE DOUBLE PRECISION;
+ALTER TABLE list_item_attribute ALTER COLUMN integer_val TYPE BIGINT;
@@ -0,0 +1,2 @@
+DROP TABLE item_list_rollup;
+ALTER TABLE item_list DROP COLUMN rollups_stale;
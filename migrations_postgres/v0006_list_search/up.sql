@@ -0,0 +1,21 @@
+-- One row per list.  The list's name is weighted above the names of its items, which are
+-- weighted above the values of the Text attributes of the list and its items.
+CREATE TABLE list_search
+(
+    item_list_id  INTEGER           NOT NULL PRIMARY KEY REFERENCES item_list (id) ON DELETE CASCADE,
+    document      TSVECTOR          NOT NULL
+);
+CREATE INDEX list_search_document ON list_search USING GIN (document);
+
+INSERT INTO list_search (item_list_id, document)
+SELECT l.id,
+       setweight(to_tsvector('simple', l.name), 'A') ||
+       setweight(to_tsvector('simple', COALESCE((SELECT string_agg(i.name, E'\n') FROM list_item i
+                                                 WHERE i.item_list_id = l.id), '')), 'B') ||
+       setweight(to_tsvector('simple',
+           COALESCE((SELECT string_agg(a.text_val, E'\n') FROM item_list_attribute a
+                     WHERE a.item_list_id = l.id AND a.type = 'Text'), '') || E'\n' ||
+           COALESCE((SELECT string_agg(a.text_val, E'\n') FROM list_item_attribute a
+                     JOIN list_item i ON i.id = a.list_item_id
+                     WHERE i.item_list_id = l.id AND a.type = 'Text'), '')), 'C')
+FROM item_list l;
@@ -0,0 +1,2 @@
+ALTER TABLE list_item DROP COLUMN version;
+ALTER TABLE item_list DROP COLUMN version;
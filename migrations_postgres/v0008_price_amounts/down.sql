@@ -0,0 +1,3 @@
+ALTER TABLE item_list_rollup ALTER COLUMN price_amount TYPE TEXT USING price_amount::TEXT;
+ALTER TABLE list_item_attribute ALTER COLUMN price_amount TYPE TEXT USING price_amount::TEXT;
+ALTER TABLE item_list_attribute ALTER COLUMN price_amount TYPE TEXT USING price_amount::TEXT;
@@ -0,0 +1,37 @@
+-- Amounts are compared as the exact decimals they are, rather than as text or doubles.  As on
+-- SQLite, they are kept to six decimal places and within the range of a BIGINT of millionths.
+--
+-- A legacy price whose amount is missing, unreadable, out of range or has more than six decimal
+-- places would lose its value, so it becomes a Text attribute holding the price as it was written,
+-- "PRICE: _{amount} _{source}", rather than failing the cast or being truncated.
+UPDATE item_list_attribute
+SET type         = 'Text',
+    text_val     = COALESCE(text_val, 'PRICE: _' || price_amount || ' _' || COALESCE(price_source, '')),
+    price_amount = NULL,
+    price_source = NULL
+WHERE type = 'Price'
+  AND CASE WHEN price_amount ~ '^-?[0-9]+(\.[0-9]{1,6})?$'
+           THEN abs(price_amount::NUMERIC) > 9223372036854.775807
+           ELSE true END;
+
+UPDATE list_item_attribute
+SET type         = 'Text',
+    text_val     = COALESCE(text_val, 'PRICE: _' || price_amount || ' _' || COALESCE(price_source, '')),
+    price_amount = NULL,
+    price_source = NULL
+WHERE type = 'Price'
+  AND CASE WHEN price_amount ~ '^-?[0-9]+(\.[0-9]{1,6})?$'
+           THEN abs(price_amount::NUMERIC) > 9223372036854.775807
+           ELSE true END;
+
+-- Rollups are derived from the items, so a stored average keeps only its first six decimal places,
+-- and a price rollup that cannot be read is dropped for `rebuild-rollups` to compute again.
+DELETE FROM item_list_rollup
+WHERE type = 'Price'
+  AND CASE WHEN price_amount ~ '^-?[0-9]+(\.[0-9]+)?$'
+           THEN abs(price_amount::NUMERIC) >= 9223372036854.775808
+           ELSE true END;
+
+ALTER TABLE item_list_attribute ALTER COLUMN price_amount TYPE NUMERIC USING price_amount::NUMERIC;
+ALTER TABLE list_item_attribute ALTER COLUMN price_amount TYPE NUMERIC USING price_amount::NUMERIC;
+ALTER TABLE item_list_rollup ALTER COLUMN price_amount TYPE NUMERIC USING trunc(price_amount::NUMERIC, 6);
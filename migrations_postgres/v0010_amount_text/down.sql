@@ -0,0 +1 @@
+UPDATE item_list SET rollups_stale = TRUE;
@@ -0,0 +1,3 @@
+-- NUMERIC already keeps the decimal places an amount was written with.  Averages were kept to six
+-- decimal places, so rollups are recomputed the first time they are read.
+UPDATE item_list SET rollups_stale = TRUE;
@@ -0,0 +1,35 @@
+#!/usr/bin/env bash
+# Runs the test suite against a throwaway Postgres cluster on localhost.  Any arguments are passed
+# on to `cargo test`.  Skipped when Postgres is not installed.
+set -euo pipefail
+
+if ! command -v initdb > /dev/null || ! command -v pg_ctl > /dev/null; then
+    echo "initdb and pg_ctl were not found; skipping the Postgres tests."
+    exit 0
+fi
+
+port="${TEST_POSTGRES_PORT:-55432}"
+data_dir="$(mktemp -d)"
+
+# Postgres will not run as root.
+run_pg() {
+    if [ "$(id -u)" = "0" ]; then
+        su postgres -s /bin/sh -c "cd / && $*"
+    else
+        sh -c "$*"
+    fi
+}
+if [ "$(id -u)" = "0" ]; then
+    chown postgres "$data_dir"
+fi
+
+stop() {
+    run_pg "pg_ctl -D '$data_dir' -m immediate stop" > /dev/null || true
+    rm -rf "$data_dir"
+}
+trap stop EXIT
+
+run_pg "initdb -D '$data_dir' -U list_management -A trust --locale=C" > /dev/null
+run_pg "pg_ctl -D '$data_dir' -o \"-p $port -k '$data_dir' -c listen_addresses=localhost\" -w start" > /dev/null
+
+DATABASE_URL="postgres://list_management@localhost:$port/postgres" cargo test --features postgres "$@"
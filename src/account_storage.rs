@@ -2,11 +2,12 @@ use diesel::prelude::*;
 
 use crate::common::ListManagementError;
 use crate::db;
+use crate::db::DbPool;
 use crate::models::{AccountDb, AccountTypeDb};
 use crate::schema::{account, account_type};
 
-pub fn insert_account_type(name: &str, source: &str) -> Result<AccountTypeDb, ListManagementError> {
-    let mut c = db::connection();
+pub fn insert_account_type(pool: &DbPool, name: &str, source: &str) -> Result<AccountTypeDb, ListManagementError> {
+    let mut c = db::check_out(pool)?;
     let at: AccountTypeDb = diesel::insert_into(account_type::table)
         .values((account_type::name.eq(name),
                  account_type::source.eq(source)))
@@ -14,8 +15,8 @@ pub fn insert_account_type(name: &str, source: &str) -> Result<AccountTypeDb, Li
     Ok(at)
 }
 
-pub fn insert_account(account_type_id: i32, account_source_id: &str) -> Result<AccountDb, ListManagementError> {
-    let mut c = db::connection();
+pub fn insert_account(pool: &DbPool, account_type_id: i32, account_source_id: &str) -> Result<AccountDb, ListManagementError> {
+    let mut c = db::check_out(pool)?;
     let acct: AccountDb = diesel::insert_into(account::table)
         .values((account::account_type_id.eq(account_type_id),
                  account::account_source_id.eq(account_source_id)))
@@ -36,23 +37,23 @@ pub mod tests {
     #[serial]
     fn test_insert_account_type() {
         setup_db();
-        let at_result = insert_account_type("my name", "my source");
+        let at_result = insert_account_type(db::default_pool(), "my name", "my source");
         assert!(at_result.is_ok());
         let at_result_1 = at_result.unwrap();
         assert!(at_result_1.id > 0);
         assert_eq!("my source", at_result_1.source);
         assert_eq!("my name", at_result_1.name);
 
-        let at_result = insert_account_type("my name", "my source");
-        assert!(at_result.is_err());
+        let at_result = insert_account_type(db::default_pool(), "my name", "my source");
+        assert!(matches!(at_result, Err(ListManagementError::Conflict(_))));
 
-        let at_result = insert_account_type("my second name", "my source");
+        let at_result = insert_account_type(db::default_pool(), "my second name", "my source");
         assert!(at_result.is_ok());
         let at_result_2 = at_result.unwrap();
         assert!(at_result_2.id > 0);
         assert_ne!(at_result_1, at_result_2);
 
-        let at_result = insert_account_type("my name", "my second source");
+        let at_result = insert_account_type(db::default_pool(), "my name", "my second source");
         assert!(at_result.is_ok());
         let at_result_3 = at_result.unwrap();
         assert!(at_result_3.id > 0);
@@ -64,18 +65,18 @@ pub mod tests {
     #[serial]
     fn test_insert_account() {
         setup_db();
-        let at = insert_account_type("my name", "my source").unwrap();
-        let acct_result = insert_account(at.id, "my-source-123");
+        let at = insert_account_type(db::default_pool(), "my name", "my source").unwrap();
+        let acct_result = insert_account(db::default_pool(), at.id, "my-source-123");
         assert!(acct_result.is_ok());
         let acct = acct_result.unwrap();
         assert!(acct.id > 0);
         assert_eq!(at.id, acct.account_type_id);
         assert_eq!("my-source-123", acct.account_source_id);
 
-        let acct_result = insert_account(at.id, "my-source-123");
-        assert!(acct_result.is_err());
+        let acct_result = insert_account(db::default_pool(), at.id, "my-source-123");
+        assert!(matches!(acct_result, Err(ListManagementError::Conflict(_))));
 
-        let acct_result = insert_account(at.id, "my-source-456");
+        let acct_result = insert_account(db::default_pool(), at.id, "my-source-456");
         assert!(acct_result.is_ok());
         let acct2 = acct_result.unwrap();
         assert!(acct2.id > 0);
@@ -0,0 +1,72 @@
+use std::sync::Arc;
+
+use actix_web::web;
+use lazy_static::lazy_static;
+
+use crate::auth::{Authenticator, JwtAuthenticator};
+use crate::clock::{Clock, SystemClock};
+use crate::common::ListManagementError;
+use crate::config::AppConfig;
+use crate::currency::CurrencyConverter;
+use crate::db;
+use crate::db::DbPool;
+use crate::list_of_lists_service::{ListOfListsService, ListProvider};
+use crate::rollup::RollupSpecification;
+use crate::user_service::{DbUserService, UserService};
+
+/// Everything a server needs to answer requests, registered as `web::Data<AppState>` app data.
+/// Requests to an application that registers none use the pool and services configured by the
+/// environment.
+///
+/// Services are swapped by setting the fields, as in
+/// `AppState { clock: Arc::new(MyClock), ..AppState::new(config)? }`.  `user_service` keeps the
+/// pool it was made with, so a different pool is best given to `with_pool`.
+pub struct AppState {
+    pub config: AppConfig,
+    pub pool: DbPool,
+    pub list_provider: Arc<dyn ListProvider>,
+    pub user_service: Arc<dyn UserService>,
+    /// Resolves the caller of each request; by default verifies JWTs as `config.auth` says.
+    pub authenticator: Arc<dyn Authenticator>,
+    pub clock: Arc<dyn Clock>,
+    /// The rollups to compute for each list.
+    pub rollup_specification: Arc<RollupSpecification>,
+    /// Converts rollups to the currency asked for, when there is a converter.
+    pub currency_converter: Option<Arc<dyn CurrencyConverter>>,
+}
+
+impl AppState {
+    /// Connects to the configured database, with lists and users stored in it, the configured JWT
+    /// authentication, the system clock, and the configured rollups and currency rates.
+    pub fn new(config: AppConfig) -> Result<AppState, ListManagementError> {
+        let pool = db::build_pool(&config.database)?;
+        Ok(AppState::with_pool(config, pool))
+    }
+
+    /// As `new`, but with a pool already built.  The configuration must be one `AppConfig` has
+    /// checked, as its authentication settings are relied on.
+    pub fn with_pool(config: AppConfig, pool: DbPool) -> AppState {
+        AppState {
+            list_provider: Arc::new(ListOfListsService()),
+            user_service: Arc::new(DbUserService::new(pool.clone())),
+            authenticator: Arc::new(
+                JwtAuthenticator::from_config(&config.auth).expect("The configuration was checked as it was read"),
+            ),
+            clock: Arc::new(SystemClock),
+            rollup_specification: Arc::new(config.rollups.specification.clone()),
+            currency_converter: config.rollups.currency_rates.clone().map(|rates| Arc::new(rates) as Arc<dyn CurrencyConverter>),
+            config,
+            pool,
+        }
+    }
+
+    /// The state configured by the environment, sharing its pool with `db::connection`.
+    pub fn from_env() -> AppState {
+        AppState::with_pool(AppConfig::from_env(), db::default_pool().clone())
+    }
+}
+
+lazy_static! {
+    /// The state used by requests to an application that registers none.
+    pub(crate) static ref DEFAULT_STATE: web::Data<AppState> = web::Data::new(AppState::from_env());
+}
@@ -0,0 +1,195 @@
+use std::fs;
+
+use actix_web::{Error, HttpMessage, ResponseError, web};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, HeaderMap};
+use actix_web::middleware::Next;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::{AppState, DEFAULT_STATE};
+use crate::common::{ListManagementError, UserState};
+use crate::config::AuthConfig;
+use crate::db::blocking;
+use crate::user_service::UserService;
+
+/// Resolves the caller of a request into a `UserState` from the request's headers.  The default is
+/// `JwtAuthenticator`; an application may use another by setting `AppState::authenticator`.  It is
+/// called on the blocking thread pool with the application's `UserService`, so may wait on the
+/// database.
+pub trait Authenticator: Send + Sync {
+    fn authenticate(&self, headers: &HeaderMap, users: &dyn UserService) -> Result<UserState, ListManagementError>;
+}
+
+/// The claims read from a bearer token.  The issuer is the user's source and the subject is
+/// their id within that source.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct Claims {
+    pub exp: u64,
+    pub iss: String,
+    pub sub: String,
+}
+
+/// Authenticates signed JWT bearer tokens, using either a shared secret (HS256) or an RSA public
+/// key (RS256).
+pub struct JwtAuthenticator {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl JwtAuthenticator {
+    pub fn new(key: DecodingKey, algorithm: Algorithm) -> JwtAuthenticator {
+        let mut validation = Validation::new(algorithm);
+        validation.set_required_spec_claims(&["exp", "iss", "sub"]);
+        JwtAuthenticator { key, validation }
+    }
+
+    /// Reads the key configured for the algorithm.
+    pub fn from_config(config: &AuthConfig) -> Result<JwtAuthenticator, String> {
+        let key = match config.jwt_algorithm {
+            Algorithm::HS256 => match &config.jwt_secret {
+                Some(secret) if !secret.is_empty() => DecodingKey::from_secret(secret.as_bytes()),
+                _ => return Err("auth.jwt_secret must be set for HS256.".to_string()),
+            },
+            Algorithm::RS256 => {
+                let path = config
+                    .jwt_public_key_file
+                    .as_ref()
+                    .ok_or_else(|| "auth.jwt_public_key_file must be set for RS256.".to_string())?;
+                let pem = fs::read(path).map_err(|e| format!("Could not read auth.jwt_public_key_file '{}': {}", path, e))?;
+                DecodingKey::from_rsa_pem(&pem)
+                    .map_err(|_| format!("auth.jwt_public_key_file '{}' must be an RSA public key.", path))?
+            }
+            _ => return Err("auth.jwt_algorithm must be HS256 or RS256.".to_string()),
+        };
+        Ok(JwtAuthenticator::new(key, config.jwt_algorithm))
+    }
+}
+
+impl Authenticator for JwtAuthenticator {
+    fn authenticate(&self, headers: &HeaderMap, users: &dyn UserService) -> Result<UserState, ListManagementError> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or_else(|| ListManagementError::Unauthorized("a bearer token is required".to_string()))?;
+        let claims = jsonwebtoken::decode::<Claims>(token.trim(), &self.key, &self.validation)
+            .map_err(|e| ListManagementError::Unauthorized(format!("the bearer token is not valid: {}", e)))?
+            .claims;
+        let user = users.retrieve_user(&claims.iss, &claims.sub)?
+            .ok_or_else(|| ListManagementError::Unauthorized("the bearer token is for an unknown user".to_string()))?;
+        Ok(UserState {
+            active_user_accounts: user.user_accounts,
+            user_id: user.id.unwrap(),
+        })
+    }
+}
+
+/// Middleware that authenticates each request and stores the caller's `UserState` in the request
+/// extensions, or responds 401.
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let headers = req.headers().clone();
+    let state = req.app_data::<web::Data<AppState>>().unwrap_or(&DEFAULT_STATE);
+    let (authenticator, users) = (state.authenticator.clone(), state.user_service.clone());
+    let user_state = blocking(move || authenticator.authenticate(&headers, users.as_ref())).await;
+    match user_state {
+        Ok(user_state) => {
+            req.extensions_mut().insert(user_state);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        Err(e) => Ok(req.into_response(e.error_response()).map_into_right_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serial_test::serial;
+
+    use crate::config::AppConfig;
+    use crate::user_service::DbUserService;
+    use crate::test_helpers::{bearer_token, insert_account, insert_account_type, insert_user, setup_db};
+
+    use super::*;
+
+    #[test]
+    #[serial]
+    fn test_authenticate_hs256() {
+        setup_db();
+        let account_type_id = insert_account_type("at1".to_string(), "ats1".to_string());
+        let account_id = insert_account(account_type_id, "as1".to_string());
+        let user_id = insert_user("User One", "s1", "s1-1");
+        crate::test_helpers::insert_user_account(user_id, account_id);
+
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, bearer_token("s1", "s1-1")))
+            .to_http_request();
+        let authenticator = JwtAuthenticator::from_config(&AppConfig::from_env().auth).unwrap();
+        let user_state = authenticator.authenticate(req.headers(), &users()).unwrap();
+        assert_eq!(user_id as u64, user_state.user_id);
+        assert_eq!(1, user_state.active_user_accounts.len());
+        assert_eq!(Some(account_id as u64), user_state.active_user_accounts[0].id);
+
+        for header in [
+            None,
+            Some("s1-1".to_string()),
+            Some("Bearer not-a-token".to_string()),
+            Some(bearer_token("s1", "s1-2")),
+            Some(bearer_token("s2", "s1-1")),
+            Some(format!("Bearer {}", token(Algorithm::HS256, &EncodingKey::from_secret(b"wrong secret"), 60))),
+            Some(format!("Bearer {}", token(Algorithm::HS256, &EncodingKey::from_secret(secret().as_bytes()), -120))),
+        ] {
+            let mut req = TestRequest::default();
+            if let Some(header) = &header {
+                req = req.insert_header((AUTHORIZATION, header.as_str()));
+            }
+            let result = authenticator.authenticate(req.to_http_request().headers(), &users());
+            assert!(matches!(result, Err(ListManagementError::Unauthorized(_))), "{:?}", header);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_authenticate_rs256() {
+        setup_db();
+        let user_id = insert_user("User One", "s1", "s1-1");
+        let authenticator = JwtAuthenticator::new(
+            DecodingKey::from_rsa_pem(include_bytes!("../test_resources/jwt_rs256_public.pem")).unwrap(),
+            Algorithm::RS256,
+        );
+        let private_key = EncodingKey::from_rsa_pem(include_bytes!("../test_resources/jwt_rs256_private.pem")).unwrap();
+
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, format!("Bearer {}", token(Algorithm::RS256, &private_key, 60))))
+            .to_http_request();
+        assert_eq!(user_id as u64, authenticator.authenticate(req.headers(), &users()).unwrap().user_id);
+
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, bearer_token("s1", "s1-1")))
+            .to_http_request();
+        assert!(matches!(authenticator.authenticate(req.headers(), &users()), Err(ListManagementError::Unauthorized(_))));
+    }
+
+    fn token(algorithm: Algorithm, key: &EncodingKey, expires_in_seconds: i64) -> String {
+        let claims = Claims {
+            exp: (chrono::Utc::now().timestamp() + expires_in_seconds) as u64,
+            iss: "s1".to_string(),
+            sub: "s1-1".to_string(),
+        };
+        jsonwebtoken::encode(&Header::new(algorithm), &claims, key).unwrap()
+    }
+
+    fn users() -> DbUserService {
+        DbUserService::new(crate::db::default_pool().clone())
+    }
+
+    fn secret() -> String {
+        AppConfig::from_env().auth.jwt_secret.unwrap()
+    }
+}
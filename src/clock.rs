@@ -0,0 +1,15 @@
+use chrono::{NaiveDateTime, Utc};
+
+/// Tells the time that lists and items are stamped with as they are created and changed.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> NaiveDateTime;
+}
+
+/// The system clock, in UTC.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Utc::now().naive_utc()
+    }
+}
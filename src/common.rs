@@ -3,10 +3,16 @@ use std::collections::HashMap;
 use chrono::NaiveDateTime;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use strum_macros::{Display, EnumString};
+use diesel::result::DatabaseErrorKind;
+use strum_macros::{Display, EnumString, IntoStaticStr};
 use thiserror::Error;
+use tracing::warn;
 
-use crate::list_of_lists_service::{ListOfListsService, ListProvider};
+use crate::clock::{Clock, SystemClock};
+use crate::currency::CurrencyConverter;
+use crate::db::DbPool;
+use crate::list_of_lists_service::{LIST_OF_LISTS_SERVICE, ListProvider};
+use crate::rollup::{RollupFunction, RollupSpecification};
 
 pub static ATTRIBUTE_QUANTITY: &str = "quantity";
 
@@ -28,6 +34,51 @@ pub struct AccountType {
     pub source: String,
 }
 
+/// Predicates on attributes combined with `And` and `Or`, as used to select lists and items.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum AttributeFilter {
+    Predicate(AttributePredicate),
+    /// Holds when every one of the filters holds, and so always when there are none.
+    And(Vec<AttributeFilter>),
+    /// Holds when any one of the filters holds, and so never when there are none.
+    Or(Vec<AttributeFilter>),
+}
+
+/// What must be true of the list's, or item's, attribute with the given name.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct AttributePredicate {
+    pub name: String,
+    pub condition: AttributeCondition,
+}
+
+/// What an `AttributePredicate` asks of the attribute.  In a comparison, a Boolean, DateTime or
+/// Text value only matches attributes of the same type.  An Integer or Float matches the number
+/// of an Integer or Float attribute, or the amount of a Price in any currency, while a Price only
+/// matches the amount of a Price in its own currency.  Every condition but `Missing` requires the
+/// attribute to be there.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum AttributeCondition {
+    Exists,
+    Missing,
+    Compare(Comparison, ListAttribute),
+    /// A Text attribute containing this text, without regard to case.
+    Contains(String),
+}
+
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
 #[derive(Clone, Debug)]
 #[derive(Serialize, Deserialize)]
 pub struct ItemList {
@@ -44,19 +95,60 @@ pub struct ItemList {
     pub modified: NaiveDateTime,
     pub name: String,
     pub read_only: bool,
-    pub rollups: Option<HashMap<String, ItemListRollup>>,
+    /// The results of the list's rollups, as given by the `RollupSpecification`.
+    pub rollups: Option<Vec<ItemListRollup>>,
+    /// The results of the list's price rollups in the currency asked for by `RollupRequest`.
+    pub converted_rollups: Option<Vec<ItemListRollup>>,
+    /// Raised by every change to the list or its items, and given as the list's `ETag`.
+    pub version: u64,
 }
 
-#[derive(Clone, Debug)]
+/// The result of one rollup over a list's items.  `value` is an Integer for counts and a Price,
+/// one for each currency, for rollups of prices.  Other rollups give an Integer when every value
+/// was an Integer, and a Float otherwise or for an average.  `total_lines` is the number of items
+/// that went into the result and `total_units` the sum of their `multiply_by` attribute.
+#[derive(Clone, Debug, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub struct ItemListRollup {
-    pub total_amount: Price,
+    pub name: String,
+    pub function: RollupFunction,
+    pub value: ListAttribute,
     pub total_lines: u64,
     pub total_units: u64,
 }
 
+/// The changes to make to an `ItemList`.  Fields left as `None` are not changed; when creating a
+/// list they take their default values.  `attributes`, when given, replaces the whole set.
+#[derive(Clone, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct ItemListUpdate {
+    pub attributes: Option<HashMap<String, ListAttribute>>,
+    pub folder: Option<String>,
+    pub list_access: Option<ListAccess>,
+    pub list_type: Option<ListType>,
+    pub name: Option<String>,
+}
+
+/// The orders items found across lists can be returned in.
 #[derive(Clone, Debug, EnumString, PartialEq)]
 #[derive(Serialize, Deserialize)]
+pub enum ItemSortKey {
+    CreatedDate,
+    Id,
+    ModifiedDate,
+    Name,
+    Source,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ItemSortRequest {
+    pub descending: bool,
+    pub key: ItemSortKey,
+}
+
+#[derive(Clone, Debug, Display, EnumString, PartialEq)]
+#[derive(Serialize, Deserialize)]
 pub enum ListAccess {
     Private,
     Public,
@@ -84,21 +176,90 @@ pub struct ListItem {
     pub modified: NaiveDateTime,
     pub name: String,
     pub source: String,
+    /// Raised by every change to the item, and given as the item's `ETag`.
+    pub version: u64,
+}
+
+/// The changes to make to a `ListItem`.  Fields left as `None` are not changed; `name` and
+/// `source` are required when adding an item.  `attributes` are added to or replace the item's
+/// existing attributes of the same name, and `remove_attributes` are removed.
+#[derive(Clone, Debug, Default)]
+#[derive(Serialize, Deserialize)]
+pub struct ListItemUpdate {
+    pub attributes: Option<HashMap<String, ListAttribute>>,
+    pub name: Option<String>,
+    pub remove_attributes: Option<Vec<String>>,
+    pub source: Option<String>,
 }
 
-#[derive(Error, Debug)]
+/// Everything that can go wrong handling a request.  Each variant is answered with its own HTTP
+/// status and an `ErrorResponse` body.
+#[derive(Error, Debug, IntoStaticStr)]
 pub enum ListManagementError {
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("database error")]
-    Database(#[from] diesel::result::Error),
+    Database(#[source] diesel::result::Error),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
 
     #[error("Not Found: {0}")]
     NotFound(String),
 
+    #[error("migration error: {0}")]
+    Migration(String),
+
+    /// The list or item was changed since the version the caller gave in `If-Match`.
+    #[error("Precondition Failed: {0}")]
+    PreconditionFailed(String),
+
+    /// A change was asked for without the version it was based on.
+    #[error("Precondition Required: {0}")]
+    PreconditionRequired(String),
+
+    #[error("Invalid: {0}")]
+    Validation(String),
+
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
+
     #[error("other error")]
     Other,
 }
 
-#[derive(Clone, Debug, EnumString, PartialEq)]
+/// Writes that would break a uniqueness or reference constraint conflict with existing data.  The
+/// database's own message names tables and constraints, so it is logged rather than returned.
+impl From<diesel::result::Error> for ListManagementError {
+    fn from(e: diesel::result::Error) -> Self {
+        match e {
+            diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, info) => {
+                warn!("Write conflicts with existing data: {}", info.message());
+                ListManagementError::Conflict("it already exists".to_string())
+            }
+            diesel::result::Error::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info) => {
+                warn!("Write conflicts with existing data: {}", info.message());
+                ListManagementError::Conflict("it refers to, or is referred to by, other data".to_string())
+            }
+            e => ListManagementError::Database(e),
+        }
+    }
+}
+
+/// The body of every error response.  `error` is the kind of error, named as in
+/// `ListManagementError`, and `message` describes it.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Display, EnumString, PartialEq, Eq, Hash)]
 #[derive(Serialize, Deserialize)]
 pub enum ListType {
     Standard,
@@ -106,12 +267,25 @@ pub enum ListType {
     Transient,
 }
 
-pub trait LMContext {
-    fn current_user(&self) -> User;
+/// What services need to know about the request they serve.  Contexts are shared with list
+/// providers across awaits, so must be `Sync`.
+pub trait LMContext: Sync {
+    fn current_user(&self) -> Result<User, ListManagementError>;
     fn current_user_state(&self) -> UserState;
-    fn list_provider(&self) -> impl ListProvider {
-        ListOfListsService()
+    /// Where lists come from.
+    fn list_provider(&self) -> &dyn ListProvider {
+        &LIST_OF_LISTS_SERVICE
+    }
+    /// The database that lists and users are stored in.
+    fn pool(&self) -> &DbPool;
+    /// The time that changes are stamped with.
+    fn clock(&self) -> &dyn Clock {
+        &SystemClock
     }
+    /// The rollups to compute for each list.
+    fn rollup_specification(&self) -> &RollupSpecification;
+    /// Converts rollups to the currency asked for, if any converter is configured.
+    fn currency_converter(&self) -> Option<&dyn CurrencyConverter>;
 }
 
 #[derive(Clone, Debug)]
@@ -119,6 +293,9 @@ pub trait LMContext {
 pub struct PagingRequest {
     pub start: u64,
     pub rows: u64,
+    /// The `next_cursor` of the previous page.  When given, the page starts after the row the
+    /// cursor points at and `start` is ignored.
+    pub cursor: Option<String>,
 }
 
 
@@ -130,7 +307,15 @@ pub struct Price {
 }
 
 
-#[derive(Clone, Debug, EnumString)]
+/// Asks for each list's rollups.  When `currency` is given, each price attribute is also totalled
+/// across all of its currencies, converted to that one.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct RollupRequest {
+    pub currency: Option<String>,
+}
+
+#[derive(Clone, Debug, EnumString, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub enum SortKey {
     Attribute(String),
@@ -138,9 +323,11 @@ pub enum SortKey {
     Id,
     ModifiedDate,
     Name,
+    /// How well each list matches the selector's search, best first.  Requires a search.
+    Relevance,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub struct SortRequest {
     pub descending: bool,
@@ -165,8 +352,15 @@ pub struct UserState {
 
 #[cfg(test)]
 pub(crate) mod tests {
-    use crate::common::{ItemList, LMContext, PagingRequest, SortRequest, User, UserState};
-    use crate::list_of_lists_service::{ListProvider, ListSelector};
+    use crate::common::{ItemList, ListManagementError, LMContext, PagingRequest, RollupRequest, SortRequest, User, UserState};
+    use std::future::ready;
+    use std::sync::Mutex;
+
+    use crate::currency::CurrencyConverter;
+    use crate::db;
+    use crate::db::DbPool;
+    use crate::list_of_lists_service::{ListOfListsResponse, ListProvider, ListSelector, ListsFuture};
+    use crate::rollup::RollupSpecification;
 
     pub fn context(
         user: User,
@@ -176,6 +370,7 @@ pub(crate) mod tests {
             current_user: user,
             current_user_state: state,
             list_provider: mock_list_provider(vec![]),
+            rollup_specification: RollupSpecification::default(),
         }
     }
 
@@ -189,26 +384,82 @@ pub(crate) mod tests {
             current_user: user,
             current_user_state: state,
             list_provider: mock_list_provider(lists),
+            rollup_specification: RollupSpecification::default(),
         }
     }
 
+    #[allow(clippy::upper_case_acronyms)]
     pub struct LMC {
         pub current_user: User,
         pub current_user_state: UserState,
         pub list_provider: MockListProvider,
+        pub rollup_specification: RollupSpecification,
     }
 
     impl LMContext for LMC {
-        fn current_user(&self) -> User {
-            self.current_user.clone()
+        fn current_user(&self) -> Result<User, ListManagementError> {
+            Ok(self.current_user.clone())
         }
 
         fn current_user_state(&self) -> UserState {
             self.current_user_state.clone()
         }
 
-        fn list_provider(&self) -> impl ListProvider {
-            self.list_provider.clone()
+        fn list_provider(&self) -> &dyn ListProvider {
+            &self.list_provider
+        }
+
+        fn pool(&self) -> &DbPool {
+            db::default_pool()
+        }
+
+        fn rollup_specification(&self) -> &RollupSpecification {
+            &self.rollup_specification
+        }
+
+        fn currency_converter(&self) -> Option<&dyn CurrencyConverter> {
+            None
+        }
+    }
+
+    /// A context whose list provider reads from the database.
+    pub fn db_context(
+        user: User,
+        state: UserState,
+    ) -> impl LMContext {
+        DbLMC {
+            current_user: user,
+            current_user_state: state,
+            rollup_specification: RollupSpecification::default(),
+        }
+    }
+
+    #[allow(clippy::upper_case_acronyms)]
+    pub struct DbLMC {
+        pub current_user: User,
+        pub current_user_state: UserState,
+        pub rollup_specification: RollupSpecification,
+    }
+
+    impl LMContext for DbLMC {
+        fn current_user(&self) -> Result<User, ListManagementError> {
+            Ok(self.current_user.clone())
+        }
+
+        fn current_user_state(&self) -> UserState {
+            self.current_user_state.clone()
+        }
+
+        fn pool(&self) -> &DbPool {
+            db::default_pool()
+        }
+
+        fn rollup_specification(&self) -> &RollupSpecification {
+            &self.rollup_specification
+        }
+
+        fn currency_converter(&self) -> Option<&dyn CurrencyConverter> {
+            None
         }
     }
 
@@ -229,42 +480,48 @@ pub(crate) mod tests {
         }
     }
 
-    #[derive(Clone)]
     pub struct MockListProvider {
-        last_selector: Option<ListSelector>,
-        last_paging: Option<PagingRequest>,
-        last_sort: Option<SortRequest>,
-        last_return_attributes: Option<bool>,
-        last_return_rollups: Option<bool>,
+        last_selector: Mutex<Option<ListSelector>>,
+        last_paging: Mutex<Option<PagingRequest>>,
+        last_sort: Mutex<Option<SortRequest>>,
+        last_return_attributes: Mutex<Option<bool>>,
+        last_rollups: Mutex<Option<Option<RollupRequest>>>,
         //
         lists: Vec<ItemList>,
     }
 
     impl ListProvider for MockListProvider {
-        fn retrieve_lists(&mut self,
-                          _context: &impl LMContext,
-                          selector: ListSelector,
-                          paging: PagingRequest,
-                          sort: SortRequest,
-                          return_attributes: bool,
-                          return_rollups: bool) -> Vec<ItemList> {
-            self.last_selector = Some(selector);
-            self.last_paging = Some(paging);
-            self.last_sort = Some(sort);
-            self.last_return_attributes = Some(return_attributes);
-            self.last_return_rollups = Some(return_rollups);
-
-            self.lists.clone()
+        fn retrieve_lists<'a>(&'a self,
+                              _context: &'a dyn LMContext,
+                              selector: ListSelector,
+                              paging: PagingRequest,
+                              sort: SortRequest,
+                              return_attributes: bool,
+                              rollups: Option<RollupRequest>) -> ListsFuture<'a> {
+            let (start, rows) = (paging.start, paging.rows);
+            *self.last_selector.lock().unwrap() = Some(selector);
+            *self.last_paging.lock().unwrap() = Some(paging);
+            *self.last_sort.lock().unwrap() = Some(sort);
+            *self.last_return_attributes.lock().unwrap() = Some(return_attributes);
+            *self.last_rollups.lock().unwrap() = Some(rollups);
+
+            Box::pin(ready(Ok(ListOfListsResponse {
+                total: self.lists.len() as u64,
+                start,
+                rows,
+                lists: self.lists.clone(),
+                next_cursor: None,
+            })))
         }
     }
 
     pub fn mock_list_provider(lists: Vec<ItemList>) -> MockListProvider {
         MockListProvider {
-            last_selector: None,
-            last_paging: None,
-            last_sort: None,
-            last_return_attributes: None,
-            last_return_rollups: None,
+            last_selector: Mutex::new(None),
+            last_paging: Mutex::new(None),
+            last_sort: Mutex::new(None),
+            last_return_attributes: Mutex::new(None),
+            last_rollups: Mutex::new(None),
             lists,
         }
     }
@@ -0,0 +1,508 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::BufReader;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use dotenvy::dotenv;
+use jsonwebtoken::Algorithm;
+use thiserror::Error;
+use toml::{Table, Value};
+use tracing_subscriber::EnvFilter;
+
+use crate::auth::JwtAuthenticator;
+use crate::common::User;
+use crate::currency::StaticRateConverter;
+use crate::db::MigrationMode;
+use crate::rollup::RollupSpecification;
+
+/// How long a request waits for a connection when `database.checkout_timeout_ms` is not set.
+const DEFAULT_CHECKOUT_TIMEOUT_MS: u64 = 5000;
+
+/// Every setting, with the environment variable that overrides it.
+const SETTINGS: &[(&str, &str)] = &[
+    ("database.url", "DATABASE_URL"),
+    ("database.pool_size", "DATABASE_POOL_SIZE"),
+    ("database.checkout_timeout_ms", "DATABASE_CHECKOUT_TIMEOUT_MS"),
+    ("database.migrations", "DATABASE_MIGRATIONS"),
+    ("server.host", "SERVER_HOST"),
+    ("server.port", "SERVER_PORT"),
+    ("server.workers", "SERVER_WORKERS"),
+    ("server.tls.cert_file", "SERVER_TLS_CERT_FILE"),
+    ("server.tls.key_file", "SERVER_TLS_KEY_FILE"),
+    ("auth.jwt_algorithm", "AUTH_JWT_ALGORITHM"),
+    ("auth.jwt_secret", "AUTH_JWT_SECRET"),
+    ("auth.jwt_public_key_file", "AUTH_JWT_PUBLIC_KEY_FILE"),
+    ("auth.admins", "AUTH_ADMINS"),
+    ("paging.default_rows", "PAGING_DEFAULT_ROWS"),
+    ("paging.default_item_rows", "PAGING_DEFAULT_ITEM_ROWS"),
+    ("paging.max_rows", "PAGING_MAX_ROWS"),
+    ("features.search", "FEATURES_SEARCH"),
+    ("features.admin", "FEATURES_ADMIN"),
+    ("rollups.definitions_file", "ROLLUP_DEFINITIONS_FILE"),
+    ("rollups.currency_rates", "CURRENCY_RATES"),
+    ("log_level", "LOG_LEVEL"),
+];
+
+/// The settings the server runs with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppConfig {
+    pub database: DatabaseConfig,
+    pub server: ServerConfig,
+    pub auth: AuthConfig,
+    pub paging: PagingConfig,
+    pub features: FeatureConfig,
+    pub rollups: RollupConfig,
+    pub log_level: String,
+}
+
+/// Where the database is, and how connections to it are pooled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub pool_size: u32,
+    /// How long a request waits for a free connection before being answered 503.
+    pub checkout_timeout_ms: u64,
+    /// What to do at startup about migrations that have not been run.
+    pub migrations: MigrationMode,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// The number of worker threads, by default one per CPU.
+    pub workers: Option<usize>,
+    /// Serves HTTPS when set.
+    pub tls: Option<TlsConfig>,
+}
+
+/// PEM files holding the server's certificate chain and its private key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TlsConfig {
+    pub cert_file: String,
+    pub key_file: String,
+}
+
+/// How the bearer tokens of `JwtAuthenticator` are verified: with `jwt_secret` for HS256, or
+/// the RSA public key in the PEM file `jwt_public_key_file` for RS256.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthConfig {
+    pub jwt_algorithm: Algorithm,
+    pub jwt_secret: Option<String>,
+    pub jwt_public_key_file: Option<String>,
+    /// The users who may call the `/admin` endpoints, each written `SOURCE:SOURCE_ID`.
+    pub admins: Vec<String>,
+}
+
+impl AuthConfig {
+    pub fn is_admin(&self, user: &User) -> bool {
+        self.admins.iter().any(|admin| admin.split_once(':') == Some((user.source.as_str(), user.source_id.as_str())))
+    }
+}
+
+/// The rows in a page when a request does not say, and the most a request may ask for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PagingConfig {
+    /// For `/list_of_lists` and `/items`.
+    pub default_rows: u64,
+    /// For the items of a list.
+    pub default_item_rows: u64,
+    pub max_rows: u64,
+}
+
+/// Parts of the API that may be turned off.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeatureConfig {
+    /// Full-text search over lists.
+    pub search: bool,
+    /// The `/admin` endpoints.
+    pub admin: bool,
+}
+
+/// The rollups computed for each list, and the rates they are converted to other currencies at.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RollupConfig {
+    /// The JSON file `specification` was read from.
+    pub definitions_file: Option<String>,
+    /// The definitions in `definitions_file`, or none, giving the default rollups.
+    pub specification: RollupSpecification,
+    /// Read from rates written `USD=1.00,EUR=1.08`; without them, rollups are not converted.
+    pub currency_rates: Option<StaticRateConverter>,
+}
+
+/// Every setting that could not be used, each on its own line.
+#[derive(Debug, Error, PartialEq)]
+#[error("The configuration is not valid:\n  {}", .problems.join("\n  "))]
+pub struct ConfigError {
+    pub problems: Vec<String>,
+}
+
+impl AppConfig {
+    /// Reads the settings from the TOML file named by `--config=<file>` or `CONFIG_FILE`, then
+    /// the environment and any `.env` file, then the command-line flags, each overriding the
+    /// last.  Flags are written `--<setting>=<value>`, as in `--server.port=8081`.
+    pub fn load(args: &[String]) -> Result<AppConfig, ConfigError> {
+        dotenv().ok();
+        AppConfig::load_with(args, |name| env::var(name).ok())
+    }
+
+    /// As `load`, reading environment variables with `env`.
+    pub fn load_with(args: &[String], env: impl Fn(&str) -> Option<String>) -> Result<AppConfig, ConfigError> {
+        let mut settings = Settings::default();
+        let mut flags = Vec::new();
+        let mut config_file = env("CONFIG_FILE");
+        for arg in args {
+            match arg.strip_prefix("--").and_then(|flag| flag.split_once('=')) {
+                Some(("config", file)) => config_file = Some(file.to_string()),
+                Some((name, value)) => flags.push((name, value)),
+                None => settings.problems.push(format!("The flag '{}' must be written --<setting>=<value>.", arg)),
+            }
+        }
+        if let Some(file) = config_file {
+            let table = std::fs::read_to_string(&file)
+                .map_err(|e| e.to_string())
+                .and_then(|text| text.parse::<Table>().map_err(|e| e.to_string()));
+            match table {
+                Ok(table) => settings.add_table("", &table, &file),
+                Err(e) => settings.problems.push(format!("Could not read the configuration file '{}': {}", file, e.trim())),
+            }
+        }
+        for (name, var) in SETTINGS {
+            if let Some(value) = env(var) {
+                settings.add(name, Value::String(value), var);
+            }
+        }
+        for (name, value) in flags {
+            settings.add(name, Value::String(value.to_string()), &format!("--{}", name));
+        }
+        let config = settings.read();
+        if settings.problems.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError { problems: settings.problems })
+        }
+    }
+
+    /// Reads the settings from any `CONFIG_FILE` and the environment, panicking with every
+    /// problem found should they not be valid.
+    pub fn from_env() -> AppConfig {
+        AppConfig::load(&[]).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+impl TlsConfig {
+    /// Loads the certificate chain and key.
+    pub fn server_config(&self) -> Result<rustls::ServerConfig, String> {
+        let open = |name: &str, file: &str| {
+            File::open(file)
+                .map(BufReader::new)
+                .map_err(|e| format!("Could not read {} '{}': {}", name, file, e))
+        };
+        let certs = rustls_pemfile::certs(&mut open("server.tls.cert_file", &self.cert_file)?)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("server.tls.cert_file '{}' is not a PEM file: {}", self.cert_file, e))?;
+        if certs.is_empty() {
+            return Err(format!("server.tls.cert_file '{}' holds no certificates.", self.cert_file));
+        }
+        let key = rustls_pemfile::private_key(&mut open("server.tls.key_file", &self.key_file)?)
+            .map_err(|e| format!("server.tls.key_file '{}' is not a PEM file: {}", self.key_file, e))?
+            .ok_or_else(|| format!("server.tls.key_file '{}' holds no private key.", self.key_file))?;
+        rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+            .map_err(|e| format!("The TLS certificate and key cannot be used: {}.", e))
+    }
+}
+
+/// Settings by name, as given by the last layer to set each, and the problems found reading them.
+#[derive(Default)]
+struct Settings {
+    values: BTreeMap<String, (Value, String)>,
+    problems: Vec<String>,
+}
+
+impl Settings {
+    fn add_table(&mut self, prefix: &str, table: &Table, source: &str) {
+        for (key, value) in table {
+            match value {
+                Value::Table(table) => self.add_table(&format!("{}{}.", prefix, key), table, source),
+                value => self.add(&format!("{}{}", prefix, key), value.clone(), source),
+            }
+        }
+    }
+
+    fn add(&mut self, name: &str, value: Value, source: &str) {
+        if SETTINGS.iter().any(|(setting, _)| *setting == name) {
+            self.values.insert(name.to_string(), (value, source.to_string()));
+        } else {
+            self.problems.push(format!("Unknown setting '{}' in {}.", name, source));
+        }
+    }
+
+    /// The setting, or `None` when it is not given or cannot be read as a `T`.
+    fn optional<T: FromStr>(&mut self, name: &str, expected: &str) -> Option<T> {
+        let (value, source) = self.values.get(name)?;
+        let text = match value {
+            Value::String(s) => s.clone(),
+            Value::Integer(i) => i.to_string(),
+            Value::Boolean(b) => b.to_string(),
+            Value::Array(values) => values.iter().map(|v| v.as_str().map_or(v.to_string(), str::to_string)).collect::<Vec<_>>().join(","),
+            other => other.to_string(),
+        };
+        match T::from_str(text.trim()) {
+            Ok(t) => Some(t),
+            Err(_) => {
+                let problem = format!("{} (from {}) must be {}, not '{}'.", name, source, expected, text);
+                self.problems.push(problem);
+                None
+            }
+        }
+    }
+
+    /// As `optional`, recording a problem when the setting is not given.
+    fn required<T: FromStr>(&mut self, name: &str, expected: &str) -> Option<T> {
+        if !self.values.contains_key(name) {
+            let var = SETTINGS.iter().find(|(setting, _)| *setting == name).map_or("", |(_, var)| var);
+            self.problems.push(format!("{} must be set, in the configuration file, {} or --{}=.", name, var, name));
+        }
+        self.optional(name, expected)
+    }
+
+    fn check(&mut self, name: &str, valid: bool, expected: &str) {
+        if !valid {
+            let source = self.values.get(name).map_or("the default", |(_, source)| source.as_str());
+            self.problems.push(format!("{} (from {}) must be {}.", name, source, expected));
+        }
+    }
+
+    fn read(&mut self) -> AppConfig {
+        let url: Option<String> = self.required("database.url", "a database url");
+        let pool_size: Option<u32> = self.required("database.pool_size", "a positive integer");
+        let checkout_timeout_ms = self.optional("database.checkout_timeout_ms", "a positive integer").unwrap_or(DEFAULT_CHECKOUT_TIMEOUT_MS);
+        self.check("database.url", url.as_ref().is_none_or(|url| !url.trim().is_empty()), "a database url");
+        self.check("database.pool_size", pool_size != Some(0), "a positive integer");
+        self.check("database.checkout_timeout_ms", checkout_timeout_ms > 0, "a positive integer");
+        let database = DatabaseConfig {
+            url: url.unwrap_or_default(),
+            pool_size: pool_size.unwrap_or_default(),
+            checkout_timeout_ms,
+            migrations: self.optional("database.migrations", "apply, verify or refuse").unwrap_or(MigrationMode::Apply),
+        };
+
+        let tls = match (
+            self.optional::<String>("server.tls.cert_file", "a file"),
+            self.optional::<String>("server.tls.key_file", "a file"),
+        ) {
+            (None, None) => None,
+            (Some(cert_file), Some(key_file)) => Some(TlsConfig { cert_file, key_file }),
+            _ => {
+                self.problems.push("server.tls.cert_file and server.tls.key_file must be set together.".to_string());
+                None
+            }
+        };
+        if let Some(Err(e)) = tls.as_ref().map(TlsConfig::server_config) {
+            self.problems.push(e);
+        }
+        let server = ServerConfig {
+            host: self.required("server.host", "a host name or address").unwrap_or_default(),
+            port: self.required("server.port", "a port number").unwrap_or_default(),
+            workers: self.optional("server.workers", "a positive integer"),
+            tls,
+        };
+        self.check("server.workers", server.workers != Some(0), "a positive integer");
+
+        let jwt_algorithm: Option<Algorithm> = self.required("auth.jwt_algorithm", "HS256 or RS256");
+        let auth = AuthConfig {
+            jwt_algorithm: jwt_algorithm.unwrap_or_default(),
+            jwt_secret: self.optional("auth.jwt_secret", "a secret"),
+            jwt_public_key_file: self.optional("auth.jwt_public_key_file", "a file"),
+            admins: self
+                .optional::<String>("auth.admins", "a list of users")
+                .map(|admins| admins.split(',').map(|admin| admin.trim().to_string()).filter(|admin| !admin.is_empty()).collect())
+                .unwrap_or_default(),
+        };
+        let valid = |admin: &String| admin.split_once(':').is_some_and(|(source, id)| !source.is_empty() && !id.is_empty());
+        self.check("auth.admins", auth.admins.iter().all(valid), "a list of users, each written SOURCE:SOURCE_ID");
+        if let Some(Err(e)) = jwt_algorithm.map(|_| JwtAuthenticator::from_config(&auth)) {
+            self.problems.push(e);
+        }
+
+        let paging = PagingConfig {
+            default_rows: self.optional("paging.default_rows", "a positive integer").unwrap_or(10),
+            default_item_rows: self.optional("paging.default_item_rows", "a positive integer").unwrap_or(100),
+            max_rows: self.optional("paging.max_rows", "a positive integer").unwrap_or(1000),
+        };
+        self.check("paging.default_rows", (1..=paging.max_rows).contains(&paging.default_rows), "from 1 to paging.max_rows");
+        self.check("paging.default_item_rows", (1..=paging.max_rows).contains(&paging.default_item_rows), "from 1 to paging.max_rows");
+
+        let features = FeatureConfig {
+            search: self.optional("features.search", "true or false").unwrap_or(true),
+            admin: self.optional("features.admin", "true or false").unwrap_or(true),
+        };
+
+        let definitions_file: Option<String> = self.optional("rollups.definitions_file", "a file");
+        let specification = match definitions_file.as_deref().map(RollupSpecification::from_file) {
+            Some(Ok(specification)) => specification,
+            Some(Err(e)) => {
+                self.problems.push(format!("rollups.definitions_file '{}' {}.", definitions_file.as_deref().unwrap_or_default(), e));
+                RollupSpecification::default()
+            }
+            None => RollupSpecification::default(),
+        };
+        let currency_rates = match self.optional::<String>("rollups.currency_rates", "rates").as_deref().map(StaticRateConverter::parse) {
+            Some(Ok(rates)) => Some(rates),
+            Some(Err(e)) => {
+                self.problems.push(format!("rollups.currency_rates must be written as USD=1.00,EUR=1.08: {}.", e));
+                None
+            }
+            None => None,
+        };
+        let rollups = RollupConfig { definitions_file, specification, currency_rates };
+
+        let log_level: String = self.optional("log_level", "a log filter").unwrap_or("warn".to_string());
+        self.check("log_level", EnvFilter::try_new(&log_level).is_ok(), "a log filter such as info or list_management=debug");
+
+        AppConfig { database, server, auth, paging, features, rollups, log_level }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn test_load_layers() {
+        let mut file = tempfile("layers.toml");
+        writeln!(file.1, r#"
+            log_level = "info"
+            [database]
+            url = "file.db"
+            pool_size = 4
+            [server]
+            host = "0.0.0.0"
+            port = 8000
+            workers = 2
+            [paging]
+            max_rows = 500
+            [features]
+            search = false
+            [rollups]
+            currency_rates = ["USD=1", "EUR=1.25"]
+        "#).unwrap();
+        let mut definitions = tempfile("layers.json");
+        writeln!(definitions.1, r#"{{"by_list": {{"7": []}}}}"#).unwrap();
+        let env = HashMap::from([
+            ("CONFIG_FILE", file.0.clone()),
+            ("SERVER_PORT", "8001".to_string()),
+            ("DATABASE_MIGRATIONS", "verify".to_string()),
+            ("AUTH_JWT_ALGORITHM", "HS256".to_string()),
+            ("AUTH_JWT_SECRET", "secret".to_string()),
+            ("AUTH_ADMINS", "s1:s1-1, s2:s2-1".to_string()),
+            ("ROLLUP_DEFINITIONS_FILE", definitions.0.clone()),
+        ]);
+        let args = vec!["--server.port=8002".to_string(), "--paging.default_rows=5".to_string()];
+
+        let config = AppConfig::load_with(&args, |name| env.get(name).cloned()).unwrap();
+        assert_eq!("file.db", config.database.url);
+        assert_eq!(4, config.database.pool_size);
+        assert_eq!(DEFAULT_CHECKOUT_TIMEOUT_MS, config.database.checkout_timeout_ms);
+        assert_eq!(MigrationMode::Verify, config.database.migrations);
+        assert_eq!("0.0.0.0", config.server.host);
+        assert_eq!(8002, config.server.port);
+        assert_eq!(Some(2), config.server.workers);
+        assert_eq!(None, config.server.tls);
+        assert_eq!(Some("secret".to_string()), config.auth.jwt_secret);
+        assert_eq!(vec!["s1:s1-1".to_string(), "s2:s2-1".to_string()], config.auth.admins);
+        assert_eq!(PagingConfig { default_rows: 5, default_item_rows: 100, max_rows: 500 }, config.paging);
+        assert!(!config.features.search);
+        assert!(config.features.admin);
+        assert_eq!(Some(definitions.0), config.rollups.definitions_file);
+        assert_eq!(Some(&vec![]), config.rollups.specification.by_list.get(&7));
+        assert_eq!(StaticRateConverter::parse("USD=1,EUR=1.25").ok(), config.rollups.currency_rates);
+        assert_eq!("info", config.log_level);
+    }
+
+    #[test]
+    fn test_load_reports_every_problem() {
+        let mut file = tempfile("problems.toml");
+        writeln!(file.1, r#"
+            [server]
+            prot = 8000
+            workers = 0
+            [auth]
+            admins = ["s1:s1-1", "s2"]
+            [paging]
+            max_rows = 5
+        "#).unwrap();
+        let env = HashMap::from([
+            ("DATABASE_POOL_SIZE", "many".to_string()),
+            ("AUTH_JWT_ALGORITHM", "RS256".to_string()),
+            ("SERVER_TLS_CERT_FILE", "cert.pem".to_string()),
+            ("ROLLUP_DEFINITIONS_FILE", "test_resources/missing.json".to_string()),
+            ("CURRENCY_RATES", "USD=1,EUR".to_string()),
+        ]);
+        let args = vec![format!("--config={}", file.0), "--server.port=http".to_string(), "verbose".to_string()];
+
+        let problems = AppConfig::load_with(&args, |name| env.get(name).cloned()).unwrap_err().problems;
+        for expected in [
+            "The flag 'verbose'",
+            "Unknown setting 'server.prot'",
+            "database.url must be set",
+            "database.pool_size (from DATABASE_POOL_SIZE) must be a positive integer, not 'many'",
+            "server.tls.cert_file and server.tls.key_file must be set together",
+            "server.host must be set",
+            "server.port (from --server.port) must be a port number, not 'http'",
+            "server.workers",
+            "auth.jwt_public_key_file must be set for RS256",
+            "auth.admins (from",
+            "paging.default_rows (from the default) must be from 1 to paging.max_rows",
+            "paging.default_item_rows (from the default) must be from 1 to paging.max_rows",
+            "rollups.definitions_file 'test_resources/missing.json' could not be read",
+            "rollups.currency_rates must be written as USD=1.00,EUR=1.08: 'EUR' is not written as CURRENCY=RATE",
+        ] {
+            assert!(problems.iter().any(|p| p.starts_with(expected)), "{}: {:?}", expected, problems);
+        }
+        assert_eq!(14, problems.len(), "{:?}", problems);
+    }
+
+    #[test]
+    fn test_tls() {
+        let env = HashMap::from([
+            ("DATABASE_URL", "file.db".to_string()),
+            ("DATABASE_POOL_SIZE", "1".to_string()),
+            ("SERVER_HOST", "localhost".to_string()),
+            ("SERVER_PORT", "8443".to_string()),
+            ("AUTH_JWT_ALGORITHM", "RS256".to_string()),
+            ("AUTH_JWT_PUBLIC_KEY_FILE", "test_resources/jwt_rs256_public.pem".to_string()),
+        ]);
+        let load = |cert_file: &str, key_file: &str| {
+            let args = vec![format!("--server.tls.cert_file={}", cert_file), format!("--server.tls.key_file={}", key_file)];
+            AppConfig::load_with(&args, |name| env.get(name).cloned())
+        };
+
+        let tls = load("test_resources/tls_cert.pem", "test_resources/tls_key.pem").unwrap().server.tls.unwrap();
+        assert_eq!("test_resources/tls_cert.pem", tls.cert_file);
+        assert!(tls.server_config().is_ok());
+
+        for (cert_file, key_file, expected) in [
+            ("test_resources/missing.pem", "test_resources/tls_key.pem", "Could not read server.tls.cert_file"),
+            ("test_resources/jwt_rs256_public.pem", "test_resources/tls_key.pem", "server.tls.cert_file 'test_resources/jwt_rs256_public.pem' holds no certificates"),
+            ("test_resources/tls_cert.pem", "test_resources/tls_cert.pem", "server.tls.key_file 'test_resources/tls_cert.pem' holds no private key"),
+            ("test_resources/tls_cert.pem", "test_resources/jwt_rs256_private.pem", "The TLS certificate and key cannot be used"),
+        ] {
+            let problems = load(cert_file, key_file).unwrap_err().problems;
+            assert_eq!(1, problems.len(), "{:?}", problems);
+            assert!(problems[0].starts_with(expected), "{}", problems[0]);
+        }
+    }
+
+    fn tempfile(name: &str) -> (String, File) {
+        let path = env::temp_dir().join(format!("list_management_{}_{}", std::process::id(), name));
+        (path.to_str().unwrap().to_string(), File::create(&path).unwrap())
+    }
+}
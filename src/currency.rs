@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+
+/// Converts amounts between currencies, which are named as in `Price.source`.  The default is a
+/// `StaticRateConverter` with the rates of `rollups.currency_rates`; an application may use
+/// another by setting the `currency_converter` of its `AppState`.
+pub trait CurrencyConverter: Send + Sync {
+    /// The amount in the `to` currency, or `None` when there is no rate between the two.
+    fn convert(&self, amount: Decimal, from: &str, to: &str) -> Option<Decimal>;
+}
+
+/// Converts at fixed rates, each being the value of one unit of a currency in some common base.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaticRateConverter {
+    rates: HashMap<String, Decimal>,
+}
+
+impl StaticRateConverter {
+    pub fn new(rates: HashMap<String, Decimal>) -> StaticRateConverter {
+        StaticRateConverter { rates }
+    }
+
+    /// Reads rates written as `USD=1.00,EUR=1.08,GBP=1.27`.
+    pub fn parse(rates: &str) -> Result<StaticRateConverter, String> {
+        let mut parsed = HashMap::new();
+        for pair in rates.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (currency, rate) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("'{}' is not written as CURRENCY=RATE", pair))?;
+            let rate = Decimal::from_str(rate.trim())
+                .ok()
+                .filter(|rate| rate.is_sign_positive() && !rate.is_zero())
+                .ok_or_else(|| format!("the rate for '{}' must be a positive number", currency.trim()))?;
+            parsed.insert(currency.trim().to_string(), rate);
+        }
+        Ok(StaticRateConverter::new(parsed))
+    }
+}
+
+impl CurrencyConverter for StaticRateConverter {
+    fn convert(&self, amount: Decimal, from: &str, to: &str) -> Option<Decimal> {
+        if from == to {
+            return Some(amount);
+        }
+        amount
+            .checked_mul(*self.rates.get(from)?)?
+            .checked_div(*self.rates.get(to)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_rates() {
+        let converter = StaticRateConverter::parse("USD=1, EUR=1.25,GBP=2").unwrap();
+        let amount = Decimal::from_str("10.00").unwrap();
+        assert_eq!(Some(Decimal::from_str("12.50").unwrap()), converter.convert(amount, "EUR", "USD"));
+        assert_eq!(Some(Decimal::from_str("6.25").unwrap()), converter.convert(amount, "EUR", "GBP"));
+        assert_eq!(Some(amount), converter.convert(amount, "JPY", "JPY"));
+        assert_eq!(None, converter.convert(amount, "JPY", "USD"));
+        assert_eq!(None, converter.convert(amount, "USD", "JPY"));
+
+        assert!(StaticRateConverter::parse("").unwrap().rates.is_empty());
+        assert!(StaticRateConverter::parse("USD").is_err());
+        assert!(StaticRateConverter::parse("USD=one").is_err());
+        assert!(StaticRateConverter::parse("USD=0").is_err());
+        assert!(StaticRateConverter::parse("USD=-1").is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+
+use crate::common::{ItemSortRequest, ListAttribute, ListManagementError, SortRequest};
+
+/// Where a page of lists ended: the sort value and id of its last list.  The value is `None` when
+/// sorting by id, or by an attribute the last list does not have.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ListCursor {
+    pub(crate) id: u64,
+    pub(crate) sort: SortRequest,
+    pub(crate) value: Option<ListAttribute>,
+}
+
+/// Where a page of a list's items ended.  Items are always in position order.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ItemCursor {
+    pub(crate) id: u64,
+    pub(crate) list_id: u64,
+    pub(crate) position: i32,
+}
+
+/// Where a page of items found across lists ended: the sort value and id of its last item.  The
+/// value is `None` when sorting by id.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub(crate) struct FoundItemCursor {
+    pub(crate) id: u64,
+    pub(crate) sort: ItemSortRequest,
+    pub(crate) value: Option<ListAttribute>,
+}
+
+/// Cursors are handed to clients as opaque strings.
+pub(crate) fn encode<T: Serialize>(cursor: &T) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap())
+}
+
+pub(crate) fn decode<T: DeserializeOwned>(cursor: &str) -> Result<T, ListManagementError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| ListManagementError::Validation(format!("'{}' is not a valid cursor", cursor)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::SortKey;
+
+    use super::*;
+
+    #[test]
+    fn test_encode_and_decode() {
+        let cursor = ListCursor {
+            id: 3,
+            sort: SortRequest {
+                descending: true,
+                key: SortKey::Attribute("my text".to_string()),
+            },
+            value: Some(ListAttribute::Text("default A3 Naming".to_string())),
+        };
+        let encoded = encode(&cursor);
+        assert!(!encoded.contains(' '));
+        assert_eq!(cursor, decode::<ListCursor>(&encoded).unwrap());
+
+        assert!(matches!(decode::<ListCursor>("not a cursor"), Err(ListManagementError::Validation(_))));
+        assert!(matches!(decode::<ItemCursor>(&encoded), Err(ListManagementError::Validation(_))));
+    }
+}
@@ -1,43 +1,298 @@
-use std::env;
+use std::cmp::Ordering;
+use std::str::FromStr;
+use std::time::Duration;
 
+use actix_web::web;
+use diesel::connection::SimpleConnection;
+use diesel::migration::MigrationVersion;
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql};
 use diesel::prelude::*;
-use diesel::r2d2::{ConnectionManager, PooledConnection};
+use diesel::r2d2::{Error, ManageConnection, PooledConnection, R2D2Connection};
 use diesel::r2d2::Pool;
-use dotenvy::dotenv;
+use diesel::serialize::{self, IsNull, ToSql};
+use diesel::sql_types::{HasSqlType, Text};
+use diesel::sqlite::Sqlite;
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use lazy_static::lazy_static;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+use tracing::{error, info, warn};
+
+use crate::common::ListManagementError;
+use crate::config::{AppConfig, DatabaseConfig};
+use crate::schema::sql_types::Amount;
 
 #[derive(diesel::MultiConnection)]
 pub enum MultiConnection {
+    #[cfg(feature = "postgres")]
+    Postgres(diesel::PgConnection),
     Sqlite(diesel::SqliteConnection),
 }
 
+const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
+#[cfg(feature = "postgres")]
+const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations_postgres");
+
 lazy_static! {
-    static ref POOL: Pool<ConnectionManager<MultiConnection>> = get_connection_pool();
+    /// The pool configured by the environment, for applications that do not build their own.
+    static ref POOL: DbPool = build_pool(&AppConfig::from_env().database).expect("Could not build connection pool");
+}
+
+pub type DbPool = Pool<DbConnectionManager>;
+
+pub type DbConnection = PooledConnection<DbConnectionManager>;
+
+/// The pool configured by the environment.
+pub fn default_pool() -> &'static DbPool {
+    &POOL
+}
+
+/// Checks out a connection from the default pool.
+pub fn connection() -> Result<DbConnection, ListManagementError> {
+    check_out(&POOL)
+}
+
+/// Checks out a connection, waiting no longer than the pool's checkout timeout for one to be
+/// free.
+pub fn check_out(pool: &DbPool) -> Result<DbConnection, ListManagementError> {
+    pool.get().map_err(|e| {
+        warn!("Could not check out a database connection: {}", e);
+        ListManagementError::Unavailable("no database connection is free; try again later".to_string())
+    })
+}
+
+/// Runs database work on actix's blocking thread pool, so that a request waiting on the database
+/// does not hold up the other requests on its worker.  Work the pool could not finish, as when it
+/// panicked, is logged and reported as `Other`.
+pub(crate) async fn blocking<T, F>(work: F) -> Result<T, ListManagementError>
+where
+    F: FnOnce() -> Result<T, ListManagementError> + Send + 'static,
+    T: Send + 'static,
+{
+    web::block(work).await.map_err(|e| {
+        error!("Could not finish database work: {}", e);
+        ListManagementError::Other
+    })?
+}
+
+/// The name of the SQLite collation that orders `Amount` columns, which hold the amounts' text, by
+/// their value.  It is registered on every connection, as SQLite cannot use a table that names it
+/// otherwise.
+pub(crate) const AMOUNT_COLLATION: &str = "amount";
+
+/// Orders two amounts written as text by their value, so that "9" comes before "10" and "1.1"
+/// equals "1.10".  Text that is not an amount, which is never stored, comes after every amount.
+fn compare_amounts(a: &str, b: &str) -> Ordering {
+    match (Decimal::from_str(a), Decimal::from_str(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Less,
+        (Err(_), Ok(_)) => Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// An amount as stored in an `Amount` column.
+#[derive(diesel::AsExpression, diesel::FromSqlRow, Clone, Copy, Debug, PartialEq)]
+#[diesel(sql_type = Amount)]
+pub struct DbAmount(pub Decimal);
+
+impl HasSqlType<Amount> for MultiBackend {
+    fn metadata(lookup: &mut Self::MetadataLookup) -> Self::TypeMetadata {
+        MultiBackend::lookup_sql_type::<Amount>(lookup)
+    }
 }
 
-pub fn connection() -> PooledConnection<ConnectionManager<MultiConnection>> {
-    POOL.get().unwrap()
+impl FromSql<Amount, MultiBackend> for DbAmount {
+    fn from_sql(bytes: <MultiBackend as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        bytes.from_sql::<DbAmount, Amount>()
+    }
 }
 
-fn get_connection_pool() -> Pool<ConnectionManager<MultiConnection>> {
-    dotenv().ok();
+impl ToSql<Amount, MultiBackend> for DbAmount {
+    fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, MultiBackend>) -> serialize::Result {
+        out.set_value((Amount, self));
+        Ok(IsNull::No)
+    }
+}
 
-    let url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let pool_size_str = env::var("DATABASE_POOL_SIZE").expect("DATABASE_POOL_SIZE must be set");
-    let pool_size: u32 = pool_size_str
-        .parse()
-        .expect("DATABASE_POOL_SIZE must be a positive integer");
+impl FromSql<Amount, Sqlite> for DbAmount {
+    fn from_sql(bytes: <Sqlite as Backend>::RawValue<'_>) -> deserialize::Result<Self> {
+        let text = <String as FromSql<Text, Sqlite>>::from_sql(bytes)?;
+        Decimal::from_str(&text).map(DbAmount).map_err(|e| format!("the stored amount '{}' is not a decimal: {}", text, e).into())
+    }
+}
+
+impl ToSql<Amount, Sqlite> for DbAmount {
+    fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, Sqlite>) -> serialize::Result {
+        out.set_value(self.0.to_string());
+        Ok(IsNull::No)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl FromSql<Amount, diesel::pg::Pg> for DbAmount {
+    fn from_sql(bytes: diesel::pg::PgValue<'_>) -> deserialize::Result<Self> {
+        <Decimal as FromSql<diesel::sql_types::Numeric, diesel::pg::Pg>>::from_sql(bytes).map(DbAmount)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl ToSql<Amount, diesel::pg::Pg> for DbAmount {
+    fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, diesel::pg::Pg>) -> serialize::Result {
+        <Decimal as ToSql<diesel::sql_types::Numeric, diesel::pg::Pg>>::to_sql(&self.0, out)
+    }
+}
+
+/// The migrations written for the connection's backend.
+pub(crate) fn migrations(c: &MultiConnection) -> EmbeddedMigrations {
+    match c {
+        #[cfg(feature = "postgres")]
+        MultiConnection::Postgres(_) => POSTGRES_MIGRATIONS,
+        MultiConnection::Sqlite(_) => SQLITE_MIGRATIONS,
+    }
+}
+
+/// What to do at startup about migrations that have not been run, as set by
+/// `DATABASE_MIGRATIONS`.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[strum(serialize_all = "lowercase")]
+pub enum MigrationMode {
+    /// Run them.
+    Apply,
+    /// Log them and start anyway.
+    Verify,
+    /// Refuse to start.
+    Refuse,
+}
+
+/// The newest migration run, and those not yet run, by version.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct SchemaVersion {
+    pub version: Option<String>,
+    pub pending: Vec<String>,
+}
+
+/// Deals with pending migrations as the mode says, returning the versions of those it ran.
+pub fn migrate(pool: &DbPool, mode: MigrationMode) -> Result<Vec<String>, ListManagementError> {
+    let mut c = check_out(pool)?;
+    let pending: Vec<String> = c
+        .pending_migrations(migrations(&c))
+        .map_err(migration_error)?
+        .iter()
+        .map(|m| m.name().version().to_string())
+        .collect();
+    if pending.is_empty() {
+        info!("The database schema is up to date");
+        return Ok(vec![]);
+    }
+    match mode {
+        MigrationMode::Apply => {
+            let ran: Vec<String> = c
+                .run_pending_migrations(migrations(&c))
+                .map_err(migration_error)?
+                .iter()
+                .map(MigrationVersion::to_string)
+                .collect();
+            for version in &ran {
+                info!("Ran migration {}", version);
+            }
+            Ok(ran)
+        }
+        MigrationMode::Verify => {
+            warn!("Migrations have not been run: {}", pending.join(", "));
+            Ok(vec![])
+        }
+        MigrationMode::Refuse => Err(ListManagementError::Migration(format!(
+            "migrations have not been run: {}",
+            pending.join(", ")
+        ))),
+    }
+}
+
+pub fn schema_version(pool: &DbPool) -> Result<SchemaVersion, ListManagementError> {
+    let mut c = check_out(pool)?;
+    let version = c
+        .applied_migrations()
+        .map_err(migration_error)?
+        .into_iter()
+        .max()
+        .map(|v| v.to_string());
+    let pending = c
+        .pending_migrations(migrations(&c))
+        .map_err(migration_error)?
+        .iter()
+        .map(|m| m.name().version().to_string())
+        .collect();
+    Ok(SchemaVersion { version, pending })
+}
+
+fn migration_error(e: Box<dyn std::error::Error + Send + Sync>) -> ListManagementError {
+    ListManagementError::Migration(e.to_string())
+}
+
+/// Connects to the backend named by the database url's scheme: `postgres://` or `postgresql://`
+/// for Postgres (with the `postgres` feature), and anything else is a SQLite file.
+pub struct DbConnectionManager {
+    database_url: String,
+}
+
+impl DbConnectionManager {
+    fn establish(&self) -> ConnectionResult<MultiConnection> {
+        if self.database_url.starts_with("postgres://") || self.database_url.starts_with("postgresql://") {
+            #[cfg(feature = "postgres")]
+            return Ok(MultiConnection::Postgres(diesel::PgConnection::establish(&self.database_url)?));
+            #[cfg(not(feature = "postgres"))]
+            return Err(ConnectionError::InvalidConnectionUrl(
+                "Postgres urls require the postgres feature".to_string(),
+            ));
+        }
+        // SQLite only enforces foreign keys when asked to, once per connection.
+        let mut c = diesel::SqliteConnection::establish(&self.database_url)?;
+        c.batch_execute("PRAGMA foreign_keys = ON")
+            .map_err(|e| ConnectionError::BadConnection(e.to_string()))?;
+        // Diesel passes the collation the right-hand text first.
+        c.register_collation(AMOUNT_COLLATION, |rhs, lhs| compare_amounts(lhs, rhs))
+            .map_err(|e| ConnectionError::BadConnection(e.to_string()))?;
+        Ok(MultiConnection::Sqlite(c))
+    }
+}
+
+impl ManageConnection for DbConnectionManager {
+    type Connection = MultiConnection;
+    type Error = Error;
+
+    fn connect(&self) -> Result<MultiConnection, Error> {
+        self.establish().map_err(Error::ConnectionError)
+    }
+
+    fn is_valid(&self, conn: &mut MultiConnection) -> Result<(), Error> {
+        conn.ping().map_err(Error::QueryError)
+    }
 
-    let manager = ConnectionManager::<MultiConnection>::new(url);
+    fn has_broken(&self, conn: &mut MultiConnection) -> bool {
+        std::thread::panicking() || conn.is_broken()
+    }
+}
+
+/// Connects to the configured database.  Fails when the pool cannot open its first connections
+/// within the checkout timeout.
+pub fn build_pool(config: &DatabaseConfig) -> Result<DbPool, ListManagementError> {
+    let manager = DbConnectionManager { database_url: config.url.clone() };
     Pool::builder()
-        .max_size(pool_size)
+        .max_size(config.pool_size)
+        .connection_timeout(Duration::from_millis(config.checkout_timeout_ms))
         .test_on_check_out(true)
         .build(manager)
-        .expect("Could not build connection pool")
+        .map_err(|e| ListManagementError::Unavailable(format!("could not connect to the database: {}", e)))
 }
 
 #[cfg(test)]
 pub mod tests {
+    use std::str::FromStr;
+
     use chrono::NaiveDate;
     use serial_test::serial;
 
@@ -48,7 +303,7 @@ pub mod tests {
     };
     use crate::schema::{
         item_list, item_list_attribute, list_item,
-        list_item_attribute,
+        list_item_attribute, user,
     };
     use crate::test_helpers::{insert_item_list, insert_list_item, insert_user, setup_db};
 
@@ -59,7 +314,7 @@ pub mod tests {
     fn test_item_lists() {
         setup_db();
         let user_id = insert_user("name","a", "b");
-        let c = &mut db::connection();
+        let c = &mut db::connection().unwrap();
         let item_list_id_1 = insert_item_list(c, user_id, "Item List One".to_string());
         let item_list_id_2 = insert_item_list(c, user_id, "Item List Two".to_string());
         let item_list_id_3 = insert_item_list(c, user_id, "Item List Three".to_string());
@@ -95,12 +350,178 @@ pub mod tests {
         assert_eq!("Item List One", results[1].name);
     }
 
+    #[test]
+    #[serial]
+    fn test_check_out_timeout() {
+        setup_db();
+        let pool = build_pool(&DatabaseConfig {
+            pool_size: 1,
+            checkout_timeout_ms: 50,
+            ..AppConfig::from_env().database
+        }).unwrap();
+
+        let held = check_out(&pool).unwrap();
+        assert!(matches!(check_out(&pool), Err(ListManagementError::Unavailable(_))));
+        drop(held);
+        assert!(check_out(&pool).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_blocking_not_finished() {
+        let result: Result<(), ListManagementError> = blocking(|| panic!("the work failed")).await;
+        assert!(matches!(result, Err(ListManagementError::Other)));
+    }
+
+    #[test]
+    #[serial]
+    fn test_migrate() {
+        setup_db();
+        let current = schema_version(default_pool()).unwrap();
+        assert!(current.pending.is_empty());
+        assert!(migrate(default_pool(), MigrationMode::Refuse).unwrap().is_empty());
+
+        {
+            let mut c = db::connection().unwrap();
+            c.revert_last_migration(migrations(&c)).unwrap();
+        }
+        let reverted = schema_version(default_pool()).unwrap();
+        assert_eq!(current.version.clone().into_iter().collect::<Vec<_>>(), reverted.pending);
+        assert_ne!(current.version, reverted.version);
+
+        assert!(matches!(migrate(default_pool(), MigrationMode::Refuse), Err(ListManagementError::Migration(_))));
+        assert!(migrate(default_pool(), MigrationMode::Verify).unwrap().is_empty());
+        assert_eq!(reverted, schema_version(default_pool()).unwrap());
+        assert_eq!(reverted.pending, migrate(default_pool(), MigrationMode::Apply).unwrap());
+        assert_eq!(current, schema_version(default_pool()).unwrap());
+    }
+
+    #[test]
+    #[serial]
+    fn test_price_migration() {
+        setup_db();
+        let user_id = insert_user("name", "a", "b");
+        let mut c = db::connection().unwrap();
+        let item_list_id = insert_item_list(&mut c, user_id, "Item List One".to_string());
+        while c.applied_migrations().unwrap().iter().any(|v| v.to_string() == "v0003") {
+            c.revert_last_migration(migrations(&c)).unwrap();
+        }
+        let legacy = [
+            ("price", "PRICE: _12.50 _Corner Shop"),
+            ("unreadable", "twelve fifty"),
+            ("refund", "PRICE: _-12.000001 _Corner Shop"),
+            ("precise", "PRICE: _12345678901.1234567 _Corner Shop"),
+            ("words", "PRICE: _twelve _Corner Shop"),
+            ("points", "PRICE: _1.2.3 _Corner Shop"),
+            ("large", "PRICE: _9223372036855 _Corner Shop"),
+        ];
+        for (name, text_val) in legacy {
+            diesel::sql_query("INSERT INTO item_list_attribute (item_list_id, name, type, text_val) VALUES ($1, $2, 'Price', $3)")
+                .bind::<diesel::sql_types::Integer, _>(item_list_id)
+                .bind::<diesel::sql_types::Text, _>(name)
+                .bind::<diesel::sql_types::Text, _>(text_val)
+                .execute(&mut c)
+                .unwrap();
+        }
+        c.run_pending_migrations(migrations(&c)).unwrap();
+
+        let results: Vec<ItemListAttributeDb> = item_list_attribute::table
+            .select(ItemListAttributeDb::as_select())
+            .order(item_list_attribute::id.asc())
+            .load(&mut c)
+            .unwrap();
+        let amount = |text: &str| Some(DbAmount(Decimal::from_str(text).unwrap()));
+        assert_eq!("Price", results[0].attribute_type);
+        assert_eq!(amount("12.50"), results[0].price_amount);
+        assert_eq!(Some("Corner Shop".to_string()), results[0].price_source);
+        assert_eq!(None, results[0].text_val);
+        assert_eq!("Price", results[2].attribute_type);
+        assert_eq!(amount("-12.000001"), results[2].price_amount);
+        // Prices that cannot be stored exactly are kept as Text, as they were written.
+        for i in [1, 3, 4, 5, 6] {
+            assert_eq!("Text", results[i].attribute_type, "{}", legacy[i].0);
+            assert_eq!(Some(legacy[i].1.to_string()), results[i].text_val);
+            assert_eq!(None, results[i].price_amount);
+            assert_eq!(None, results[i].price_source);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_amount_text_migration() {
+        setup_db();
+        let user_id = insert_user("name", "a", "b");
+        let mut c = db::connection().unwrap();
+        let item_list_id = insert_item_list(&mut c, user_id, "Item List One".to_string());
+        while c.applied_migrations().unwrap().iter().any(|v| v.to_string() == "v0010") {
+            c.revert_last_migration(migrations(&c)).unwrap();
+        }
+        // Amounts were whole numbers of millionths.
+        let units: [i64; 5] = [12_500_000, -1, 0, 10_000_000, -9_223_372_036_854_775_807];
+        for units in units {
+            diesel::sql_query("INSERT INTO item_list_attribute (item_list_id, name, type, price_amount, price_source) VALUES ($1, 'price', 'Price', $2, 'Corner Shop')")
+                .bind::<diesel::sql_types::Integer, _>(item_list_id)
+                .bind::<diesel::sql_types::BigInt, _>(units)
+                .execute(&mut c)
+                .unwrap();
+        }
+        c.run_pending_migrations(migrations(&c)).unwrap();
+
+        let amounts: Vec<String> = item_list_attribute::table
+            .select(item_list_attribute::price_amount.assume_not_null())
+            .order(item_list_attribute::id.asc())
+            .load::<DbAmount>(&mut c)
+            .unwrap()
+            .iter()
+            .map(|amount| amount.0.to_string())
+            .collect();
+        assert_eq!(vec!["12.5", "-0.000001", "0", "10", "-9223372036854.775807"], amounts);
+    }
+
+    #[test]
+    #[serial]
+    fn test_foreign_keys() {
+        setup_db();
+        let user_id = insert_user("name", "a", "b");
+        let c = &mut db::connection().unwrap();
+        let item_list_id = insert_item_list(c, user_id, "Item List One".to_string());
+        let list_item_id = insert_list_item(c, item_list_id, "List Item One".to_string());
+        diesel::insert_into(list_item_attribute::table)
+            .values((
+                list_item_attribute::list_item_id.eq(&list_item_id),
+                list_item_attribute::attribute_type.eq("Integer"),
+                list_item_attribute::name.eq("My Integer"),
+                list_item_attribute::integer_val.eq(123),
+            ))
+            .execute(c)
+            .unwrap();
+
+        let orphan = diesel::insert_into(list_item::table)
+            .values((
+                list_item::item_list_id.eq(item_list_id + 1),
+                list_item::name.eq("Orphan"),
+                list_item::source.eq("My Source"),
+            ))
+            .execute(c)
+            .map_err(ListManagementError::from);
+        assert!(matches!(&orphan, Err(ListManagementError::Conflict(m)) if !m.contains("list_item")), "{:?}", orphan);
+        let owned = diesel::delete(user::table.filter(user::id.eq(user_id)))
+            .execute(c)
+            .map_err(ListManagementError::from);
+        assert!(matches!(owned, Err(ListManagementError::Conflict(_))), "{:?}", owned);
+
+        diesel::delete(item_list::table.filter(item_list::id.eq(item_list_id)))
+            .execute(c)
+            .unwrap();
+        assert_eq!(0, list_item::table.count().get_result::<i64>(c).unwrap());
+        assert_eq!(0, list_item_attribute::table.count().get_result::<i64>(c).unwrap());
+    }
+
     #[test]
     #[serial]
     fn test_list_items() {
         setup_db();
         let user_id = insert_user("name", "a", "b");
-        let c = &mut db::connection();
+        let c = &mut db::connection().unwrap();
         let item_list_id_1 = insert_item_list(c, user_id, "Item List One".to_string());
         let list_item_id_1 = insert_list_item(c, item_list_id_1, "List Item One".to_string());
         let list_item_id_2 = insert_list_item(c, item_list_id_1, "List Item Two".to_string());
@@ -125,7 +546,7 @@ pub mod tests {
     fn test_list_item_attributes() {
         setup_db();
         let user_id = insert_user("name", "a", "b");
-        let c = &mut db::connection();
+        let c = &mut db::connection().unwrap();
         let item_list_id_1 = insert_item_list(c, user_id, "Item List One".to_string());
         let list_item_id_1 = insert_list_item(c, item_list_id_1, "List Item One".to_string());
         let july_19_2024 = NaiveDate::from_ymd_opt(2024, 7, 19).unwrap().and_hms_opt(0,0,0).unwrap();
@@ -152,7 +573,7 @@ pub mod tests {
                 list_item_attribute::list_item_id.eq(&list_item_id_1),
                 list_item_attribute::attribute_type.eq("Float"),
                 list_item_attribute::name.eq("My Float"),
-                list_item_attribute::float_val.eq(1.1f32),
+                list_item_attribute::float_val.eq(1.1f64),
             ))
             .execute(c)
             .expect("Could not insert float");
@@ -201,7 +622,7 @@ pub mod tests {
     fn test_item_list_attributes() {
         setup_db();
         let user_id = insert_user("name", "a", "b");
-        let c = &mut db::connection();
+        let c = &mut db::connection().unwrap();
 
         let item_list_id_1 = insert_item_list(c, user_id, "Item List One".to_string());
         let july_20_2024 = NaiveDate::from_ymd_opt(2024, 7, 20).unwrap().and_hms_opt(0,0,0).unwrap();
@@ -228,7 +649,7 @@ pub mod tests {
                 item_list_attribute::item_list_id.eq(&item_list_id_1),
                 item_list_attribute::attribute_type.eq("Float"),
                 item_list_attribute::name.eq("My Float"),
-                item_list_attribute::float_val.eq(1.1f32),
+                item_list_attribute::float_val.eq(1.1f64),
             ))
             .execute(c)
             .expect("Could not insert float");
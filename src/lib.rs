@@ -1,16 +1,26 @@
 
+#[allow(dead_code)]
 mod account_storage;
+pub mod app_state;
+pub mod auth;
+pub mod clock;
 pub mod common;
+pub mod config;
 mod routes;
-mod db;
+mod cursor;
+pub mod currency;
+pub mod db;
 pub mod helpers;
+pub mod list_item_service;
 pub mod list_of_lists_service;
 pub mod list_service;
 mod list_storage;
 mod models;
 mod schema;
+pub mod rollup;
 pub mod route_config;
-mod user_service;
+pub mod search;
+pub mod user_service;
 mod user_storage;
 pub mod test_helpers;
 
@@ -0,0 +1,556 @@
+use serde::{Deserialize, Serialize};
+
+use crate::common::{AttributeFilter, ItemSortKey, ItemSortRequest, ListAttribute, ListItem, ListItemUpdate, ListManagementError, LMContext, PagingRequest};
+use crate::cursor;
+use crate::cursor::{FoundItemCursor, ItemCursor};
+use crate::db::blocking;
+use crate::list_of_lists_service::ListSelector;
+use crate::list_service::{find_list, validate_attributes};
+
+/// One page of a list's items, in list order.  `total` counts all of the list's items, while
+/// `start` and `rows` echo the page that was asked for.  `next_cursor` is given whenever the page
+/// is full, and retrieves the following page when passed back.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ListItemsResponse {
+    pub total: u64,
+    pub start: u64,
+    pub rows: u64,
+    pub items: Vec<ListItem>,
+    pub next_cursor: Option<String>,
+}
+
+/// Which items to find across the user's lists.  An item must be in one of the lists selected by
+/// `limit_lists` and match every other limit given.
+#[derive(Clone, Debug)]
+pub struct ItemSelector {
+    pub limit_lists: ListSelector,
+    /// Keywords that must each match a word of the item's name, as with the list selector's
+    /// `limit_name_keywords`.
+    pub limit_name_keywords: Option<String>,
+    /// Keeps the items from any of these sources, if any are given.
+    pub limit_sources: Vec<String>,
+    /// Keeps the items whose attributes pass every one of these filters.
+    pub limit_attributes: Vec<AttributeFilter>,
+}
+
+/// An item found across lists, with the list it is in.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct FoundItem {
+    pub list_id: u64,
+    pub list_name: String,
+    pub item: ListItem,
+}
+
+/// One page of the items found across lists.  `total` counts every item matching the selector,
+/// while `start` and `rows` echo the page that was asked for.  `next_cursor` is given whenever the
+/// page is full, and retrieves the following page when passed back with the same selector and
+/// sort.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct FoundItemsResponse {
+    pub total: u64,
+    pub start: u64,
+    pub rows: u64,
+    pub items: Vec<FoundItem>,
+    pub next_cursor: Option<String>,
+}
+
+/// Finds the items matching the selector in any of the lists the user can see.
+pub fn find_items(
+    context: &impl LMContext,
+    selector: ItemSelector,
+    paging: PagingRequest,
+    sort: ItemSortRequest,
+) -> Result<FoundItemsResponse, ListManagementError> {
+    let after: Option<FoundItemCursor> = match &paging.cursor {
+        Some(c) => {
+            let after: FoundItemCursor = cursor::decode(c)?;
+            if after.sort != sort {
+                return Err(ListManagementError::Validation("the cursor was made for a different sort".to_string()));
+            }
+            Some(after)
+        }
+        None => None,
+    };
+    let user_state = context.current_user_state();
+    let total = crate::list_storage::count_user_items(context.pool(), &user_state, &selector)?;
+    let items = crate::list_storage::user_items(context.pool(), &user_state, &selector, &paging, &sort, after.as_ref())?;
+    let next_cursor = match items.last() {
+        Some(last) if items.len() as u64 == paging.rows => Some(cursor::encode(&FoundItemCursor {
+            id: last.item.id.unwrap(),
+            sort: sort.clone(),
+            value: match sort.key {
+                ItemSortKey::CreatedDate => Some(ListAttribute::DateTime(last.item.created)),
+                ItemSortKey::Id => None,
+                ItemSortKey::ModifiedDate => Some(ListAttribute::DateTime(last.item.modified)),
+                ItemSortKey::Name => Some(ListAttribute::Text(last.item.name.clone())),
+                ItemSortKey::Source => Some(ListAttribute::Text(last.item.source.clone())),
+            },
+        })),
+        _ => None,
+    };
+    Ok(FoundItemsResponse {
+        total,
+        start: paging.start,
+        rows: paging.rows,
+        items,
+        next_cursor,
+    })
+}
+
+pub async fn retrieve_items(context: &impl LMContext, list_id: u64) -> Result<Vec<ListItem>, ListManagementError> {
+    find_list(context, list_id, false).await?;
+    let pool = context.pool().clone();
+    blocking(move || crate::list_storage::all_list_items(&pool, list_id)).await
+}
+
+pub async fn retrieve_item_page(context: &impl LMContext, list_id: u64, paging: PagingRequest) -> Result<ListItemsResponse, ListManagementError> {
+    let after: Option<ItemCursor> = match &paging.cursor {
+        Some(c) => {
+            let after: ItemCursor = cursor::decode(c)?;
+            if after.list_id != list_id {
+                return Err(ListManagementError::Validation("the cursor was made for a different list".to_string()));
+            }
+            Some(after)
+        }
+        None => None,
+    };
+    find_list(context, list_id, false).await?;
+    let pool = context.pool().clone();
+    let (start, rows) = (paging.start, paging.rows);
+    let (total, (items, last)) = blocking(move || {
+        let total = crate::list_storage::count_list_items(&pool, list_id)?;
+        Ok((total, crate::list_storage::list_items(&pool, list_id, after.as_ref(), start, rows)?))
+    }).await?;
+    let next_cursor = match last {
+        Some(last) if items.len() as u64 == paging.rows => Some(cursor::encode(&last)),
+        _ => None,
+    };
+    Ok(ListItemsResponse {
+        total,
+        start: paging.start,
+        rows: paging.rows,
+        items,
+        next_cursor,
+    })
+}
+
+pub fn add_item(context: &impl LMContext, list_id: u64, item: ListItemUpdate) -> Result<ListItem, ListManagementError> {
+    validate(&item)?;
+    let name = item.name.ok_or_else(|| ListManagementError::Validation("name is required".to_string()))?;
+    let source = item.source.ok_or_else(|| ListManagementError::Validation("source is required".to_string()))?;
+    let id = crate::list_storage::insert_list_item(
+        context.pool(),
+        &context.current_user_state(),
+        list_id,
+        &name,
+        &source,
+        &item.attributes.unwrap_or_default(),
+        context.rollup_specification(),
+        context.clock().now(),
+    )?;
+    crate::list_storage::list_item(context.pool(), list_id, id)
+}
+
+/// Changes the item, failing with `PreconditionFailed` if given a `version` it is no longer at.
+pub fn update_item(context: &impl LMContext, list_id: u64, id: u64, version: Option<u64>, changes: ListItemUpdate) -> Result<ListItem, ListManagementError> {
+    validate(&changes)?;
+    crate::list_storage::update_list_item(
+        context.pool(),
+        &context.current_user_state(),
+        list_id,
+        id,
+        version,
+        &changes,
+        context.rollup_specification(),
+        context.clock().now(),
+    )?;
+    crate::list_storage::list_item(context.pool(), list_id, id)
+}
+
+/// Deletes the item, failing with `PreconditionFailed` if given a `version` it is no longer at.
+pub fn delete_item(context: &impl LMContext, list_id: u64, id: u64, version: Option<u64>) -> Result<(), ListManagementError> {
+    crate::list_storage::delete_list_item(
+        context.pool(),
+        &context.current_user_state(),
+        list_id,
+        id,
+        version,
+        context.rollup_specification(),
+        context.clock().now(),
+    )
+}
+
+/// Puts the list's items in the order given.  Every item in the list must be named exactly once.
+/// When `version` is given, the list must still be at that version.
+pub fn reorder_items(context: &impl LMContext, list_id: u64, version: Option<u64>, ids: Vec<u64>) -> Result<Vec<ListItem>, ListManagementError> {
+    crate::list_storage::reorder_list_items(context.pool(), &context.current_user_state(), list_id, version, &ids, context.clock().now())?;
+    crate::list_storage::all_list_items(context.pool(), list_id)
+}
+
+fn validate(item: &ListItemUpdate) -> Result<(), ListManagementError> {
+    if item.name.as_ref().is_some_and(|name| name.trim().is_empty()) {
+        return Err(ListManagementError::Validation("name must not be blank".to_string()));
+    }
+    if item.source.as_ref().is_some_and(|source| source.trim().is_empty()) {
+        return Err(ListManagementError::Validation("source must not be blank".to_string()));
+    }
+    validate_attributes(item.attributes.as_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+    use serial_test::serial;
+
+    use crate::common::{AttributeCondition, AttributePredicate, Comparison, ItemListRollup, ItemListUpdate, Price, UserState};
+    use crate::common::tests::{db_context, user};
+    use crate::list_service::{create_list, retrieve_list};
+    use crate::rollup::RollupFunction;
+    use crate::test_helpers::{insert_user, setup_db};
+
+    use super::*;
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_add_update_and_delete_items() {
+        let context = &db_context(user(), db_state());
+        let list = create_list(context, ItemListUpdate {
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap();
+        let list_id = list.id.unwrap();
+
+        let apples = add_item(context, list_id, ListItemUpdate {
+            attributes: Some(HashMap::from([("quantity".to_string(), ListAttribute::Integer(2))])),
+            name: Some("Apples".to_string()),
+            source: Some("grocer".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!("Apples", apples.name);
+        assert_eq!("grocer", apples.source);
+        assert_eq!(ListAttribute::Integer(2), apples.attributes["quantity"]);
+        let pears = add_item(context, list_id, ListItemUpdate {
+            name: Some("Pears".to_string()),
+            source: Some("grocer".to_string()),
+            ..Default::default()
+        }).unwrap();
+        let changed_list = retrieve_list(context, list_id).await.unwrap();
+        assert!(changed_list.modified >= list.modified);
+        assert_eq!(list.version + 2, changed_list.version);
+
+        let stale_apples = apples.clone();
+        let apples = update_item(context, list_id, apples.id.unwrap(), Some(apples.version), ListItemUpdate {
+            attributes: Some(HashMap::from([
+                ("quantity".to_string(), ListAttribute::Integer(5)),
+                ("organic".to_string(), ListAttribute::Boolean(true)),
+            ])),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!("Apples", apples.name);
+        assert_eq!(2, apples.attributes.len());
+        assert_eq!(ListAttribute::Integer(5), apples.attributes["quantity"]);
+        assert_eq!(stale_apples.version + 1, apples.version);
+        assert_eq!(changed_list.version + 1, retrieve_list(context, list_id).await.unwrap().version);
+        let stale = update_item(context, list_id, apples.id.unwrap(), Some(stale_apples.version), ListItemUpdate::default());
+        assert!(matches!(stale, Err(ListManagementError::PreconditionFailed(_))));
+        let stale = delete_item(context, list_id, apples.id.unwrap(), Some(stale_apples.version));
+        assert!(matches!(stale, Err(ListManagementError::PreconditionFailed(_))));
+
+        let apples = update_item(context, list_id, apples.id.unwrap(), None, ListItemUpdate {
+            name: Some("Green Apples".to_string()),
+            remove_attributes: Some(vec!["organic".to_string()]),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!("Green Apples", apples.name);
+        assert_eq!(1, apples.attributes.len());
+
+        delete_item(context, list_id, apples.id.unwrap(), Some(apples.version)).unwrap();
+        let items = retrieve_items(context, list_id).await.unwrap();
+        assert_eq!(1, items.len());
+        assert_eq!(pears.id, items[0].id);
+        assert!(matches!(delete_item(context, list_id, apples.id.unwrap(), None), Err(ListManagementError::NotFound(_))));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_add_item_errors() {
+        let context = &db_context(user(), db_state());
+        let list_id = create_list(context, ItemListUpdate {
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+
+        let no_source = add_item(context, list_id, ListItemUpdate {
+            name: Some("Apples".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(no_source, Err(ListManagementError::Validation(_))));
+        let blank_name = add_item(context, list_id, ListItemUpdate {
+            name: Some("".to_string()),
+            source: Some("grocer".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(blank_name, Err(ListManagementError::Validation(_))));
+        let no_list = add_item(context, list_id + 1, ListItemUpdate {
+            name: Some("Apples".to_string()),
+            source: Some("grocer".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(no_list, Err(ListManagementError::NotFound(_))));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_reorder_items() {
+        let context = &db_context(user(), db_state());
+        let list_id = create_list(context, ItemListUpdate {
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+        let ids: Vec<u64> = ["one", "two", "three"]
+            .iter()
+            .map(|name| add_item(context, list_id, ListItemUpdate {
+                name: Some(name.to_string()),
+                source: Some("s".to_string()),
+                ..Default::default()
+            }).unwrap().id.unwrap())
+            .collect();
+
+        // Creating the list and adding each item took it to version 4.
+        let items = reorder_items(context, list_id, Some(4), vec![ids[2], ids[0], ids[1]]).unwrap();
+        let names: Vec<&str> = items.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(vec!["three", "one", "two"], names);
+        assert!(matches!(
+            reorder_items(context, list_id, Some(4), vec![ids[0], ids[1], ids[2]]),
+            Err(ListManagementError::PreconditionFailed(_))
+        ));
+
+        let added = add_item(context, list_id, ListItemUpdate {
+            name: Some("four".to_string()),
+            source: Some("s".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(added.id, retrieve_items(context, list_id).await.unwrap()[3].id);
+
+        assert!(matches!(reorder_items(context, list_id, None, vec![ids[2], ids[0], ids[1]]), Err(ListManagementError::Validation(_))));
+        assert!(matches!(
+            reorder_items(context, list_id, None, vec![ids[2], ids[0], ids[0], added.id.unwrap()]),
+            Err(ListManagementError::Validation(_))
+        ));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_item_page() {
+        let context = &db_context(user(), db_state());
+        let list_id = create_list(context, ItemListUpdate {
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+        let ids: Vec<u64> = ["one", "two", "three"]
+            .iter()
+            .map(|name| add_item(context, list_id, ListItemUpdate {
+                name: Some(name.to_string()),
+                source: Some("s".to_string()),
+                ..Default::default()
+            }).unwrap().id.unwrap())
+            .collect();
+        reorder_items(context, list_id, None, vec![ids[1], ids[2], ids[0]]).unwrap();
+
+        let page = retrieve_item_page(context, list_id, PagingRequest { start: 1, rows: 1, cursor: None }).await.unwrap();
+        assert_eq!(3, page.total);
+        assert_eq!(Some(ids[2]), page.items[0].id);
+
+        let page = retrieve_item_page(context, list_id, PagingRequest { start: 0, rows: 2, cursor: None }).await.unwrap();
+        assert_eq!(vec![Some(ids[1]), Some(ids[2])], page.items.iter().map(|i| i.id).collect::<Vec<_>>());
+        // Items added after the first page was read still show up in order.
+        let four = add_item(context, list_id, ListItemUpdate {
+            name: Some("four".to_string()),
+            source: Some("s".to_string()),
+            ..Default::default()
+        }).unwrap();
+        let page = retrieve_item_page(context, list_id, PagingRequest { start: 0, rows: 2, cursor: page.next_cursor }).await.unwrap();
+        assert_eq!(vec![Some(ids[0]), four.id], page.items.iter().map(|i| i.id).collect::<Vec<_>>());
+        let page = retrieve_item_page(context, list_id, PagingRequest { start: 0, rows: 2, cursor: page.next_cursor }).await.unwrap();
+        assert!(page.items.is_empty());
+        assert!(page.next_cursor.is_none());
+
+        let other_list_id = create_list(context, ItemListUpdate {
+            name: Some("My Other List".to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+        let page = retrieve_item_page(context, list_id, PagingRequest { start: 0, rows: 1, cursor: None }).await.unwrap();
+        let wrong_list = retrieve_item_page(context, other_list_id, PagingRequest { start: 0, rows: 1, cursor: page.next_cursor }).await;
+        assert!(matches!(wrong_list, Err(ListManagementError::Validation(_))));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_rollups_follow_item_changes() {
+        let context = &db_context(user(), db_state());
+        let list_id = create_list(context, ItemListUpdate {
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+        let priced = |amount: &str, quantity: i64| Some(HashMap::from([
+            ("cost".to_string(), ListAttribute::Price(Price { amount: Decimal::from_str(amount).unwrap(), source: "USD".to_string() })),
+            ("quantity".to_string(), ListAttribute::Integer(quantity)),
+        ]));
+        let cost = |amount: &str, total_lines: u64, total_units: u64| vec![ItemListRollup {
+            name: "cost".to_string(),
+            function: RollupFunction::Sum,
+            value: ListAttribute::Price(Price { amount: Decimal::from_str(amount).unwrap(), source: "USD".to_string() }),
+            total_lines,
+            total_units,
+        }];
+        let rollups = async || retrieve_list(context, list_id).await.unwrap().rollups.unwrap();
+        assert!(rollups().await.is_empty());
+
+        let apples = add_item(context, list_id, ListItemUpdate {
+            attributes: priced("1.50", 2),
+            name: Some("Apples".to_string()),
+            source: Some("grocer".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(cost("3.00", 1, 2), rollups().await);
+        let pears = add_item(context, list_id, ListItemUpdate {
+            attributes: priced("2.00", 1),
+            name: Some("Pears".to_string()),
+            source: Some("grocer".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(cost("5.00", 2, 3), rollups().await);
+
+        update_item(context, list_id, apples.id.unwrap(), None, ListItemUpdate {
+            attributes: Some(HashMap::from([("quantity".to_string(), ListAttribute::Integer(4))])),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(cost("8.00", 2, 5), rollups().await);
+        update_item(context, list_id, pears.id.unwrap(), None, ListItemUpdate {
+            remove_attributes: Some(vec!["quantity".to_string()]),
+            ..Default::default()
+        }).unwrap();
+        assert_eq!(cost("6.00", 2, 4), rollups().await);
+
+        delete_item(context, list_id, apples.id.unwrap(), None).unwrap();
+        assert_eq!(cost("0.00", 1, 0), rollups().await);
+        delete_item(context, list_id, pears.id.unwrap(), None).unwrap();
+        assert!(rollups().await.is_empty());
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_find_items() {
+        let context = &db_context(user(), db_state());
+        let list = async |name: &str| create_list(context, ItemListUpdate {
+            name: Some(name.to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+        let (groceries, hardware) = (list("Groceries").await, list("Hardware").await);
+        let item = |list_id: u64, name: &str, source: &str, attributes: Vec<(&str, ListAttribute)>| add_item(context, list_id, ListItemUpdate {
+            attributes: Some(attributes.into_iter().map(|(n, a)| (n.to_string(), a)).collect()),
+            name: Some(name.to_string()),
+            source: Some(source.to_string()),
+            ..Default::default()
+        }).unwrap().id.unwrap();
+        let text = |t: &str| ListAttribute::Text(t.to_string());
+        let usd = |amount: &str| ListAttribute::Price(Price { amount: Decimal::from_str(amount).unwrap(), source: "USD".to_string() });
+        let apples = item(groceries, "Green Apples", "grocer", vec![("priority", text("high")), ("cost", usd("3.50"))]);
+        let pears = item(groceries, "Pears", "market", vec![("priority", text("low")), ("weight", ListAttribute::Float(1.5))]);
+        let nails = item(hardware, "Nails", "grocer", vec![("priority", text("high")), ("count", ListAttribute::Integer(100))]);
+        // Lists of other users are not searched.
+        let other = &db_context(user(), UserState { active_user_accounts: vec![], user_id: insert_user("other", "source", "source-2") as u64 });
+        let other_list = create_list(other, ItemListUpdate { name: Some("Other".to_string()), ..Default::default() }).await.unwrap().id.unwrap();
+        add_item(other, other_list, ListItemUpdate { name: Some("Apples".to_string()), source: Some("grocer".to_string()), ..Default::default() }).unwrap();
+
+        let by_id = ItemSortRequest { descending: false, key: ItemSortKey::Id };
+        let find = |selector: ItemSelector| -> Vec<u64> {
+            find_items(context, selector, PagingRequest { start: 0, rows: 10, cursor: None }, by_id.clone())
+                .unwrap()
+                .items
+                .iter()
+                .map(|found| found.item.id.unwrap())
+                .collect()
+        };
+        let predicate = |name: &str, comparison: Comparison, value: ListAttribute| AttributeFilter::Predicate(AttributePredicate {
+            name: name.to_string(),
+            condition: AttributeCondition::Compare(comparison, value),
+        });
+
+        assert_eq!(vec![apples, pears, nails], find(selector()));
+        assert_eq!(vec![apples], find(ItemSelector { limit_name_keywords: Some("app*".to_string()), ..selector() }));
+        assert_eq!(vec![apples, nails], find(ItemSelector { limit_sources: vec!["grocer".to_string()], ..selector() }));
+        assert_eq!(vec![apples, nails], find(ItemSelector {
+            limit_attributes: vec![predicate("priority", Comparison::Eq, text("high"))],
+            ..selector()
+        }));
+        assert_eq!(vec![apples], find(ItemSelector {
+            limit_lists: ListSelector { limit_list_ids: vec![groceries], ..selector().limit_lists },
+            limit_attributes: vec![predicate("priority", Comparison::Ne, text("low"))],
+            ..selector()
+        }));
+        // Numbers compare with Integer, Float and Price attributes alike.
+        let more_than_one = |name: &str| find(ItemSelector {
+            limit_attributes: vec![predicate(name, Comparison::Gt, ListAttribute::Integer(1))],
+            ..selector()
+        });
+        assert_eq!(vec![apples], more_than_one("cost"));
+        assert_eq!(vec![pears], more_than_one("weight"));
+        assert_eq!(vec![nails], more_than_one("count"));
+        assert!(find(ItemSelector {
+            limit_attributes: vec![predicate("cost", Comparison::Lt, usd("5.00")), predicate("priority", Comparison::Eq, text("low"))],
+            ..selector()
+        }).is_empty());
+        assert_eq!(vec![pears], find(ItemSelector {
+            limit_attributes: vec![AttributeFilter::Predicate(AttributePredicate {
+                name: "weight".to_string(),
+                condition: AttributeCondition::Exists,
+            })],
+            ..selector()
+        }));
+
+        let by_name = ItemSortRequest { descending: true, key: ItemSortKey::Name };
+        let page = find_items(context, selector(), PagingRequest { start: 0, rows: 2, cursor: None }, by_name.clone()).unwrap();
+        assert_eq!(3, page.total);
+        assert_eq!(vec!["Pears", "Nails"], page.items.iter().map(|found| found.item.name.as_str()).collect::<Vec<_>>());
+        assert_eq!((hardware, "Hardware"), (page.items[1].list_id, page.items[1].list_name.as_str()));
+        let page = find_items(context, selector(), PagingRequest { start: 0, rows: 2, cursor: page.next_cursor }, by_name).unwrap();
+        assert_eq!(vec![Some(apples)], page.items.iter().map(|found| found.item.id).collect::<Vec<_>>());
+        assert!(page.next_cursor.is_none());
+    }
+
+    fn selector() -> ItemSelector {
+        ItemSelector {
+            limit_lists: ListSelector {
+                limit_show_read_only: false,
+                limit_list_types: vec![],
+                limit_list_access: vec![],
+                limit_show_deleted: false,
+                limit_show_not_deleted: true,
+                limit_in_folders: vec![],
+                limit_name_keywords: None,
+                limit_list_ids: vec![],
+                limit_search: None,
+                limit_attributes: vec![],
+            },
+            limit_name_keywords: None,
+            limit_sources: vec![],
+            limit_attributes: vec![],
+        }
+    }
+
+    fn db_state() -> UserState {
+        setup_db();
+        let user_id = insert_user("name", "source", "source-1");
+        UserState {
+            active_user_accounts: vec![],
+            user_id: user_id as u64,
+        }
+    }
+}
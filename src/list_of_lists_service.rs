@@ -1,22 +1,49 @@
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
 
 use rust_decimal::Decimal;
-use tracing::info;
-
-use crate::common::{ATTRIBUTE_QUANTITY, ItemList, ItemListRollup, ListAccess, ListAttribute, ListItem, ListType, LMContext, PagingRequest, Price, SortKey, SortRequest};
-use crate::common::ListAttribute::DateTime;
-
-pub trait ListProvider {
-    fn retrieve_lists(
-        &mut self,
-        context: &impl LMContext,
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::common::{AttributeFilter, ItemList, ItemListRollup, ListAccess, ListAttribute, ListItem, ListManagementError, ListType, LMContext, PagingRequest, Price, RollupRequest, SortKey, SortRequest};
+use crate::currency::CurrencyConverter;
+use crate::rollup::{RollupDefinition, RollupField, RollupFunction};
+use crate::search::SearchQuery;
+use crate::cursor;
+use crate::cursor::ListCursor;
+use crate::db::blocking;
+
+/// A page of lists on its way from a `ListProvider`.
+pub type ListsFuture<'a> = Pin<Box<dyn Future<Output = Result<ListOfListsResponse, ListManagementError>> + Send + 'a>>;
+
+/// Supplies the pages of lists that a caller may see.  `ListOfListsService` reads them from the
+/// database; an application may use another provider, such as a remote or cached source, by
+/// registering it as `web::Data<dyn ListProvider>` app data.
+pub trait ListProvider: Send + Sync {
+    fn retrieve_lists<'a>(
+        &'a self,
+        context: &'a dyn LMContext,
         selector: ListSelector,
         paging: PagingRequest,
         sort: SortRequest,
         return_attributes: bool,
-        return_rollups: bool,
-    ) -> Vec<ItemList>;
+        rollups: Option<RollupRequest>,
+    ) -> ListsFuture<'a>;
+}
+
+/// One page of lists.  `total` counts every list matching the selector, while `start` and `rows`
+/// echo the page that was asked for.  `next_cursor` is given whenever the page is full, and
+/// retrieves the following page when passed back with the same selector and sort.
+#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize)]
+pub struct ListOfListsResponse {
+    pub total: u64,
+    pub start: u64,
+    pub rows: u64,
+    pub lists: Vec<ItemList>,
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -29,250 +56,494 @@ pub struct ListSelector {
     pub limit_in_folders: Vec<String>,
     pub limit_name_keywords: Option<String>,
     pub limit_list_ids: Vec<u64>,
+    /// Keeps only the lists matching this full-text search.
+    pub limit_search: Option<SearchQuery>,
+    /// Keeps only the lists whose attributes pass every one of these filters.
+    pub limit_attributes: Vec<AttributeFilter>,
 }
 
+/// Reads lists from the database, on the blocking thread pool.
 pub struct ListOfListsService();
 
+/// The provider used when the application registers none.
+pub(crate) static LIST_OF_LISTS_SERVICE: ListOfListsService = ListOfListsService();
+
 impl ListProvider for ListOfListsService {
-    fn retrieve_lists(
-        &mut self,
-        context: &impl LMContext,
+    fn retrieve_lists<'a>(
+        &'a self,
+        context: &'a dyn LMContext,
         selector: ListSelector,
         paging: PagingRequest,
         sort: SortRequest,
         return_attributes: bool,
-        return_rollups: bool,
-    ) -> Vec<ItemList> {
-        let user_state = context.current_user_state();
-        let a = crate::list_storage::user_lists(user_state);
-        let start = paging.start as usize;
-        let mut end = (paging.start + paging.rows) as usize;
-        if end > a.len() {
-            end = a.len();
-        }
-
-        if paging.rows == 0 || paging.start >= a.len() as u64 {
-            return vec![];
-        }
-        let a = sort_list_of_lists(a, sort);
-        let mut i: usize = 0;
-        let mut a1: Vec<ItemList> = Vec::new();
-        for mut item_list in a {
-            let mut include: bool = i >= start;
-            include = include && (selector.limit_show_not_deleted || item_list.deleted);
-            include = include && (selector.limit_show_deleted || !item_list.deleted);
-            include = include && (selector.limit_show_read_only || !item_list.read_only);
-            include = include
-                && (selector.limit_list_access.is_empty()
-                || selector.limit_list_access.contains(&item_list.list_access));
-            include = include
-                && (selector.limit_list_types.is_empty()
-                || selector.limit_list_types.contains(&item_list.list_type));
-            include = include
-                && (selector.limit_in_folders.is_empty()
-                || selector.limit_in_folders.contains(&item_list.folder));
-            include = include
-                && (selector.limit_list_ids.is_empty()
-                || (item_list.id.is_some() && selector.limit_list_ids.contains(&item_list.id.unwrap())));
-            if include && selector.limit_name_keywords.is_some() {
-                let name_tokens: Vec<String> = item_list
-                    .name
-                    .split_whitespace()
-                    .map(|a| a.to_ascii_lowercase())
-                    .collect();
-                for kw in selector
-                    .limit_name_keywords
-                    .as_ref()
-                    .unwrap()
-                    .split_whitespace()
-                    .map(|a| a.to_ascii_lowercase())
-                {
-                    let mut found: bool = false;
-                    if kw.len() > 1 && kw.ends_with("*") {
-                        let mut kw_no_star = kw;
-                        kw_no_star.pop();
-                        for name_token in &name_tokens {
-                            if name_token.starts_with(&kw_no_star) {
-                                found = true;
-                                break;
-                            }
-                        }
-                    } else {
-                        found = name_tokens.contains(&kw);
-                    }
-                    if !found {
-                        include = false;
-                        break;
+        rollups: Option<RollupRequest>,
+    ) -> ListsFuture<'a> {
+        Box::pin(async move {
+            let converter = match rollups.as_ref().and_then(|r| r.currency.as_ref()) {
+                Some(currency) => Some((
+                    currency,
+                    context.currency_converter().ok_or_else(|| {
+                        ListManagementError::Validation("rollups cannot be converted: no currency rates are configured".to_string())
+                    })?,
+                )),
+                None => None,
+            };
+            let after: Option<ListCursor> = match &paging.cursor {
+                Some(c) => {
+                    let after: ListCursor = cursor::decode(c)?;
+                    if after.sort != sort {
+                        return Err(ListManagementError::Validation(
+                            "the cursor was made for a different sort".to_string(),
+                        ));
                     }
+                    Some(after)
                 }
-            }
-
-            if include {
+                None => None,
+            };
+            // The next cursor needs the sort attribute of the page's last list.
+            let sort_attribute = match &sort.key {
+                SortKey::Attribute(name) => Some(name),
+                _ => None,
+            };
+            let user_state = context.current_user_state();
+            let load_attributes = return_attributes || sort_attribute.is_some();
+            let specification = rollups.as_ref().map(|_| context.rollup_specification().clone());
+            let (total, mut a) = {
+                let (selector, paging, sort, after) = (selector.clone(), paging.clone(), sort.clone(), after.clone());
+                let pool = context.pool().clone();
+                blocking(move || {
+                    let total = crate::list_storage::count_user_lists(&pool, &user_state, &selector)?;
+                    let lists = crate::list_storage::user_lists(
+                        &pool,
+                        &user_state,
+                        &selector,
+                        &paging,
+                        &sort,
+                        after.as_ref(),
+                        load_attributes,
+                        specification.as_ref(),
+                    )?;
+                    Ok((total, lists))
+                }).await?
+            };
+            let next_cursor = match a.last() {
+                Some(last) if a.len() as u64 == paging.rows => Some(cursor::encode(&ListCursor {
+                    id: last.id.unwrap(),
+                    sort: sort.clone(),
+                    value: match &sort.key {
+                        SortKey::Attribute(name) => last.attributes.get(name).cloned(),
+                        SortKey::CreatedDate => Some(ListAttribute::DateTime(last.created)),
+                        SortKey::Id => None,
+                        SortKey::ModifiedDate => Some(ListAttribute::DateTime(last.modified)),
+                        SortKey::Name => Some(ListAttribute::Text(last.name.clone())),
+                        SortKey::Relevance => {
+                            let start = match after.as_ref().and_then(|after| after.value.as_ref()) {
+                                Some(ListAttribute::Integer(position)) => *position as u64,
+                                _ => paging.start,
+                            };
+                            Some(ListAttribute::Integer((start + a.len() as u64) as i64))
+                        }
+                    },
+                })),
+                _ => None,
+            };
+            for item_list in &mut a {
                 if !return_attributes {
                     item_list.attributes = HashMap::with_capacity(0);
                 }
-                let il_i = &item_list.items.as_ref().unwrap();
-                item_list.rollups = compute_rollup_values(return_rollups, &il_i);
-                a1.push(item_list);
+                if let (Some(totals), Some((currency, converter))) = (&item_list.rollups, converter) {
+                    item_list.converted_rollups = Some(convert_rollup_values(totals, currency, converter));
+                }
             }
-            i += 1;
-            if i == end {
-                break;
+            info!("Returning {} of {} list results for {:?} with {:?}", a.len(), total, selector, paging);
+            Ok(ListOfListsResponse {
+                total,
+                start: paging.start,
+                rows: paging.rows,
+                lists: a,
+                next_cursor,
+            })
+        })
+    }
+}
+
+/// A rollup as it is stored with its list: its result and, for a rollup of numbers, the running
+/// total the result was evaluated from.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StoredRollup {
+    pub rollup: ItemListRollup,
+    pub total: Option<RollupTotal>,
+}
+
+/// Evaluates each rollup over the items, in the order the rollups are defined.  Rollups of prices
+/// give one result for each currency.
+pub(crate) fn compute_rollups(items: &[ListItem], definitions: &[RollupDefinition]) -> Vec<StoredRollup> {
+    let mut rollups: Vec<StoredRollup> = Vec::new();
+    for definition in definitions {
+        match definition.function {
+            RollupFunction::Count | RollupFunction::CountDistinct => {
+                let values: Vec<String> = items.iter().filter_map(|item| count_key(item, &definition.of)).collect();
+                let count = match definition.function {
+                    RollupFunction::Count => values.len(),
+                    _ => values.iter().collect::<HashSet<_>>().len(),
+                };
+                rollups.push(StoredRollup {
+                    rollup: ItemListRollup {
+                        name: definition.name.clone(),
+                        function: definition.function,
+                        value: ListAttribute::Integer(count as i64),
+                        total_lines: values.len() as u64,
+                        total_units: 0,
+                    },
+                    total: None,
+                });
+            }
+            _ => {
+                let RollupField::Attribute(attribute) = &definition.of else { continue };
+                // Values are grouped by currency, numbers that are not prices having none.
+                let mut groups: BTreeMap<Option<&str>, RollupTotal> = BTreeMap::new();
+                for item in items {
+                    let Some((currency, value, units)) = contribution(item, definition, attribute) else { continue };
+                    groups
+                        .entry(currency)
+                        .or_insert_with(|| RollupTotal { currency: currency.map(str::to_string), ..Default::default() })
+                        .add(definition.function, value, units);
+                }
+                rollups.extend(groups.into_values().filter_map(|total| total.evaluate(definition)));
             }
         }
-        info!("Returning {} list results for {:?} with {:?}", a1.len(), selector, paging);
-        a1
     }
+    rollups
 }
 
-fn sort_list_of_lists(mut a: Vec<ItemList>, sort: SortRequest) -> Vec<ItemList> {
-    a.sort_by(|a, b| {
-        let (one, two) = if sort.descending { (b, a) } else { (a, b) };
-        match &sort.key {
-            SortKey::Attribute(attribute_name) => {
-                let one_attribute_opt = one.attributes.get(attribute_name);
-                let two_attribute_opt = two.attributes.get(attribute_name);
-                if one_attribute_opt.is_some() && two_attribute_opt.is_some() {
-                    let one_attribute_value = one_attribute_opt.unwrap();
-                    let two_attribute_value = two_attribute_opt.unwrap();
-                    let mut ordering: Option<Ordering> = None;
-                    match one_attribute_value {
-                        ListAttribute::Boolean(v1) => {
-                            if let ListAttribute::Boolean(v2) = two_attribute_value {
-                                ordering = Some(ordering_with_tiebreaker(v1.cmp(v2), one, two));
-                            }
-                        }
-                        DateTime(v1) => {
-                            if let DateTime(v2) = two_attribute_value {
-                                ordering = Some(ordering_with_tiebreaker(v1.cmp(v2), one, two));
-                            }
-                        }
-                        ListAttribute::Float(v1) => {
-                            if let ListAttribute::Float(v2) = two_attribute_value {
-                                if v1.min(*v2) == *v1 {
-                                    return Ordering::Less;
-                                }
-                                return Ordering::Greater;
-                            }
-                        }
-                        ListAttribute::Integer(v1) => {
-                            if let ListAttribute::Integer(v2) = two_attribute_value {
-                                ordering = Some(ordering_with_tiebreaker(v1.cmp(v2), one, two));
-                            }
-                        }
-                        ListAttribute::Price(v1) => {
-                            if let ListAttribute::Price(v2) = two_attribute_value {
-                                let value1 = v1.amount;
-                                let value2 = v2.amount;
-                                if value1.min(value2) == value1 {
-                                    return Ordering::Less;
-                                }
-                                return Ordering::Greater;
-                            }
-                        }
-                        ListAttribute::Text(v1) => {
-                            if let ListAttribute::Text(v2) = two_attribute_value {
-                                ordering = Some(ordering_with_tiebreaker(v1.cmp(v2), one, two));
-                            }
-                        }
+/// Changes a list's rollups, as computed by `compute_rollups` and in the same order, for one of
+/// its items changing from `before` to `after`, either being None when the item is added or
+/// deleted.  Returns false, leaving the rollups to be recomputed from every item, when a rollup
+/// would gain or lose a result, when a `Min` or `Max` loses its least or greatest value, and when
+/// the values a `CountDistinct` counts change.
+pub(crate) fn apply_item_change(
+    rollups: &mut [StoredRollup],
+    definitions: &[RollupDefinition],
+    before: Option<&ListItem>,
+    after: Option<&ListItem>,
+) -> bool {
+    for definition in definitions {
+        match definition.function {
+            RollupFunction::Count | RollupFunction::CountDistinct => {
+                let old = before.and_then(|item| count_key(item, &definition.of));
+                let new = after.and_then(|item| count_key(item, &definition.of));
+                if old == new {
+                    continue;
+                }
+                let Some(stored) = rollups.iter_mut().find(|stored| stored.rollup.name == definition.name) else {
+                    return false;
+                };
+                if definition.function == RollupFunction::CountDistinct {
+                    return false;
+                }
+                let Some(count) = (stored.rollup.total_lines + new.is_some() as u64).checked_sub(old.is_some() as u64) else {
+                    return false;
+                };
+                stored.rollup.value = ListAttribute::Integer(count as i64);
+                stored.rollup.total_lines = count;
+            }
+            _ => {
+                let RollupField::Attribute(attribute) = &definition.of else { continue };
+                let old = before.and_then(|item| contribution(item, definition, attribute));
+                let new = after.and_then(|item| contribution(item, definition, attribute));
+                if old == new {
+                    continue;
+                }
+                // The new value goes in first, so a `Min` or `Max` that it takes over from is not
+                // recomputed.
+                for (change, added) in [(new, true), (old, false)] {
+                    let Some((currency, value, units)) = change else { continue };
+                    let Some(stored) = rollups.iter_mut().find(|stored| {
+                        stored.rollup.name == definition.name
+                            && stored.total.as_ref().is_some_and(|total| total.currency.as_deref() == currency)
+                    }) else {
+                        return false;
+                    };
+                    // A total whose exact part could not be kept must be recomputed.
+                    let Some(mut total) = stored.total.clone().filter(|total| total.exact.is_some() || total.lines == total.float_lines) else {
+                        return false;
+                    };
+                    if added {
+                        total.add(definition.function, value, units);
+                    } else if !total.remove(definition.function, value, units) {
+                        return false;
                     }
-                    if let Some(ret_val) = ordering {
-                        return ret_val;
+                    match total.evaluate(definition) {
+                        Some(evaluated) => *stored = evaluated,
+                        None => return false,
                     }
                 }
-                ordering_by_id(one, two)
             }
-            SortKey::CreatedDate => one.created.cmp(&two.created),
-            SortKey::Id => ordering_by_id(one, two),
-            SortKey::ModifiedDate => one.modified.cmp(&two.modified),
-            SortKey::Name => one.name.cmp(&two.name),
         }
-    });
-    a
+    }
+    true
+}
+
+/// What an item contributes to a `Count` or `CountDistinct` rollup, if anything.
+fn count_key(item: &ListItem, field: &RollupField) -> Option<String> {
+    match field {
+        RollupField::Attribute(name) => match item.attributes.get(name)? {
+            ListAttribute::Boolean(false) => None,
+            attr => Some(serde_json::to_string(attr).unwrap()),
+        },
+        RollupField::Name => Some(item.name.clone()),
+        RollupField::Source => Some(item.source.clone()),
+    }
+}
+
+/// What an item contributes to a rollup of numbers, if anything: the currency of the result it
+/// goes into, its value, and its units.
+fn contribution<'a>(
+    item: &'a ListItem,
+    definition: &RollupDefinition,
+    attribute: &str,
+) -> Option<(Option<&'a str>, RollupNumber, u64)> {
+    let multiplier = match definition.multiply_by.as_ref().map(|m| item.attributes.get(m)) {
+        Some(Some(ListAttribute::Integer(m))) => *m,
+        Some(_) => 0,
+        None => 1,
+    };
+    let (currency, value) = match item.attributes.get(attribute)? {
+        ListAttribute::Integer(i) => (None, RollupNumber::Integer(*i as i128 * multiplier as i128)),
+        ListAttribute::Float(f) => (None, RollupNumber::Float(f * multiplier as f64)),
+        ListAttribute::Price(p) => (
+            Some(p.source.as_str()),
+            match p.amount.checked_mul(Decimal::from(multiplier)) {
+                Some(amount) => RollupNumber::Decimal(amount),
+                None => RollupNumber::Float(p.amount.to_f64().unwrap_or_default() * multiplier as f64),
+            },
+        ),
+        _ => return None,
+    };
+    let units = match definition.multiply_by {
+        Some(_) => multiplier.max(0) as u64,
+        None => 0,
+    };
+    Some((currency, value, units))
 }
 
-fn ordering_by_id(one: &ItemList, two: &ItemList) -> Ordering {
-    one.id.cmp(&two.id)
+/// An item's value for a rollup.  Integers and prices are added up exactly, and only become
+/// floating point when mixed with Floats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RollupNumber {
+    Integer(i128),
+    Decimal(Decimal),
+    Float(f64),
 }
 
-fn ordering_with_tiebreaker(o: Ordering, one: &ItemList, two: &ItemList) -> Ordering {
-    if o == Ordering::Equal {
-        ordering_by_id(one, two)
-    } else {
-        o
+impl RollupNumber {
+    fn to_f64(self) -> f64 {
+        match self {
+            RollupNumber::Integer(i) => i as f64,
+            RollupNumber::Decimal(d) => d.to_f64().unwrap_or_default(),
+            RollupNumber::Float(f) => f,
+        }
+    }
+
+    fn to_decimal(self) -> Option<Decimal> {
+        match self {
+            RollupNumber::Integer(i) => Decimal::from_i128(i),
+            RollupNumber::Decimal(d) => Some(d),
+            RollupNumber::Float(_) => None,
+        }
     }
 }
 
+/// The running total of a `Sum`, `Min`, `Max` or `Avg` rollup over the values in one currency, or
+/// over numbers that are not prices, which its result is evaluated from.  The exact values are
+/// totalled apart from the Floats, so the result is exact again once the last Float is gone.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct RollupTotal {
+    pub currency: Option<String>,
+    /// The sum of the exact values, or for `Min` and `Max` the least or greatest of them.  None
+    /// when there are none, or their sum is too large to keep.
+    pub exact: Option<Decimal>,
+    /// The same over every value taken as a Float.
+    pub float: Option<f64>,
+    pub float_lines: u64,
+    pub lines: u64,
+    pub units: u64,
+}
 
-fn compute_rollup_values(
-    return_rollups: bool,
-    items: &Vec<ListItem>,
-) -> Option<HashMap<String, ItemListRollup>> {
-    if return_rollups {
-        let mut rollups: HashMap<String, ItemListRollup> = HashMap::new();
-        for item in items {
-            let qty_o = item.attributes.get(ATTRIBUTE_QUANTITY);
-            let mut qty: u64 = 0;
-            if qty_o.is_some() {
-                if let ListAttribute::Integer(qty1) = qty_o.unwrap() {
-                    qty = *qty1 as u64;
-                }
+impl RollupTotal {
+    fn add(&mut self, function: RollupFunction, value: RollupNumber, units: u64) {
+        let float = value.to_f64();
+        self.float = Some(self.float.map_or(float, |total| match function {
+            RollupFunction::Min => total.min(float),
+            RollupFunction::Max => total.max(float),
+            _ => total + float,
+        }));
+        match value.to_decimal() {
+            Some(exact) if self.lines == self.float_lines => self.exact = Some(exact),
+            Some(exact) => {
+                self.exact = self.exact.and_then(|total| match function {
+                    RollupFunction::Min => Some(total.min(exact)),
+                    RollupFunction::Max => Some(total.max(exact)),
+                    _ => total.checked_add(exact),
+                })
             }
-            for (k, v) in &item.attributes {
-                if let ListAttribute::Price(price) = v {
-                    let ilr_price = Price {
-                        amount: price.amount * Decimal::from(qty),
-                        source: price.source.clone(),
-                    };
+            None => self.float_lines += 1,
+        }
+        self.lines += 1;
+        self.units += units;
+    }
 
-                    let ilr_o = rollups.get(k);
-                    if ilr_o.is_none() {
-                        let ilr = ItemListRollup {
-                            total_lines: 1,
-                            total_units: qty,
-                            total_amount: ilr_price,
-                        };
-                        rollups.insert(k.clone(), ilr);
-                    } else {
-                        let ilr = ilr_o.unwrap();
-                        let mut price1 = ilr.total_amount.clone();
-                        let currency1 = price1.amount + ilr_price.amount;
-                        price1.amount = currency1;
-
-                        let ilr1 = ItemListRollup {
-                            total_lines: ilr.total_lines + 1,
-                            total_units: ilr.total_units + qty,
-                            total_amount: price1,
-                        };
-                        rollups.insert(k.clone(), ilr1);
-                    }
+    /// Takes a value back out, returning false when the total cannot be known without the values
+    /// left: when it was the least or greatest of them, or their exact sum was too large to keep.
+    fn remove(&mut self, function: RollupFunction, value: RollupNumber, units: u64) -> bool {
+        let (float, exact) = (value.to_f64(), value.to_decimal());
+        let no_such_value = match exact {
+            Some(_) => self.lines == self.float_lines,
+            None => self.float_lines == 0,
+        };
+        if no_such_value {
+            return false;
+        }
+        match function {
+            RollupFunction::Min | RollupFunction::Max => {
+                if self.float == Some(float) || (exact.is_some() && self.exact == exact) {
+                    return false;
                 }
             }
+            _ => {
+                self.float = self.float.map(|total| total - float);
+                if let Some(exact) = exact {
+                    let Some(total) = self.exact.and_then(|total| total.checked_sub(exact)) else { return false };
+                    self.exact = Some(total);
+                }
+            }
+        }
+        if exact.is_none() {
+            self.float_lines -= 1;
+        }
+        self.lines -= 1;
+        self.units = self.units.saturating_sub(units);
+        true
+    }
+
+    /// The result of the rollup, unless it has no values.
+    fn evaluate(self, definition: &RollupDefinition) -> Option<StoredRollup> {
+        if self.lines == 0 {
+            return None;
+        }
+        let function = definition.function;
+        let exact = self.exact.filter(|_| self.float_lines == 0).and_then(|total| match function {
+            RollupFunction::Avg => total.checked_div(Decimal::from(self.lines)),
+            _ => Some(total),
+        });
+        let value = match (exact, &self.currency) {
+            (Some(d), Some(currency)) => ListAttribute::Price(Price { amount: d, source: currency.clone() }),
+            (Some(d), None) if function == RollupFunction::Avg => ListAttribute::Float(d.to_f64().unwrap_or_default()),
+            (Some(d), None) => d.to_i64().map(ListAttribute::Integer).unwrap_or(ListAttribute::Float(d.to_f64().unwrap_or_default())),
+            (None, _) => {
+                let total = self.float?;
+                ListAttribute::Float(match function {
+                    RollupFunction::Avg => total / self.lines as f64,
+                    _ => total,
+                })
+            }
+        };
+        Some(StoredRollup {
+            rollup: ItemListRollup {
+                name: definition.name.clone(),
+                function,
+                value,
+                total_lines: self.lines,
+                total_units: self.units,
+            },
+            total: Some(self),
+        })
+    }
+}
+
+/// Combines each price rollup's results in the given currency.  A rollup with a result that
+/// cannot be converted, or whose combined result is too large, is left out, rather than given a
+/// result that is missing part of its items.
+fn convert_rollup_values(
+    rollups: &[ItemListRollup],
+    currency: &str,
+    converter: &dyn CurrencyConverter,
+) -> Vec<ItemListRollup> {
+    let mut converted: Vec<(ItemListRollup, bool)> = Vec::new();
+    for rollup in rollups {
+        let ListAttribute::Price(price) = &rollup.value else { continue };
+        let position = match converted.iter().position(|(c, _)| c.name == rollup.name) {
+            Some(position) => position,
+            None => {
+                converted.push((ItemListRollup {
+                    value: ListAttribute::Price(Price { amount: Decimal::ZERO, source: currency.to_string() }),
+                    total_lines: 0,
+                    total_units: 0,
+                    ..rollup.clone()
+                }, true));
+                converted.len() - 1
+            }
+        };
+        let (total, convertible) = &mut converted[position];
+        if !*convertible {
+            continue;
         }
-        return Some(rollups);
+        let Some(amount) = converter.convert(price.amount, &price.source, currency) else {
+            warn!("Cannot convert '{}' to '{}' for the '{}' rollup", price.source, currency, rollup.name);
+            *convertible = false;
+            continue;
+        };
+        let ListAttribute::Price(total_price) = &mut total.value else { unreachable!() };
+        let lines = Decimal::from(rollup.total_lines);
+        let combined = match rollup.function {
+            RollupFunction::Min if total.total_lines > 0 => Some(total_price.amount.min(amount)),
+            RollupFunction::Max if total.total_lines > 0 => Some(total_price.amount.max(amount)),
+            RollupFunction::Avg => {
+                let previous = Decimal::from(total.total_lines);
+                total_price
+                    .amount
+                    .checked_mul(previous)
+                    .zip(amount.checked_mul(lines))
+                    .and_then(|(before, added)| before.checked_add(added))
+                    .zip(previous.checked_add(lines))
+                    .and_then(|(sum, count)| sum.checked_div(count))
+            }
+            RollupFunction::Min | RollupFunction::Max => Some(amount),
+            _ => total_price.amount.checked_add(amount),
+        };
+        let Some(combined) = combined else {
+            warn!("The '{}' rollup is too large to combine in '{}'", rollup.name, currency);
+            *convertible = false;
+            continue;
+        };
+        total_price.amount = combined;
+        total.total_lines += rollup.total_lines;
+        total.total_units += rollup.total_units;
     }
-    None
+    converted.into_iter().filter(|(_, convertible)| *convertible).map(|(total, _)| total).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
 
+    use chrono::NaiveDateTime;
     use diesel::{RunQueryDsl, sql_query};
-    use diesel_migrations::MigrationHarness;
     use rust_decimal::Decimal;
     use serial_test::serial;
 
     use crate::common::*;
-    use crate::common::tests::{context, user, state};
+    use crate::common::tests::{context, db_context, user, state};
+    use crate::currency::StaticRateConverter;
     use crate::db;
-    use crate::test_helpers::MIGRATIONS;
+    use crate::db::DbAmount;
+    use crate::schema::sql_types::Amount;
 
     use super::*;
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_id() {
+    async fn test_retrieve_all_lists_by_id() {
         setup(false, false);
         let sort_request = sort(SortKey::Id, false);
         let results = ListOfListsService().retrieve_lists(
@@ -281,8 +552,8 @@ mod tests {
             paging(0, 10),
             sort_request,
             false,
-            false,
-        );
+            None,
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(1, results[0].id.unwrap());
         assert_eq!(2, results[1].id.unwrap());
@@ -298,9 +569,9 @@ mod tests {
         assert_eq!(1, results[2].list_accounts.len());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_id_with_attributes_and_rollups() {
+    async fn test_retrieve_all_lists_by_id_with_attributes_and_rollups() {
         setup(true, true);
         let sort_request = sort(SortKey::Id, false);
         let results = ListOfListsService().retrieve_lists(
@@ -309,8 +580,8 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(1, results[0].id.unwrap());
         assert_eq!(2, results[1].id.unwrap());
@@ -323,38 +594,53 @@ mod tests {
         let list_2_rollups = results[2].rollups.clone().unwrap();
         assert_eq!(2, list_0_rollups.len());
 
-        let l0r_xyz = list_0_rollups.get("xyz").unwrap();
+        let l0r_xyz = list_0_rollups.iter().find(|r| r.name == "xyz").unwrap();
         assert_eq!(3, l0r_xyz.total_lines);
         assert_eq!(6, l0r_xyz.total_units);
-        assert_eq!("xyz-source", l0r_xyz.total_amount.source);
-        assert_eq!(Decimal::from_str("19.98").unwrap(), l0r_xyz.total_amount.amount);
+        assert_eq!(price("19.98", "xyz-source"), l0r_xyz.value);
 
-        let l0r_qwe = list_0_rollups.get("qwe").unwrap();
+        let l0r_qwe = list_0_rollups.iter().find(|r| r.name == "qwe").unwrap();
         assert_eq!(2, l0r_qwe.total_lines);
         assert_eq!(4, l0r_qwe.total_units);
-        assert_eq!("qwe-source", l0r_qwe.total_amount.source);
-        assert_eq!(Decimal::from_str("9.36").unwrap(), l0r_qwe.total_amount.amount);
+        assert_eq!(price("9.36", "qwe-source"), l0r_qwe.value);
 
         assert_eq!(2, list_1_rollups.len());
 
-        let l1r_xyz = list_1_rollups.get("xyz").unwrap();
+        let l1r_xyz = list_1_rollups.iter().find(|r| r.name == "xyz").unwrap();
         assert_eq!(3, l1r_xyz.total_lines);
         assert_eq!(6, l1r_xyz.total_units);
-        assert_eq!("xyz-source", l1r_xyz.total_amount.source);
-        assert_eq!(Decimal::from_str("6.66").unwrap(), l1r_xyz.total_amount.amount);
+        assert_eq!(price("6.66", "xyz-source"), l1r_xyz.value);
 
-        let l1r_qwe = list_1_rollups.get("qwe").unwrap();
+        let l1r_qwe = list_1_rollups.iter().find(|r| r.name == "qwe").unwrap();
         assert_eq!(2, l1r_qwe.total_lines);
         assert_eq!(4, l1r_qwe.total_units);
-        assert_eq!("qwe-source", l1r_qwe.total_amount.source);
-        assert_eq!(Decimal::from_str("9.36").unwrap(), l1r_qwe.total_amount.amount);
+        assert_eq!(price("9.36", "qwe-source"), l1r_qwe.value);
 
         assert_eq!(2, list_2_rollups.len());
     }
 
-    #[test]
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_all_lists_without_attributes_or_rollups() {
+        setup(true, true);
+        let results = ListOfListsService().retrieve_lists(
+            &context(user(), state()),
+            selector(),
+            paging(1, 1),
+            sort(SortKey::Id, false),
+            false,
+            None,
+        ).await.unwrap().lists;
+        assert_eq!(1, results.len());
+        assert_eq!(2, results[0].id.unwrap());
+        assert!(results[0].attributes.is_empty());
+        assert!(results[0].items.is_none());
+        assert!(results[0].rollups.is_none());
+    }
+
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_name() {
+    async fn test_retrieve_all_lists_by_name() {
         setup(false, false);
         let sort_request = sort(SortKey::Name, false);
         let results = ListOfListsService().retrieve_lists(
@@ -363,17 +649,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!("A3 Naming", results[0].name);
         assert_eq!("B1 My Name", results[1].name);
         assert_eq!("C2 Your Name", results[2].name);
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_id_descending() {
+    async fn test_retrieve_all_lists_by_id_descending() {
         setup(false, false);
         let sort_request = sort(SortKey::Id, true);
         let results = ListOfListsService().retrieve_lists(
@@ -382,17 +668,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(3, results[0].id.unwrap());
         assert_eq!(2, results[1].id.unwrap());
         assert_eq!(1, results[2].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_name_descending() {
+    async fn test_retrieve_all_lists_by_name_descending() {
         setup(false, false);
         let sort_request = sort(SortKey::Name, true);
         let results = ListOfListsService().retrieve_lists(
@@ -401,17 +687,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!("C2 Your Name", results[0].name);
         assert_eq!("B1 My Name", results[1].name);
         assert_eq!("A3 Naming", results[2].name);
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_create_date() {
+    async fn test_retrieve_all_lists_by_create_date() {
         setup(false, false);
         let sort_request = sort(SortKey::CreatedDate, false);
         let results = ListOfListsService().retrieve_lists(
@@ -420,17 +706,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(2, results[0].id.unwrap());
         assert_eq!(1, results[1].id.unwrap());
         assert_eq!(3, results[2].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_modified_date_descending() {
+    async fn test_retrieve_all_lists_by_modified_date_descending() {
         setup(false, false);
         let sort_request = sort(SortKey::ModifiedDate, true);
         let results = ListOfListsService().retrieve_lists(
@@ -439,17 +725,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(2, results[0].id.unwrap());
         assert_eq!(3, results[1].id.unwrap());
         assert_eq!(1, results[2].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_nonexistent_attribute_descending() {
+    async fn test_retrieve_all_lists_by_nonexistent_attribute_descending() {
         setup(false, false);
         let sort_request = sort(SortKey::Attribute("does not exist".to_string()), false);
         let results = ListOfListsService().retrieve_lists(
@@ -458,17 +744,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(1, results[0].id.unwrap());
         assert_eq!(2, results[1].id.unwrap());
         assert_eq!(3, results[2].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_boolean_attribute_descending() {
+    async fn test_retrieve_all_lists_by_boolean_attribute_descending() {
         setup(false, true);
         let sort_request = sort(SortKey::Attribute("my boolean".to_string()), true);
         let results = ListOfListsService().retrieve_lists(
@@ -477,17 +763,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(1, results[0].id.unwrap()); // has true
         assert_eq!(3, results[1].id.unwrap()); // has false, id (descending) tie-breaker
         assert_eq!(2, results[2].id.unwrap()); // has false, id (descending) tie-breaker
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_float_attribute() {
+    async fn test_retrieve_all_lists_by_float_attribute() {
         setup(false, true);
         let sort_request = sort(SortKey::Attribute("my float".to_string()), false);
         let results = ListOfListsService().retrieve_lists(
@@ -496,17 +782,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(3, results[0].id.unwrap()); // -3.1
         assert_eq!(2, results[1].id.unwrap()); // -2.1
         assert_eq!(1, results[2].id.unwrap()); // -1.1
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_integer_attribute() {
+    async fn test_retrieve_all_lists_by_integer_attribute() {
         setup(false, false);
         let sort_request = sort(SortKey::Attribute("my integer".to_string()), false);
         let results = ListOfListsService().retrieve_lists(
@@ -515,36 +801,65 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(1, results[0].id.unwrap());
         assert_eq!(2, results[1].id.unwrap());
         assert_eq!(3, results[2].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_price_attribute() {
+    async fn test_retrieve_all_lists_by_attribute_of_mixed_types() {
         setup(false, true);
-        let sort_request = sort(SortKey::Attribute("my price".to_string()), false);
+        {
+            let mut c = db::connection().unwrap();
+            sql_query(r#"
+            insert into item_list_attribute (id, item_list_id, name, type, text_val) values (17, 1, 'mixed', 'Text', 'a')
+                "#).execute(&mut c).unwrap();
+            sql_query(r#"
+            insert into item_list_attribute (id, item_list_id, name, type, integer_val) values (27, 2, 'mixed', 'Integer', 5)
+                "#).execute(&mut c).unwrap();
+            sql_query(r#"
+            insert into item_list_attribute (id, item_list_id, name, type, integer_val) values (37, 3, 'mixed', 'Integer', 2)
+                "#).execute(&mut c).unwrap();
+        }
+        // Most of the lists have an Integer, so the one with Text sorts as if it has none.
+        let sort_request = sort(SortKey::Attribute("mixed".to_string()), false);
         let results = ListOfListsService().retrieve_lists(
             &context(user(), state()),
             selector(),
             paging(0, 10),
             sort_request,
             true,
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
+        assert_eq!(vec![3, 2, 1], results.iter().map(|list| list.id.unwrap()).collect::<Vec<_>>());
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_all_lists_by_price_attribute() {
+        setup(false, true);
+        let sort_request = sort(SortKey::Attribute("my price".to_string()), false);
+        let results = ListOfListsService().retrieve_lists(
+            &context(user(), state()),
+            selector(),
+            paging(0, 10),
+            sort_request,
             true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(2, results[0].id.unwrap());
         assert_eq!(3, results[1].id.unwrap());
         assert_eq!(1, results[2].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_text_attribute() {
+    async fn test_retrieve_all_lists_by_text_attribute() {
         setup(false, true);
         let sort_request = sort(SortKey::Attribute("my text".to_string()), false);
         let results = ListOfListsService().retrieve_lists(
@@ -553,17 +868,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(2, results[0].id.unwrap()); // archive C2 Your Name
         assert_eq!(3, results[1].id.unwrap()); // default A3 naming
         assert_eq!(1, results[2].id.unwrap()); // default B1 My Name
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_by_date_attribute() {
+    async fn test_retrieve_all_lists_by_date_attribute() {
         setup(false, true);
         let sort_request = sort(SortKey::Attribute("my date".to_string()), false);
         let results = ListOfListsService().retrieve_lists(
@@ -572,17 +887,17 @@ mod tests {
             paging(0, 10),
             sort_request,
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(2, results[0].id.unwrap());
         assert_eq!(1, results[1].id.unwrap());
         assert_eq!(3, results[2].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_with_paging() {
+    async fn test_retrieve_all_lists_with_paging() {
         setup(false, false);
         let results = ListOfListsService().retrieve_lists(
             &context(user(), state()),
@@ -590,15 +905,18 @@ mod tests {
             paging(1, 1),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
-        assert_eq!(1, results.len());
-        assert_eq!(2, results[0].id.unwrap());
+            Some(RollupRequest::default()),
+        ).await.unwrap();
+        assert_eq!(3, results.total);
+        assert_eq!(1, results.start);
+        assert_eq!(1, results.rows);
+        assert_eq!(1, results.lists.len());
+        assert_eq!(2, results.lists[0].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_with_paging_beyond_end() {
+    async fn test_retrieve_all_lists_with_paging_beyond_end() {
         setup(false, false);
         let results = ListOfListsService().retrieve_lists(
             &context(user(), state()),
@@ -606,14 +924,15 @@ mod tests {
             paging(3, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
-        assert_eq!(0, results.len());
+            Some(RollupRequest::default()),
+        ).await.unwrap();
+        assert_eq!(3, results.total);
+        assert!(results.lists.is_empty());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_all_lists_with_no_rows_requested() {
+    async fn test_retrieve_all_lists_with_no_rows_requested() {
         setup(false, false);
         let results = ListOfListsService().retrieve_lists(
             &context(user(), state()),
@@ -621,14 +940,15 @@ mod tests {
             paging(0, 0),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
-        assert_eq!(0, results.len());
+            Some(RollupRequest::default()),
+        ).await.unwrap();
+        assert_eq!(3, results.total);
+        assert!(results.lists.is_empty());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_not_deleted_lists_by_id() {
+    async fn test_retrieve_not_deleted_lists_by_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_show_deleted = false;
@@ -638,16 +958,16 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(2, results.len());
         assert_eq!(2, results[0].id.unwrap());
         assert_eq!(3, results[1].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_deleted_lists_by_id() {
+    async fn test_retrieve_deleted_lists_by_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_show_not_deleted = false;
@@ -657,15 +977,15 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(1, results.len());
         assert_eq!(1, results[0].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_editable_lists_by_id() {
+    async fn test_retrieve_editable_lists_by_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_show_read_only = false;
@@ -675,8 +995,8 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         //TODO: read-only lists are not implemented yet
         assert_eq!(3, results.len());
         assert_eq!(1, results[0].id.unwrap());
@@ -684,11 +1004,24 @@ mod tests {
         assert_eq!(3, results[2].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_lists_in_archive_folder_by_id() {
+    async fn test_retrieve_lists_in_archive_folder_by_id() {
         setup(false, false);
         let mut selector = selector();
+        selector.limit_in_folders = vec!["default".to_string()];
+        let results = ListOfListsService().retrieve_lists(
+            &context(user(), state()),
+            selector.clone(),
+            paging(1, 1),
+            sort(SortKey::Id, false),
+            true,
+            Some(RollupRequest::default()),
+        ).await.unwrap();
+        assert_eq!(2, results.total);
+        assert_eq!(1, results.lists.len());
+        assert_eq!(3, results.lists[0].id.unwrap());
+
         selector.limit_in_folders = vec!["archive".to_string()];
         let results = ListOfListsService().retrieve_lists(
             &context(user(), state()),
@@ -696,15 +1029,16 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
-        assert_eq!(1, results.len());
-        assert_eq!(2, results[0].id.unwrap());
+            Some(RollupRequest::default()),
+        ).await.unwrap();
+        assert_eq!(1, results.total);
+        assert_eq!(1, results.lists.len());
+        assert_eq!(2, results.lists[0].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_private_lists_by_id() {
+    async fn test_retrieve_private_lists_by_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_list_access = vec![ListAccess::Private];
@@ -714,15 +1048,15 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(1, results.len());
         assert_eq!(2, results[0].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_public_or_shared_lists_by_id() {
+    async fn test_retrieve_public_or_shared_lists_by_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_list_access = vec![ListAccess::Public, ListAccess::Shared];
@@ -732,16 +1066,16 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(2, results.len());
         assert_eq!(1, results[0].id.unwrap());
         assert_eq!(3, results[1].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_transient_lists_by_id() {
+    async fn test_retrieve_transient_lists_by_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_list_types = vec![ListType::Transient];
@@ -751,15 +1085,15 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(1, results.len());
         assert_eq!(3, results[0].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_standard_or_program_lists_by_id() {
+    async fn test_retrieve_standard_or_program_lists_by_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_list_types = vec![ListType::Standard, ListType::System];
@@ -769,16 +1103,16 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(2, results.len());
         assert_eq!(1, results[0].id.unwrap());
         assert_eq!(2, results[1].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_lists_with_keyword_by_id() {
+    async fn test_retrieve_lists_with_keyword_by_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_name_keywords = Some("name".to_string());
@@ -788,16 +1122,16 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(2, results.len());
         assert_eq!(1, results[0].id.unwrap());
         assert_eq!(2, results[1].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_lists_with_wildcard_keyword_by_id() {
+    async fn test_retrieve_lists_with_wildcard_keyword_by_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_name_keywords = Some("nam*".to_string());
@@ -807,35 +1141,141 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(3, results.len());
         assert_eq!(1, results[0].id.unwrap());
         assert_eq!(2, results[1].id.unwrap());
         assert_eq!(3, results[2].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_lists_with_multiple_keyword_by_id() {
+    async fn test_retrieve_lists_with_like_wildcard_keyword() {
         setup(false, false);
         let mut selector = selector();
-        selector.limit_name_keywords = Some("Nam* c2".to_string());
+        selector.limit_name_keywords = Some("n%".to_string());
         let results = ListOfListsService().retrieve_lists(
             &context(user(), state()),
             selector,
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
+        assert_eq!(0, results.len());
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_lists_with_multiple_keyword_by_id() {
+        setup(false, false);
+        let mut selector = selector();
+        selector.limit_name_keywords = Some("Nam* c2".to_string());
+        let results = ListOfListsService().retrieve_lists(
+            &context(user(), state()),
+            selector,
+            paging(0, 10),
+            sort(SortKey::Id, false),
             true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(1, results.len());
         assert_eq!(2, results[0].id.unwrap());
     }
 
-    #[test]
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_lists_by_search() {
+        setup(false, false);
+        let db_context = &db_context(user(), state());
+        let text = |t: &str| HashMap::from([("note".to_string(), ListAttribute::Text(t.to_string()))]);
+        let create = async |name: &str, attributes: HashMap<String, ListAttribute>, items: Vec<(&str, HashMap<String, ListAttribute>)>| {
+            let id = crate::list_service::create_list(db_context, ItemListUpdate {
+                attributes: Some(attributes),
+                name: Some(name.to_string()),
+                ..Default::default()
+            }).await.unwrap().id.unwrap();
+            let item_ids: Vec<u64> = items.into_iter().map(|(name, attributes)| {
+                crate::list_item_service::add_item(db_context, id, ListItemUpdate {
+                    attributes: Some(attributes),
+                    name: Some(name.to_string()),
+                    source: Some("grocer".to_string()),
+                    ..Default::default()
+                }).unwrap().id.unwrap()
+            }).collect();
+            (id, item_ids)
+        };
+        let (basket, _) = create("Fruit Basket", HashMap::new(), vec![("Green Apples", text("organic")), ("Pears", HashMap::new())]).await;
+        let (shopping, shopping_items) = create("Weekly Shopping", text("for the party"), vec![("Apple Pie", HashMap::new())]).await;
+        let (supplies, _) = create("Party Supplies", HashMap::new(), vec![("Balloons", HashMap::new())]).await;
+
+        let search = async |query: &str, key: SortKey| {
+            let mut selector = selector();
+            selector.limit_search = Some(SearchQuery::from_str(query).unwrap());
+            ListOfListsService().retrieve_lists(&context(user(), state()), selector, paging(0, 10), sort(key, false), false, None).await
+                .unwrap()
+                .lists
+                .iter()
+                .map(|l| l.id.unwrap())
+                .collect::<Vec<u64>>()
+        };
+        assert_eq!(vec![basket], search("apples", SortKey::Id).await);
+        assert_eq!(vec![basket], search("ORGANIC", SortKey::Id).await);
+        assert_eq!(vec![basket, shopping], search("apple*", SortKey::Id).await);
+        assert_eq!(vec![basket], search("\"green apples\"", SortKey::Id).await);
+        assert!(search("\"apples green\"", SortKey::Id).await.is_empty());
+        assert_eq!(vec![basket], search("fruit AND pears", SortKey::Id).await);
+        assert_eq!(vec![basket, supplies], search("basket OR balloons", SortKey::Id).await);
+        assert_eq!(vec![basket], search("apple* NOT pie", SortKey::Id).await);
+        // A list's name counts for more than its attributes.
+        assert_eq!(vec![supplies, shopping], search("party", SortKey::Relevance).await);
+
+        crate::list_service::update_list(db_context, supplies, None, ItemListUpdate {
+            name: Some("Celebration Supplies".to_string()),
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!(vec![shopping], search("party", SortKey::Relevance).await);
+        crate::list_item_service::delete_item(db_context, shopping, shopping_items[0], None).unwrap();
+        assert_eq!(vec![basket], search("apple*", SortKey::Id).await);
+
+        let mut by_relevance = selector();
+        by_relevance.limit_search = Some(SearchQuery::from_str("supplies OR basket OR party").unwrap());
+        let mut cursor = None;
+        let mut pages = vec![];
+        loop {
+            let response = ListOfListsService().retrieve_lists(
+                &context(user(), state()),
+                by_relevance.clone(),
+                PagingRequest { start: 0, rows: 2, cursor },
+                sort(SortKey::Relevance, false),
+                false,
+                None,
+            ).await.unwrap();
+            assert_eq!(3, response.total);
+            pages.extend(response.lists.iter().map(|l| l.id.unwrap()));
+            cursor = response.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        pages.sort();
+        assert_eq!(vec![basket, shopping, supplies], pages);
+
+        let result = ListOfListsService().retrieve_lists(
+            &context(user(), state()),
+            selector(),
+            paging(0, 10),
+            sort(SortKey::Relevance, false),
+            false,
+            None,
+        ).await;
+        assert!(matches!(result, Err(ListManagementError::Validation(_))));
+    }
+
+    #[actix_web::test]
     #[serial]
-    fn test_retrieve_lists_by_list_id() {
+    async fn test_retrieve_lists_by_list_id() {
         setup(false, false);
         let mut selector = selector();
         selector.limit_list_ids = vec![1, 2];
@@ -845,15 +1285,467 @@ mod tests {
             paging(0, 10),
             sort(SortKey::Id, false),
             true,
-            true,
-        );
+            Some(RollupRequest::default()),
+        ).await.unwrap().lists;
         assert_eq!(2, results.len());
         assert_eq!(1, results[0].id.unwrap());
         assert_eq!(2, results[1].id.unwrap());
     }
 
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_lists_by_attributes() {
+        setup(false, true);
+        let filtered = async |filters: Vec<AttributeFilter>| {
+            let mut selector = selector();
+            selector.limit_attributes = filters;
+            ListOfListsService().retrieve_lists(&context(user(), state()), selector, paging(0, 10), sort(SortKey::Id, false), false, None).await
+                .unwrap()
+                .lists
+                .iter()
+                .map(|l| l.id.unwrap())
+                .collect::<Vec<u64>>()
+        };
+        let is = |name: &str, condition: AttributeCondition| AttributeFilter::Predicate(AttributePredicate {
+            name: name.to_string(),
+            condition,
+        });
+        let compare = |name: &str, comparison: Comparison, value: ListAttribute| {
+            is(name, AttributeCondition::Compare(comparison, value))
+        };
+        let date = |d: &str| ListAttribute::DateTime(NaiveDateTime::from_str(d).unwrap());
+
+        assert_eq!(vec![1], filtered(vec![compare("my boolean", Comparison::Eq, ListAttribute::Boolean(true))]).await);
+        assert_eq!(vec![2, 3], filtered(vec![compare("my boolean", Comparison::Ne, ListAttribute::Boolean(true))]).await);
+        assert_eq!(vec![1, 3], filtered(vec![compare("my date", Comparison::Ge, date("2024-07-20T00:00:00"))]).await);
+        assert_eq!(vec![2, 3], filtered(vec![compare("my float", Comparison::Lt, ListAttribute::Float(-2.0))]).await);
+        assert_eq!(vec![1, 2, 3], filtered(vec![compare("my integer", Comparison::Eq, ListAttribute::Integer(1))]).await);
+        assert_eq!(vec![3], filtered(vec![compare("my text", Comparison::Eq, ListAttribute::Text("default A3 Naming".to_string()))]).await);
+        assert_eq!(vec![1, 2], filtered(vec![is("my text", AttributeCondition::Contains("NAME".to_string()))]).await);
+        assert_eq!(vec![1, 2, 3], filtered(vec![is("my text", AttributeCondition::Exists)]).await);
+        assert!(filtered(vec![is("my text", AttributeCondition::Missing)]).await.is_empty());
+        assert_eq!(vec![1, 2, 3], filtered(vec![is("no such attribute", AttributeCondition::Missing)]).await);
+        // A range is two comparisons, each having to hold.
+        let price = |amount: &str, source: &str| ListAttribute::Price(Price { amount: Decimal::from_str(amount).unwrap(), source: source.to_string() });
+        assert_eq!(vec![3], filtered(vec![
+            compare("my price", Comparison::Gt, price("2.00", "a-source")),
+            compare("my price", Comparison::Lt, price("3.00", "a-source")),
+        ]).await);
+        assert!(filtered(vec![compare("my price", Comparison::Gt, price("2.00", "b-source"))]).await.is_empty());
+        // Amounts compare by value, whatever their decimal places.
+        assert_eq!(vec![2], filtered(vec![compare("my price", Comparison::Eq, price("1.1100", "a-source"))]).await);
+        assert_eq!(vec![1, 2, 3], filtered(vec![compare("my price", Comparison::Lt, price("10", "a-source"))]).await);
+        assert_eq!(vec![1, 3], filtered(vec![compare("my price", Comparison::Gt, ListAttribute::Integer(2))]).await);
+        assert_eq!(vec![1, 2], filtered(vec![AttributeFilter::Or(vec![
+            compare("my boolean", Comparison::Eq, ListAttribute::Boolean(true)),
+            AttributeFilter::And(vec![
+                compare("my float", Comparison::Lt, ListAttribute::Float(-2.0)),
+                compare("my date", Comparison::Lt, date("2024-07-20T00:00:00")),
+            ]),
+        ])]).await);
+        assert!(filtered(vec![AttributeFilter::Or(vec![])]).await.is_empty());
+        assert_eq!(vec![1, 2, 3], filtered(vec![AttributeFilter::And(vec![])]).await);
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_lists_with_cursor() {
+        setup(false, true);
+        {
+            let mut c = db::connection().unwrap();
+            sql_query(r#"
+            insert into item_list_attribute (id, item_list_id, name, type, integer_val)
+            values (27, 2, 'only two', 'Integer', 5)
+                "#).execute(&mut c).unwrap();
+        }
+        let sorts = vec![
+            sort(SortKey::Id, false),
+            sort(SortKey::Id, true),
+            sort(SortKey::Name, true),
+            sort(SortKey::CreatedDate, false),
+            sort(SortKey::ModifiedDate, true),
+            sort(SortKey::Attribute("my boolean".to_string()), false),
+            sort(SortKey::Attribute("my boolean".to_string()), true),
+            sort(SortKey::Attribute("my float".to_string()), true),
+            sort(SortKey::Attribute("my price".to_string()), false),
+            sort(SortKey::Attribute("my text".to_string()), true),
+            sort(SortKey::Attribute("only two".to_string()), false),
+            sort(SortKey::Attribute("only two".to_string()), true),
+            sort(SortKey::Attribute("nonexistent".to_string()), true),
+        ];
+        for sort_request in sorts {
+            let all: Vec<u64> = ListOfListsService().retrieve_lists(
+                &context(user(), state()),
+                selector(),
+                paging(0, 10),
+                sort_request.clone(),
+                false,
+                None,
+            ).await.unwrap().lists.iter().map(|l| l.id.unwrap()).collect();
+
+            let mut paged: Vec<u64> = vec![];
+            let mut cursor: Option<String> = None;
+            loop {
+                let page = ListOfListsService().retrieve_lists(
+                    &context(user(), state()),
+                    selector(),
+                    PagingRequest { start: 0, rows: 2, cursor },
+                    sort_request.clone(),
+                    false,
+                    None,
+                ).await.unwrap();
+                assert_eq!(3, page.total);
+                assert!(page.lists.iter().all(|l| l.attributes.is_empty()));
+                paged.extend(page.lists.iter().map(|l| l.id.unwrap()));
+                if page.next_cursor.is_none() {
+                    break;
+                }
+                cursor = page.next_cursor;
+            }
+            assert_eq!(all, paged, "{:?}", sort_request);
+        }
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_lists_with_cursor_for_another_sort() {
+        setup(false, false);
+        let page = ListOfListsService().retrieve_lists(
+            &context(user(), state()),
+            selector(),
+            paging(0, 1),
+            sort(SortKey::Name, false),
+            true,
+            Some(RollupRequest::default()),
+        ).await.unwrap();
+        let results = ListOfListsService().retrieve_lists(
+            &context(user(), state()),
+            selector(),
+            PagingRequest { start: 0, rows: 1, cursor: page.next_cursor },
+            sort(SortKey::Name, true),
+            true,
+            Some(RollupRequest::default()),
+        ).await;
+        assert!(matches!(results, Err(ListManagementError::Validation(_))));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_lists_with_cursor_over_written_fractions() {
+        setup(false, true);
+        let midnight = NaiveDateTime::from_str("2024-07-20T00:00:00").unwrap();
+        {
+            // Written by Diesel as "2024-07-20 00:00:00", the same time as list 1's "...00.000".
+            let mut c = db::connection().unwrap();
+            sql_query("update item_list set created = $1 where id = 2")
+                .bind::<diesel::sql_types::Timestamp, _>(midnight)
+                .execute(&mut c)
+                .unwrap();
+            sql_query("update item_list_attribute set timestamp_val = $1 where id = 23")
+                .bind::<diesel::sql_types::Timestamp, _>(midnight)
+                .execute(&mut c)
+                .unwrap();
+        }
+        let sorts = vec![
+            (sort(SortKey::CreatedDate, false), vec![1, 2, 3]),
+            (sort(SortKey::CreatedDate, true), vec![3, 2, 1]),
+            (sort(SortKey::Attribute("my date".to_string()), false), vec![1, 2, 3]),
+            (sort(SortKey::Attribute("my date".to_string()), true), vec![3, 2, 1]),
+        ];
+        for (sort_request, expected) in sorts {
+            let mut paged: Vec<u64> = vec![];
+            let mut cursor: Option<String> = None;
+            loop {
+                let page = ListOfListsService().retrieve_lists(
+                    &context(user(), state()),
+                    selector(),
+                    PagingRequest { start: 0, rows: 1, cursor },
+                    sort_request.clone(),
+                    false,
+                    None,
+                ).await.unwrap();
+                paged.extend(page.lists.iter().map(|l| l.id.unwrap()));
+                if page.next_cursor.is_none() || paged.len() > expected.len() {
+                    break;
+                }
+                cursor = page.next_cursor;
+            }
+            assert_eq!(expected, paged, "{:?}", sort_request);
+        }
+
+        let mut selector = selector();
+        selector.limit_attributes = vec![AttributeFilter::Predicate(AttributePredicate {
+            name: "my date".to_string(),
+            condition: AttributeCondition::Compare(Comparison::Eq, ListAttribute::DateTime(midnight)),
+        })];
+        let results = ListOfListsService().retrieve_lists(
+            &context(user(), state()),
+            selector,
+            paging(0, 10),
+            sort(SortKey::Id, false),
+            false,
+            None,
+        ).await.unwrap();
+        assert_eq!(vec![1, 2], results.lists.iter().map(|l| l.id.unwrap()).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_rollups_by_currency() {
+        let items = vec![
+            item("a", &[("quantity", ListAttribute::Integer(2)), ("cost", price("10.00", "USD")), ("shipping", price("1.00", "USD"))]),
+            item("a", &[("quantity", ListAttribute::Integer(1)), ("cost", price("5.00", "EUR"))]),
+            item("a", &[("quantity", ListAttribute::Integer(3)), ("cost", price("1.50", "USD")), ("shipping", price("100", "JPY"))]),
+        ];
+        let definitions = vec![
+            definition("cost", RollupFunction::Sum, "cost", Some("quantity")),
+            definition("shipping", RollupFunction::Sum, "shipping", Some("quantity")),
+        ];
+        let rollups = values(compute_rollups(&items, &definitions));
+        let summary: Vec<(&str, ListAttribute, u64, u64)> = rollups
+            .iter()
+            .map(|r| (r.name.as_str(), r.value.clone(), r.total_lines, r.total_units))
+            .collect();
+        assert_eq!(
+            vec![
+                ("cost", price("5.00", "EUR"), 1, 1),
+                ("cost", price("24.50", "USD"), 2, 5),
+                ("shipping", price("300", "JPY"), 1, 3),
+                ("shipping", price("2.00", "USD"), 1, 2),
+            ],
+            summary,
+        );
+
+        let converter = StaticRateConverter::parse("USD=1,EUR=1.10").unwrap();
+        let converted = convert_rollup_values(&rollups, "USD", &converter);
+        assert_eq!(1, converted.len());
+        assert_eq!("cost", converted[0].name);
+        assert_eq!(price("30.00", "USD"), converted[0].value);
+        assert_eq!(3, converted[0].total_lines);
+        assert_eq!(6, converted[0].total_units);
+    }
+
+    #[test]
+    fn test_rollup_definitions() {
+        let items = vec![
+            item("grocer", &[
+                ("quantity", ListAttribute::Integer(2)),
+                ("weight", ListAttribute::Float(1.5)),
+                ("price", price("4.00", "USD")),
+                ("urgent", ListAttribute::Boolean(true)),
+            ]),
+            item("grocer", &[
+                ("quantity", ListAttribute::Integer(1)),
+                ("weight", ListAttribute::Float(0.25)),
+                ("price", price("1.00", "USD")),
+                ("urgent", ListAttribute::Boolean(false)),
+            ]),
+            item("baker", &[("quantity", ListAttribute::Integer(4)), ("price", price("2.50", "USD"))]),
+        ];
+        let definitions = vec![
+            definition("weight", RollupFunction::Sum, "weight", Some("quantity")),
+            definition("units", RollupFunction::Sum, "quantity", None),
+            definition("cheapest", RollupFunction::Min, "price", None),
+            definition("dearest", RollupFunction::Max, "price", None),
+            definition("average", RollupFunction::Avg, "price", None),
+            definition("average quantity", RollupFunction::Avg, "quantity", None),
+            definition("urgent", RollupFunction::Count, "urgent", None),
+            definition("missing", RollupFunction::Sum, "missing", None),
+            RollupDefinition {
+                name: "sources".to_string(),
+                function: RollupFunction::CountDistinct,
+                of: RollupField::Source,
+                multiply_by: None,
+            },
+        ];
+        let rollups = values(compute_rollups(&items, &definitions));
+        let values: Vec<(&str, ListAttribute)> = rollups.iter().map(|r| (r.name.as_str(), r.value.clone())).collect();
+        assert_eq!(
+            vec![
+                ("weight", ListAttribute::Float(3.25)),
+                ("units", ListAttribute::Integer(7)),
+                ("cheapest", price("1.00", "USD")),
+                ("dearest", price("4.00", "USD")),
+                ("average", price("2.50", "USD")),
+                ("average quantity", ListAttribute::Float(7.0 / 3.0)),
+                ("urgent", ListAttribute::Integer(1)),
+                ("sources", ListAttribute::Integer(2)),
+            ],
+            values,
+        );
+        assert_eq!(2, rollups[0].total_lines);
+        assert_eq!(3, rollups[0].total_units);
+        assert_eq!(3, rollups[7].total_lines);
+
+        let converter = StaticRateConverter::parse("USD=2,EUR=1").unwrap();
+        let converted = convert_rollup_values(&rollups, "EUR", &converter);
+        let values: Vec<(&str, ListAttribute)> = converted.iter().map(|r| (r.name.as_str(), r.value.clone())).collect();
+        assert_eq!(
+            vec![
+                ("cheapest", price("2.00", "EUR")),
+                ("dearest", price("8.00", "EUR")),
+                ("average", price("5.00", "EUR")),
+            ],
+            values,
+        );
+    }
+
+    #[test]
+    fn test_converted_rollups_too_large() {
+        let rollup = |name: &str, function: RollupFunction, amount: Decimal, source: &str, total_lines: u64| ItemListRollup {
+            name: name.to_string(),
+            function,
+            value: ListAttribute::Price(Price { amount, source: source.to_string() }),
+            total_lines,
+            total_units: 0,
+        };
+        let rollups = vec![
+            rollup("cost", RollupFunction::Sum, Decimal::MAX, "USD", 1),
+            rollup("cost", RollupFunction::Sum, Decimal::MAX, "EUR", 1),
+            rollup("average", RollupFunction::Avg, Decimal::MAX, "USD", 2),
+            rollup("average", RollupFunction::Avg, Decimal::MAX, "EUR", 2),
+            rollup("dearest", RollupFunction::Max, Decimal::MAX, "USD", 1),
+            rollup("dearest", RollupFunction::Max, Decimal::ONE, "EUR", 1),
+        ];
+        let converter = StaticRateConverter::parse("USD=1,EUR=1").unwrap();
+        let converted = convert_rollup_values(&rollups, "USD", &converter);
+        assert_eq!(vec![rollup("dearest", RollupFunction::Max, Decimal::MAX, "USD", 2)], converted);
+    }
+
+    #[test]
+    fn test_rollups_follow_item_changes() {
+        let mut items = vec![
+            item("grocer", &[
+                ("quantity", ListAttribute::Integer(2)),
+                ("weight", ListAttribute::Float(1.5)),
+                ("size", ListAttribute::Integer(3)),
+                ("price", price("4.00", "USD")),
+                ("urgent", ListAttribute::Boolean(true)),
+            ]),
+            item("grocer", &[
+                ("quantity", ListAttribute::Integer(1)),
+                ("weight", ListAttribute::Float(0.25)),
+                ("size", ListAttribute::Float(0.5)),
+                ("price", price("1.00", "USD")),
+            ]),
+            item("baker", &[("quantity", ListAttribute::Integer(4)), ("price", price("2.50", "USD"))]),
+        ];
+        let mut definitions = vec![
+            definition("weight", RollupFunction::Sum, "weight", Some("quantity")),
+            definition("size", RollupFunction::Sum, "size", None),
+            definition("cheapest", RollupFunction::Min, "price", None),
+            definition("dearest", RollupFunction::Max, "price", None),
+            definition("average", RollupFunction::Avg, "price", Some("quantity")),
+            definition("urgent", RollupFunction::Count, "urgent", None),
+        ];
+        // Applies the change to the rollups of the items, and if that can be done, checks that it
+        // gives what recomputing them would.
+        let change = |items: &mut Vec<ListItem>, definitions: &[RollupDefinition], index: Option<usize>, after: Option<ListItem>| {
+            let mut rollups = compute_rollups(items, definitions);
+            let before = index.map(|index| items[index].clone());
+            let applied = apply_item_change(&mut rollups, definitions, before.as_ref(), after.as_ref());
+            match (index, after) {
+                (Some(index), Some(after)) => items[index] = after,
+                (Some(index), None) => drop(items.remove(index)),
+                (None, Some(after)) => items.push(after),
+                (None, None) => {}
+            }
+            if applied {
+                assert_eq!(compute_rollups(items, definitions), rollups);
+            }
+            applied
+        };
+
+        let added = item("baker", &[
+            ("quantity", ListAttribute::Integer(2)),
+            ("weight", ListAttribute::Float(0.5)),
+            ("size", ListAttribute::Integer(-2)),
+            ("price", price("3.00", "USD")),
+            ("urgent", ListAttribute::Boolean(true)),
+        ]);
+        assert!(change(&mut items, &definitions, None, Some(added)));
+        let mut more = items[0].clone();
+        more.attributes.insert("quantity".to_string(), ListAttribute::Integer(3));
+        more.attributes.insert("urgent".to_string(), ListAttribute::Boolean(false));
+        assert!(change(&mut items, &definitions, Some(0), Some(more)));
+        // The last Float goes, so the size is an Integer again.
+        let mut whole = items[1].clone();
+        whole.attributes.insert("size".to_string(), ListAttribute::Integer(5));
+        assert!(change(&mut items, &definitions, Some(1), Some(whole)));
+        assert_eq!(ListAttribute::Integer(6), values(compute_rollups(&items, &definitions))[1].value);
+        assert!(change(&mut items, &definitions, Some(2), None));
+
+        // The cheapest item gets dearer.
+        let mut dearer = items[1].clone();
+        dearer.attributes.insert("price".to_string(), price("2.00", "USD"));
+        assert!(!change(&mut items, &definitions, Some(1), Some(dearer)));
+        // A new currency.
+        let euros = item("baker", &[("price", price("2.00", "EUR"))]);
+        assert!(!change(&mut items, &definitions, None, Some(euros)));
+        // Neither the cheapest nor the dearest item, then the dearest.
+        assert!(change(&mut items, &definitions, Some(2), None));
+        assert!(!change(&mut items, &definitions, Some(0), None));
+
+        definitions.push(RollupDefinition {
+            name: "sources".to_string(),
+            function: RollupFunction::CountDistinct,
+            of: RollupField::Source,
+            multiply_by: None,
+        });
+        let mut renamed = items[0].clone();
+        renamed.name = "renamed".to_string();
+        assert!(change(&mut items, &definitions, Some(0), Some(renamed)));
+        assert!(!change(&mut items, &definitions, None, Some(item("grocer", &[]))));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    async fn test_retrieve_lists_with_currency_but_no_rates() {
+        setup(true, true);
+        let results = ListOfListsService().retrieve_lists(
+            &context(user(), state()),
+            selector(),
+            paging(0, 10),
+            sort(SortKey::Id, false),
+            false,
+            Some(RollupRequest { currency: Some("USD".to_string()) }),
+        ).await;
+        assert!(matches!(results, Err(ListManagementError::Validation(_))));
+    }
+
+    fn item(source: &str, attributes: &[(&str, ListAttribute)]) -> ListItem {
+        ListItem {
+            id: None,
+            attributes: attributes.iter().map(|(name, attr)| (name.to_string(), attr.clone())).collect(),
+            created: Default::default(),
+            modified: Default::default(),
+            name: "item".to_string(),
+            source: source.to_string(),
+            version: 1,
+        }
+    }
+
+    fn price(amount: &str, source: &str) -> ListAttribute {
+        ListAttribute::Price(Price {
+            amount: Decimal::from_str(amount).unwrap(),
+            source: source.to_string(),
+        })
+    }
+
+    fn definition(name: &str, function: RollupFunction, attribute: &str, multiply_by: Option<&str>) -> RollupDefinition {
+        RollupDefinition {
+            name: name.to_string(),
+            function,
+            of: RollupField::Attribute(attribute.to_string()),
+            multiply_by: multiply_by.map(str::to_string),
+        }
+    }
+
+    fn values(rollups: Vec<StoredRollup>) -> Vec<ItemListRollup> {
+        rollups.into_iter().map(|stored| stored.rollup).collect()
+    }
+
     fn paging(start: u64, rows: u64) -> PagingRequest {
-        PagingRequest { start, rows }
+        PagingRequest { start, rows, cursor: None }
     }
 
     fn selector() -> ListSelector {
@@ -866,6 +1758,8 @@ mod tests {
             limit_in_folders: vec![],
             limit_name_keywords: None,
             limit_list_ids: vec![],
+            limit_search: None,
+            limit_attributes: vec![],
         }
     }
 
@@ -873,15 +1767,9 @@ mod tests {
         SortRequest { descending, key }
     }
 
-    fn users() -> Vec<User> {
-        vec![user()]
-    }
-
     fn setup(need_items: bool, need_attributes: bool) {
-        let mut c = db::connection();
-        c.run_pending_migrations(MIGRATIONS)
-            .expect("Could not run migrations");
-        crate::test_helpers::cleanup_db(&mut c);
+        crate::test_helpers::setup_db();
+        let mut c = db::connection().unwrap();
 
         let _ = sql_query(r#"
         insert into account_type (id, name, source) values (1000, 'AT1', 'AT1 SOURCE')
@@ -891,7 +1779,7 @@ mod tests {
             "#).execute(&mut c);
 
         let _ = sql_query(r#"
-        insert into USER (id, name, source, source_id) values (1, 'One Name', 'user-source', 'ONE-ID')
+        insert into "user" (id, name, source, source_id) values (1, 'One Name', 'user-source', 'ONE-ID')
             "#).execute(&mut c).unwrap();
 
         let _ = sql_query(r#"
@@ -919,17 +1807,17 @@ mod tests {
 
         if need_attributes {
             let _ = sql_query(r#"
-            insert into item_list_attribute (id, item_list_id, name, type, text_val)
-            values (11, 1, 'my price', 'Price', 'PRICE: _3.33 _a-source')
-                "#).execute(&mut c).unwrap();
+            insert into item_list_attribute (id, item_list_id, name, type, price_amount, price_source)
+            values (11, 1, 'my price', 'Price', $1, 'a-source')
+                "#).bind::<Amount, _>(DbAmount(Decimal::from_str("3.33").unwrap())).execute(&mut c).unwrap();
             let _ = sql_query(r#"
-            insert into item_list_attribute (id, item_list_id, name, type, text_val)
-            values (21, 2, 'my price', 'Price', 'PRICE: _1.11 _a-source')
-                "#).execute(&mut c).unwrap();
+            insert into item_list_attribute (id, item_list_id, name, type, price_amount, price_source)
+            values (21, 2, 'my price', 'Price', $1, 'a-source')
+                "#).bind::<Amount, _>(DbAmount(Decimal::from_str("1.11").unwrap())).execute(&mut c).unwrap();
             let _ = sql_query(r#"
-            insert into item_list_attribute (id, item_list_id, name, type, text_val)
-            values (31, 3, 'my price', 'Price', 'PRICE: _2.22 _a-source')
-                "#).execute(&mut c).unwrap();
+            insert into item_list_attribute (id, item_list_id, name, type, price_amount, price_source)
+            values (31, 3, 'my price', 'Price', $1, 'a-source')
+                "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.22").unwrap())).execute(&mut c).unwrap();
 
             let _ = sql_query(r#"
             insert into item_list_attribute (id, item_list_id, name, type, bool_val)
@@ -959,15 +1847,15 @@ mod tests {
 
             let _ = sql_query(r#"
             insert into item_list_attribute (id, item_list_id, name, type, float_val)
-            values (14, 1, 'my float', 'Float', -0.1)
+            values (14, 1, 'my float', 'Float', -1.1)
                 "#).execute(&mut c).unwrap();
             let _ = sql_query(r#"
             insert into item_list_attribute (id, item_list_id, name, type, float_val)
-            values (24, 2, 'my float', 'Float', -0.1)
+            values (24, 2, 'my float', 'Float', -2.1)
                 "#).execute(&mut c).unwrap();
             let _ = sql_query(r#"
             insert into item_list_attribute (id, item_list_id, name, type, float_val)
-            values (34, 3, 'my float', 'Float', -0.1)
+            values (34, 3, 'my float', 'Float', -3.1)
                 "#).execute(&mut c).unwrap();
 
             let _ = sql_query(r#"
@@ -1075,67 +1963,68 @@ mod tests {
                     "#).execute(&mut c).unwrap();
 
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (1012, 102, 'xyz', 'Price', 'PRICE: _1.11 _xyz-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (1012, 102, 'xyz', 'Price', $1, 'xyz-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("1.11").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (1022, 202, 'xyz', 'Price', 'PRICE: _1.11 _xyz-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (1022, 202, 'xyz', 'Price', $1, 'xyz-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("1.11").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (1032, 302, 'xyz', 'Price', 'PRICE: _1.11 _xyz-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (1032, 302, 'xyz', 'Price', $1, 'xyz-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("1.11").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (2012, 101, 'xyz', 'Price', 'PRICE: _3.33 _xyz-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (2012, 101, 'xyz', 'Price', $1, 'xyz-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("3.33").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (2022, 201, 'xyz', 'Price', 'PRICE: _3.33 _xyz-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (2022, 201, 'xyz', 'Price', $1, 'xyz-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("3.33").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (2032, 301, 'xyz', 'Price', 'PRICE: _3.33 _xyz-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (2032, 301, 'xyz', 'Price', $1, 'xyz-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("3.33").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (3012, 103, 'xyz', 'Price', 'PRICE: _2.22 _xyz-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (3012, 103, 'xyz', 'Price', $1, 'xyz-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.22").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (3022, 203, 'xyz', 'Price', 'PRICE: _2.22 _xyz-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (3022, 203, 'xyz', 'Price', $1, 'xyz-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.22").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (3032, 303, 'xyz', 'Price', 'PRICE: _2.22 _xyz-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (3032, 303, 'xyz', 'Price', $1, 'xyz-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.22").unwrap())).execute(&mut c).unwrap();
 
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (2013, 201, 'qwe', 'Price', 'PRICE: _2.34 _qwe-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (2013, 201, 'qwe', 'Price', $1, 'qwe-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.34").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (3013, 301, 'qwe', 'Price', 'PRICE: _2.34 _qwe-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (3013, 301, 'qwe', 'Price', $1, 'qwe-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.34").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (2023, 202, 'qwe', 'Price', 'PRICE: _2.34 _qwe-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (2023, 202, 'qwe', 'Price', $1, 'qwe-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.34").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (3023, 302, 'qwe', 'Price', 'PRICE: _2.34 _qwe-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (3023, 302, 'qwe', 'Price', $1, 'qwe-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.34").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (2033, 203, 'qwe', 'Price', 'PRICE: _2.34 _qwe-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (2033, 203, 'qwe', 'Price', $1, 'qwe-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.34").unwrap())).execute(&mut c).unwrap();
                 let _ = sql_query(r#"
-                insert into list_item_attribute (id, list_item_id, name, type, text_val)
-                values (3033, 303, 'qwe', 'Price', 'PRICE: _2.34 _qwe-source')
-                    "#).execute(&mut c).unwrap();
+                insert into list_item_attribute (id, list_item_id, name, type, price_amount, price_source)
+                values (3033, 303, 'qwe', 'Price', $1, 'qwe-source')
+                    "#).bind::<Amount, _>(DbAmount(Decimal::from_str("2.34").unwrap())).execute(&mut c).unwrap();
             }
         }
+        crate::test_helpers::sync_id_sequences(&mut c);
     }
 }
@@ -1,9 +1,101 @@
-use crate::common::{ItemList, ListManagementError, LMContext, PagingRequest, SortKey, SortRequest};
-use crate::list_of_lists_service::{ListSelector,ListProvider};
+use std::collections::HashMap;
 
-pub fn retrieve_list(context: &impl LMContext, id: u64) -> Result<ItemList, ListManagementError> {
+
+use crate::common::{ItemList, ItemListUpdate, ListAccess, ListAttribute, ListManagementError, ListType, LMContext, PagingRequest, RollupRequest, SortKey, SortRequest};
+use crate::db::{blocking, DbPool};
+use crate::list_of_lists_service::ListSelector;
+use crate::rollup::RollupSpecification;
+
+static DEFAULT_FOLDER: &str = "default";
+
+pub async fn create_list(context: &impl LMContext, list: ItemListUpdate) -> Result<ItemList, ListManagementError> {
+    validate(&list)?;
+    let name = list.name.ok_or_else(|| ListManagementError::Validation("name is required".to_string()))?;
+    let pool = context.pool().clone();
+    let user_id = context.current_user_state().user_id;
+    let specification = context.rollup_specification().clone();
+    let now = context.clock().now();
+    let id = blocking(move || crate::list_storage::insert_list(
+        &pool,
+        user_id,
+        &name,
+        &list.folder.unwrap_or(DEFAULT_FOLDER.to_string()),
+        &list.list_access.unwrap_or(ListAccess::Public),
+        &list.list_type.unwrap_or(ListType::Standard),
+        &list.attributes.unwrap_or_default(),
+        &specification,
+        now,
+    )).await?;
+    retrieve_list(context, id).await
+}
+
+/// Replaces every field of the list, resetting fields that are not given to their defaults.
+/// Fails with `PreconditionFailed` if given a `version` the list is no longer at.
+pub async fn replace_list(context: &impl LMContext, id: u64, version: Option<u64>, list: ItemListUpdate) -> Result<ItemList, ListManagementError> {
+    validate(&list)?;
+    if list.name.is_none() {
+        return Err(ListManagementError::Validation("name is required".to_string()));
+    }
+    let list = ItemListUpdate {
+        attributes: Some(list.attributes.unwrap_or_default()),
+        folder: Some(list.folder.unwrap_or(DEFAULT_FOLDER.to_string())),
+        list_access: Some(list.list_access.unwrap_or(ListAccess::Public)),
+        list_type: Some(list.list_type.unwrap_or(ListType::Standard)),
+        name: list.name,
+    };
+    update_list(context, id, version, list).await
+}
+
+/// Changes only the fields of the list that are given, failing with `PreconditionFailed` if given
+/// a `version` the list is no longer at.
+pub async fn update_list(context: &impl LMContext, id: u64, version: Option<u64>, changes: ItemListUpdate) -> Result<ItemList, ListManagementError> {
+    validate(&changes)?;
+    let pool = context.pool().clone();
+    let user_state = context.current_user_state();
+    let specification = context.rollup_specification().clone();
+    let now = context.clock().now();
+    blocking(move || crate::list_storage::update_list(&pool, &user_state, id, version, &changes, &specification, now)).await?;
+    retrieve_list(context, id).await
+}
+
+/// Marks the list as deleted.  Deleted lists are only returned when explicitly selected.  Fails
+/// with `PreconditionFailed` if given a `version` the list is no longer at.
+pub fn delete_list(context: &impl LMContext, id: u64, version: Option<u64>) -> Result<(), ListManagementError> {
+    crate::list_storage::delete_list(context.pool(), &context.current_user_state(), id, version, context.clock().now())
+}
+
+fn validate(list: &ItemListUpdate) -> Result<(), ListManagementError> {
+    if list.name.as_ref().is_some_and(|name| name.trim().is_empty()) {
+        return Err(ListManagementError::Validation("name must not be blank".to_string()));
+    }
+    if list.folder.as_ref().is_some_and(|folder| folder.trim().is_empty()) {
+        return Err(ListManagementError::Validation("folder must not be blank".to_string()));
+    }
+    validate_attributes(list.attributes.as_ref())
+}
+
+/// Checks that every attribute can be stored exactly as given.
+pub(crate) fn validate_attributes(attributes: Option<&HashMap<String, ListAttribute>>) -> Result<(), ListManagementError> {
+    for (name, attr) in attributes.into_iter().flatten() {
+        if let ListAttribute::Float(f) = attr {
+            if !f.is_finite() {
+                return Err(ListManagementError::Validation(format!("attribute '{}' must be a finite number", name)));
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn retrieve_list(context: &impl LMContext, id: u64) -> Result<ItemList, ListManagementError> {
+    find_list(context, id, true).await
+}
+
+/// As `retrieve_list`, but the list's attributes, items and rollups are only loaded when
+/// `load_contents` is set.  The items are read separately unless the list provider gave them, as
+/// `ListOfListsService` never does.
+pub(crate) async fn find_list(context: &impl LMContext, id: u64, load_contents: bool) -> Result<ItemList, ListManagementError> {
     let selector = ListSelector {
-        limit_show_read_only: false,
+        limit_show_read_only: true,
         limit_list_types: vec![],
         limit_list_access: vec![],
         limit_show_deleted: false,
@@ -11,39 +103,335 @@ pub fn retrieve_list(context: &impl LMContext, id: u64) -> Result<ItemList, List
         limit_in_folders: vec![],
         limit_name_keywords: None,
         limit_list_ids: vec![id],
+        limit_search: None,
+        limit_attributes: vec![],
     };
     let paging = PagingRequest {
         start: 0,
         rows: 1,
+        cursor: None,
     };
     let sort = SortRequest {
         descending: false,
         key: SortKey::Id,
     };
-    let mut lists = context.list_provider().retrieve_lists(context, selector, paging, sort, true, true);
+    let mut lists = context
+        .list_provider()
+        .retrieve_lists(context, selector, paging, sort, load_contents, load_contents.then(RollupRequest::default))
+        .await?
+        .lists;
     if lists.is_empty() {
         return Err(ListManagementError::NotFound(id.to_string()));
     }
-    Ok(lists.remove(0))
+    let mut list = lists.remove(0);
+    if load_contents && list.items.is_none() {
+        let pool = context.pool().clone();
+        list.items = Some(blocking(move || crate::list_storage::all_list_items(&pool, id)).await?);
+    }
+    Ok(list)
+}
+
+/// Recomputes the stored rollups of every list, as the admin command `rebuild-rollups` does after
+/// the rollup definitions change.  Returns the number of lists.
+pub fn rebuild_rollups(pool: &DbPool, specification: &RollupSpecification) -> Result<u64, ListManagementError> {
+    crate::list_storage::rebuild_rollups(pool, specification)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::common::{ListAccess, ListType, LMContext, PagingRequest, SortRequest};
-    use crate::common::tests::{context, context_with_lists};
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+    use serial_test::serial;
+
+    use crate::common::{Account, AccountType, ListAccess, ListAttribute, ListItemUpdate, ListType, Price, UserState};
+    use crate::common::tests::{context_with_lists, db_context};
     use crate::common::tests::state;
     use crate::common::tests::user;
+    use crate::test_helpers::{insert_account, insert_account_type, insert_item_list_account, insert_user, setup_db};
 
     use super::*;
 
-    #[test]
-    pub fn test_retrieve_list() {
+    #[actix_web::test]
+    pub async fn test_retrieve_list() {
         let lists = vec![il(1, "one".to_string()), il(2, "two".to_string())];
         let context = &context_with_lists(user(), state(), lists);
-        assert_eq!("one", retrieve_list(context, 1).unwrap().name);
+        assert_eq!("one", retrieve_list(context, 1).await.unwrap().name);
 
         let context = &context_with_lists(user(), state(), vec![]);
-        assert!(retrieve_list(context, 3).is_err());
+        assert!(retrieve_list(context, 3).await.is_err());
+    }
+
+    #[actix_web::test]
+    #[serial]
+    pub async fn test_create_list() {
+        let context = &db_context(user(), db_state());
+        let created = create_list(context, ItemListUpdate {
+            attributes: Some(HashMap::from([("size".to_string(), ListAttribute::Integer(3))])),
+            folder: None,
+            list_access: Some(ListAccess::Private),
+            list_type: None,
+            name: Some("My List".to_string()),
+        }).await.unwrap();
+        assert!(created.id.is_some());
+        assert_eq!("My List", created.name);
+        assert_eq!("default", created.folder);
+        assert_eq!(ListAccess::Private, created.list_access);
+        assert_eq!(ListType::Standard, created.list_type);
+        assert_eq!(ListAttribute::Integer(3), created.attributes["size"]);
+        assert!(!created.deleted);
+        assert!(created.items.unwrap().is_empty());
+
+        assert_eq!("My List", retrieve_list(context, created.id.unwrap()).await.unwrap().name);
+
+        let no_name = create_list(context, ItemListUpdate::default()).await;
+        assert!(matches!(no_name, Err(ListManagementError::Validation(_))));
+        let blank_name = create_list(context, ItemListUpdate {
+            name: Some(" ".to_string()),
+            ..Default::default()
+        }).await;
+        assert!(matches!(blank_name, Err(ListManagementError::Validation(_))));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    pub async fn test_prices_round_trip() {
+        let context = &db_context(user(), db_state());
+        let prices = [
+            Price { amount: Decimal::from_str("9223372036854.775808").unwrap(), source: "Corner Shop".to_string() },
+            Price { amount: Decimal::from_str("1.10").unwrap(), source: "".to_string() },
+            Price { amount: Decimal::from_str("-0.0000001").unwrap(), source: "PRICE: _1 _x".to_string() },
+            Price { amount: Decimal::MAX, source: "".to_string() },
+            Price { amount: Decimal::from_str("0.00").unwrap(), source: "".to_string() },
+        ];
+        let attributes: HashMap<String, ListAttribute> = prices
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (format!("price {}", i), ListAttribute::Price(p.clone())))
+            .collect();
+        let id = create_list(context, ItemListUpdate {
+            attributes: Some(attributes.clone()),
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+        crate::list_item_service::add_item(context, id, ListItemUpdate {
+            attributes: Some(attributes.clone()),
+            name: Some("Apples".to_string()),
+            source: Some("grocer".to_string()),
+            ..Default::default()
+        }).unwrap();
+
+        let list = retrieve_list(context, id).await.unwrap();
+        let items = list.items.unwrap();
+        // Amounts keep the decimal places they were written with, which equality ignores.
+        for (i, price) in prices.iter().enumerate() {
+            let name = format!("price {}", i);
+            for read in [&list.attributes[&name], &items[0].attributes[&name]] {
+                let ListAttribute::Price(p) = read else { panic!("{}", name) };
+                assert_eq!(price.amount.to_string(), p.amount.to_string());
+                assert_eq!(price.source, p.source);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    #[serial]
+    pub async fn test_numbers_round_trip() {
+        let context = &db_context(user(), db_state());
+        let attributes = HashMap::from([
+            ("big".to_string(), ListAttribute::Integer(i64::MAX)),
+            ("small".to_string(), ListAttribute::Integer(i64::MIN)),
+            ("precise".to_string(), ListAttribute::Float(0.1 + 0.2)),
+            ("huge".to_string(), ListAttribute::Float(f64::MAX)),
+        ]);
+        let id = create_list(context, ItemListUpdate {
+            attributes: Some(attributes.clone()),
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+        crate::list_item_service::add_item(context, id, ListItemUpdate {
+            attributes: Some(attributes.clone()),
+            name: Some("Apples".to_string()),
+            source: Some("grocer".to_string()),
+            ..Default::default()
+        }).unwrap();
+
+        let list = retrieve_list(context, id).await.unwrap();
+        assert_eq!(attributes, list.attributes);
+        assert_eq!(attributes, list.items.unwrap()[0].attributes);
+
+        for f in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let not_finite = Some(HashMap::from([("f".to_string(), ListAttribute::Float(f))]));
+            let created = create_list(context, ItemListUpdate {
+                attributes: not_finite.clone(),
+                name: Some("My List".to_string()),
+                ..Default::default()
+            }).await;
+            assert!(matches!(created, Err(ListManagementError::Validation(_))), "{}", f);
+            let added = crate::list_item_service::add_item(context, id, ListItemUpdate {
+                attributes: not_finite,
+                name: Some("Apples".to_string()),
+                source: Some("grocer".to_string()),
+                ..Default::default()
+            });
+            assert!(matches!(added, Err(ListManagementError::Validation(_))), "{}", f);
+        }
+    }
+
+    #[actix_web::test]
+    #[serial]
+    pub async fn test_replace_and_update_list() {
+        let context = &db_context(user(), db_state());
+        let created = create_list(context, ItemListUpdate {
+            attributes: Some(HashMap::from([("size".to_string(), ListAttribute::Integer(3))])),
+            folder: Some("work".to_string()),
+            list_access: Some(ListAccess::Private),
+            list_type: Some(ListType::System),
+            name: Some("My List".to_string()),
+        }).await.unwrap();
+        let id = created.id.unwrap();
+
+        let updated = update_list(context, id, Some(created.version), ItemListUpdate {
+            folder: Some("archive".to_string()),
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!("My List", updated.name);
+        assert_eq!("archive", updated.folder);
+        assert_eq!(ListAccess::Private, updated.list_access);
+        assert_eq!(ListType::System, updated.list_type);
+        assert_eq!(1, updated.attributes.len());
+        assert!(updated.modified >= created.modified);
+        assert_eq!(created.version + 1, updated.version);
+
+        let stale = update_list(context, id, Some(created.version), ItemListUpdate::default()).await;
+        assert!(matches!(stale, Err(ListManagementError::PreconditionFailed(_))));
+        let replaced = replace_list(context, id, Some(updated.version), ItemListUpdate {
+            attributes: Some(HashMap::from([("color".to_string(), ListAttribute::Text("red".to_string()))])),
+            name: Some("Renamed".to_string()),
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!("Renamed", replaced.name);
+        assert_eq!("default", replaced.folder);
+        assert_eq!(ListAccess::Public, replaced.list_access);
+        assert_eq!(ListType::Standard, replaced.list_type);
+        assert_eq!(1, replaced.attributes.len());
+        assert_eq!(ListAttribute::Text("red".to_string()), replaced.attributes["color"]);
+        assert_eq!(updated.version + 1, replaced.version);
+
+        assert!(matches!(replace_list(context, id, None, ItemListUpdate::default()).await, Err(ListManagementError::Validation(_))));
+        assert!(matches!(update_list(context, id + 1, None, ItemListUpdate::default()).await, Err(ListManagementError::NotFound(_))));
+
+        let someone_else = &db_context(user(), UserState {
+            active_user_accounts: vec![],
+            user_id: context.current_user_state().user_id + 1,
+        });
+        assert!(matches!(update_list(someone_else, id, None, ItemListUpdate::default()).await, Err(ListManagementError::NotFound(_))));
+    }
+
+    #[actix_web::test]
+    #[serial]
+    pub async fn test_delete_list() {
+        let context = &db_context(user(), db_state());
+        let created = create_list(context, ItemListUpdate {
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap();
+        let id = created.id.unwrap();
+
+        let stale = delete_list(context, id, Some(created.version + 1));
+        assert!(matches!(stale, Err(ListManagementError::PreconditionFailed(_))));
+        delete_list(context, id, Some(created.version)).unwrap();
+        assert!(matches!(retrieve_list(context, id).await, Err(ListManagementError::NotFound(_))));
+        assert!(matches!(delete_list(context, id, None), Err(ListManagementError::NotFound(_))));
+        let deleted = crate::list_storage::all_lists();
+        assert_eq!(1, deleted.len());
+        assert!(deleted[0].deleted);
+        assert!(deleted[0].modified >= created.modified);
+        assert_eq!(created.version + 1, deleted[0].version);
+    }
+
+    #[actix_web::test]
+    #[serial]
+    pub async fn test_write_read_only_list() {
+        let owner = &db_context(user(), db_state());
+        let id = create_list(owner, ItemListUpdate {
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+        let account_type_id = insert_account_type("at1".to_string(), "ats1".to_string());
+        let account_id = insert_account(account_type_id, "as1".to_string());
+        insert_item_list_account(&mut crate::db::connection().unwrap(), id as i32, account_id);
+        let other_user_id = insert_user("other", "source", "source-2");
+        let other_user = &db_context(user(), UserState {
+            active_user_accounts: vec![Account {
+                id: Some(account_id as u64),
+                account_type: AccountType { id: Some(account_type_id as u64), name: "at1".to_string(), source: "ats1".to_string() },
+                account_source_id: "as1".to_string(),
+            }],
+            user_id: other_user_id as u64,
+        });
+
+        assert!(retrieve_list(other_user, id).await.unwrap().read_only);
+        let rename = ItemListUpdate {
+            name: Some("Their List".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(update_list(other_user, id, None, rename.clone()).await, Err(ListManagementError::Forbidden(_))));
+        assert!(matches!(delete_list(other_user, id, None), Err(ListManagementError::Forbidden(_))));
+
+        update_list(owner, id, None, ItemListUpdate {
+            list_access: Some(ListAccess::Shared),
+            ..Default::default()
+        }).await.unwrap();
+        assert_eq!("Their List", update_list(other_user, id, None, rename).await.unwrap().name);
+        // Only the owner may change who can see the list, or delete it.
+        let make_private = ItemListUpdate {
+            list_access: Some(ListAccess::Private),
+            ..Default::default()
+        };
+        assert!(matches!(update_list(other_user, id, None, make_private.clone()).await, Err(ListManagementError::Forbidden(_))));
+        assert!(matches!(delete_list(other_user, id, None), Err(ListManagementError::Forbidden(_))));
+        let list = retrieve_list(owner, id).await.unwrap();
+        assert_eq!(ListAccess::Shared, list.list_access);
+        assert_eq!("Their List", list.name);
+        update_list(owner, id, None, make_private).await.unwrap();
+        delete_list(owner, id, None).unwrap();
+    }
+
+    #[actix_web::test]
+    #[serial]
+    pub async fn test_rebuild_rollups() {
+        let context = &db_context(user(), db_state());
+        let id = create_list(context, ItemListUpdate {
+            name: Some("My List".to_string()),
+            ..Default::default()
+        }).await.unwrap().id.unwrap();
+        crate::list_item_service::add_item(context, id, ListItemUpdate {
+            name: Some("Apples".to_string()),
+            source: Some("grocer".to_string()),
+            ..Default::default()
+        }).unwrap();
+        assert!(retrieve_list(context, id).await.unwrap().rollups.unwrap().is_empty());
+
+        let specification = RollupSpecification::parse(
+            r#"{"by_list_type": {"Standard": [{"name": "items", "function": "Count", "of": "Name"}]}}"#,
+        ).unwrap();
+        assert_eq!(1, rebuild_rollups(crate::db::default_pool(), &specification).unwrap());
+        // The stored rollups are returned as they were rebuilt, whatever the context's definitions.
+        let rollups = retrieve_list(context, id).await.unwrap().rollups.unwrap();
+        assert_eq!(1, rollups.len());
+        assert_eq!("items", rollups[0].name);
+        assert_eq!(ListAttribute::Integer(1), rollups[0].value);
+    }
+
+    fn db_state() -> UserState {
+        setup_db();
+        let user_id = insert_user("name", "source", "source-1");
+        UserState {
+            active_user_accounts: vec![],
+            user_id: user_id as u64,
+        }
     }
 
     fn il(id: u64, name: String) -> ItemList {
@@ -53,14 +441,16 @@ mod tests {
             created: Default::default(),
             deleted: false,
             folder: "".to_string(),
-            items: None,
+            items: Some(vec![]),
             list_access: ListAccess::Private,
             list_accounts: vec![],
             list_type: ListType::Standard,
             modified: Default::default(),
-            name: name,
+            name,
             read_only: false,
             rollups: None,
+            converted_rollups: None,
+            version: 1,
         }
     }
 }
\ No newline at end of file
@@ -1,45 +1,707 @@
-use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::str::FromStr;
 
 use chrono::{NaiveDateTime, Utc};
+use diesel::dsl::{not, sql, InnerJoin, InnerJoinQuerySource, IntoBoxed};
+use diesel::expression::{AppearsOnTable, SelectableExpression};
+use diesel::query_builder::{AstPass, QueryFragment};
 use diesel::prelude::*;
-use regex::Regex;
+use diesel::result::Error::DeserializationError;
+use diesel::sql_types::{Bool, Double, Integer, Nullable, Text, Timestamp};
 use rust_decimal::Decimal;
+use rust_decimal::prelude::FromPrimitive;
 
 use crate::common::{
-    Account, AccountType, ItemList, ListAccess, ListAttribute, ListItem, ListType,
-    Price, UserState,
+    Account, AccountType, AttributeCondition, AttributeFilter, AttributePredicate, Comparison, ItemList,
+    ItemListRollup, ItemListUpdate, ItemSortKey, ItemSortRequest, ListAccess, ListAttribute,
+    ListItem, ListItemUpdate, ListManagementError, ListType, PagingRequest, Price, SortKey,
+    SortRequest, UserState,
 };
+use crate::cursor::{FoundItemCursor, ItemCursor, ListCursor};
 use crate::db;
+use crate::db::{DbAmount, DbPool, MultiBackend, MultiConnection};
+use crate::list_item_service::{FoundItem, ItemSelector};
+use crate::list_of_lists_service::{apply_item_change, compute_rollups, ListSelector, RollupTotal, StoredRollup};
 use crate::models::{
-    AccountDb, AccountTypeDb, ItemListAccountDb, ItemListAttributeDb, ItemListDb,
-    ListItemAttributeDb, ListItemDb,
+    AccountDb, AccountTypeDb, ItemListAccountDb, ItemListAttributeDb, ItemListAttributeDbInsert,
+    ItemListDb, ItemListDbInsert, ItemListDbUpdate, ItemListRollupDb, ItemListRollupDbInsert,
+    ListItemAttributeDb, ListItemAttributeDbInsert, ListItemDb, ListItemDbInsert, ListItemDbUpdate,
 };
+use crate::rollup::{RollupFunction, RollupSpecification};
+use crate::search::SearchQuery;
 use crate::schema::{
-    account, account_type, item_list, item_list_attribute, list_item, list_item_attribute,
+    account, account_type, item_list, item_list_account, item_list_attribute, item_list_rollup,
+    list_item, list_item_attribute,
 };
 use crate::schema::item_list::owner_user_id;
 
-pub(crate) fn user_lists(user_state: UserState) -> Vec<ItemList> {
-    let mut lists: Vec<ItemListDb> = Vec::new();
-    {
-        let mut c = db::connection();
-        let mut l: Vec<ItemListDb> = item_list::table
-            .filter(owner_user_id.eq(user_state.user_id as i32))
+define_sql_function! {
+    fn lower(x: Text) -> Text;
+}
+
+define_sql_function! {
+    /// SQLite's day number for the timestamp, which compares the same however the timestamp's
+    /// text writes the fraction of a second.
+    fn julianday(x: Timestamp) -> Double;
+}
+
+define_sql_function! {
+    #[sql_name = "julianday"]
+    fn julianday_nullable(x: Nullable<Timestamp>) -> Nullable<Double>;
+}
+
+/// `expr COLLATE amount`, for SQLite to compare amounts that are not read straight from their
+/// column, such as the result of a subquery, which does not carry the column's collation.
+#[derive(Clone, Debug, diesel::query_builder::QueryId, diesel::expression::ValidGrouping)]
+struct AmountCollated<E>(E);
+
+impl<E: Expression> Expression for AmountCollated<E> {
+    type SqlType = E::SqlType;
+}
+
+impl<E: AppearsOnTable<QS>, QS> AppearsOnTable<QS> for AmountCollated<E> {}
+
+impl<E: SelectableExpression<QS>, QS> SelectableExpression<QS> for AmountCollated<E> {}
+
+impl<E: QueryFragment<MultiBackend>> QueryFragment<MultiBackend> for AmountCollated<E> {
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, MultiBackend>) -> QueryResult<()> {
+        self.0.walk_ast(out.reborrow())?;
+        out.push_sql(" COLLATE ");
+        out.push_sql(db::AMOUNT_COLLATION);
+        Ok(())
+    }
+}
+
+macro_rules! then_order_by {
+    ($query:expr, $expr:expr, $descending:expr) => {
+        if $descending {
+            $query.then_order_by($expr.desc())
+        } else {
+            $query.then_order_by($expr.asc())
+        }
+    };
+}
+
+/// Keeps the rows whose (value, id) comes after the cursor's in sort order.
+macro_rules! after {
+    ($query:expr, $id:expr, $expr:expr, $value:expr, $cursor_id:expr, $descending:expr) => {
+        if $descending {
+            $query.filter($expr.lt($value.clone()).or($expr.eq($value).and($id.lt($cursor_id))))
+        } else {
+            $query.filter($expr.gt($value.clone()).or($expr.eq($value).and($id.gt($cursor_id))))
+        }
+    };
+}
+
+/// Sorts by a column that always has a value, then by the id column `$id`.  The cursor's value
+/// must be of the column's type.
+macro_rules! by_column {
+    ($query:expr, $id:expr, $column:expr, $after:expr, $descending:expr, $pattern:pat => $value:expr) => {{
+        let query = match $after {
+            Some(cursor) => match &cursor.value {
+                Some($pattern) => after!($query, $id, $column, $value, cursor.id as i32, $descending),
+                _ => return Err(invalid_cursor()),
+            },
+            None => $query,
+        };
+        then_order_by!(query, $column, $descending)
+    }};
+}
+
+/// Sorts by a timestamp column as `by_column!` does.  SQLite keeps timestamps as text, where
+/// "00:00:00" sorts before "00:00:00.000", so there the column is sorted by its day number.
+macro_rules! by_timestamp {
+    ($c:expr, $query:expr, $id:expr, $column:expr, $after:expr, $descending:expr) => {
+        match $c {
+            #[cfg(feature = "postgres")]
+            MultiConnection::Postgres(_) => {
+                by_column!($query, $id, $column, $after, $descending, ListAttribute::DateTime(d) => *d)
+            }
+            MultiConnection::Sqlite(_) => {
+                by_column!($query, $id, julianday($column), $after, $descending, ListAttribute::DateTime(d) => julianday(*d))
+            }
+        }
+    };
+}
+
+/// Sorts by an attribute value that may be missing, putting the lists without one last.  A
+/// cursor value of some other type means the cursor's list was among those without one.
+macro_rules! by_attribute {
+    ($query:expr, $expr:expr, $after:expr, $descending:expr, $pattern:pat => $value:expr) => {{
+        let v = $expr;
+        let query = match $after {
+            Some(cursor) => match &cursor.value {
+                Some($pattern) if $descending => $query.filter(v.clone().is_null().or(
+                    v.clone().lt($value).or(v.clone().eq($value).and(item_list::id.lt(cursor.id as i32))),
+                )),
+                Some($pattern) => $query.filter(v.clone().is_null().or(
+                    v.clone().gt($value).or(v.clone().eq($value).and(item_list::id.gt(cursor.id as i32))),
+                )),
+                _ if $descending => $query.filter(v.clone().is_null().and(item_list::id.lt(cursor.id as i32))),
+                _ => $query.filter(v.clone().is_null().and(item_list::id.gt(cursor.id as i32))),
+            },
+            None => $query,
+        };
+        then_order_by!(query.then_order_by(v.clone().is_null()), v, $descending)
+    }};
+}
+
+/// Whether `$id` is among the owners of the attribute rows in `$query` whose value compares with
+/// the given one as asked.
+macro_rules! compare {
+    ($id:expr, $query:expr, $owner:expr, $expr:expr, $comparison:expr, $value:expr) => {
+        match $comparison {
+            Comparison::Eq => Box::new($id.eq_any($query.filter($expr.eq($value)).select($owner))),
+            Comparison::Ne => Box::new($id.eq_any($query.filter($expr.ne($value)).select($owner))),
+            Comparison::Lt => Box::new($id.eq_any($query.filter($expr.lt($value)).select($owner))),
+            Comparison::Le => Box::new($id.eq_any($query.filter($expr.le($value)).select($owner))),
+            Comparison::Gt => Box::new($id.eq_any($query.filter($expr.gt($value)).select($owner))),
+            Comparison::Ge => Box::new($id.eq_any($query.filter($expr.ge($value)).select($owner))),
+        }
+    };
+}
+
+/// As `compare!`, for the amount column `$column` and a Float, which may lie beyond every amount.
+macro_rules! compare_amount {
+    ($id:expr, $query:expr, $owner:expr, $column:expr, $comparison:expr, $value:expr) => {
+        match amount_comparison(*$comparison, $value) {
+            AmountComparison::Always(true) => Box::new($id.eq_any($query.select($owner))),
+            AmountComparison::Always(false) => Box::new(false.into_sql::<Bool>()),
+            AmountComparison::Compare(comparison, value) => compare!($id, $query, $owner, $column, comparison, DbAmount(value)),
+        }
+    };
+}
+
+/// Whether the predicate holds for the item or list with id `$id`, whose attributes are the rows
+/// of `$attributes` (`list_item_attribute` or `item_list_attribute`) with `$owner` set to its id.
+macro_rules! attribute_matches {
+    ($c:expr, $id:expr, $attributes:ident, $owner:expr, $predicate:expr) => {{
+        let predicate: &AttributePredicate = $predicate;
+        let named = $attributes::table.filter($attributes::name.eq(predicate.name.clone()));
+        let matches: AttributeSql<_> = match &predicate.condition {
+            AttributeCondition::Exists => Box::new($id.eq_any(named.select($owner))),
+            AttributeCondition::Missing => Box::new(not($id.eq_any(named.select($owner)))),
+            AttributeCondition::Contains(text) => {
+                let pattern = format!("%{}%", escape_like(&text.to_lowercase()));
+                let text_val = sql::<Nullable<Text>>("lower(text_val)");
+                Box::new($id.eq_any(
+                    named
+                        .filter($attributes::attribute_type.eq(ListAttribute::Text(String::new()).to_string()))
+                        .filter(text_val.like(pattern).escape('\\'))
+                        .select($owner),
+                ))
+            }
+            AttributeCondition::Compare(comparison, value) => {
+                let typed = named.clone().filter($attributes::attribute_type.eq(value.to_string()));
+                match value {
+                    ListAttribute::Boolean(b) => compare!($id, typed, $owner, $attributes::bool_val, comparison, *b),
+                    ListAttribute::DateTime(d) => match $c {
+                        #[cfg(feature = "postgres")]
+                        MultiConnection::Postgres(_) => compare!($id, typed, $owner, $attributes::timestamp_val, comparison, *d),
+                        MultiConnection::Sqlite(_) => compare!(
+                            $id,
+                            typed,
+                            $owner,
+                            julianday_nullable($attributes::timestamp_val),
+                            comparison,
+                            julianday_nullable(*d)
+                        ),
+                    },
+                    ListAttribute::Float(f) => {
+                        let numbers: AttributeSql<_> =
+                            compare!($id, named.clone(), $owner, sql::<Nullable<Double>>(NUMERIC_VALUE), comparison, *f);
+                        let prices: AttributeSql<_> =
+                            compare_amount!($id, named.filter($attributes::attribute_type.eq("Price")), $owner, $attributes::price_amount, comparison, *f);
+                        Box::new(numbers.or(prices))
+                    }
+                    ListAttribute::Integer(i) => {
+                        let numbers: AttributeSql<_> =
+                            compare!($id, named.clone(), $owner, sql::<Nullable<Double>>(NUMERIC_VALUE), comparison, *i as f64);
+                        let prices: AttributeSql<_> =
+                            compare!($id, named.filter($attributes::attribute_type.eq("Price")), $owner, $attributes::price_amount, comparison, DbAmount(Decimal::from(*i)));
+                        Box::new(numbers.or(prices))
+                    }
+                    ListAttribute::Price(p) => compare!(
+                        $id,
+                        typed.filter($attributes::price_source.eq(p.source.clone())),
+                        $owner,
+                        $attributes::price_amount,
+                        comparison,
+                        DbAmount(p.amount)
+                    ),
+                    ListAttribute::Text(t) => compare!($id, typed, $owner, $attributes::text_val, comparison, t.clone()),
+                }
+            }
+        };
+        matches
+    }};
+}
+
+type AttributeSql<QS> = Box<dyn BoxableExpression<QS, MultiBackend, SqlType = Bool>>;
+
+/// Combines the filter's predicates, each made into SQL by `predicate`.
+fn attribute_filter<QS: 'static>(
+    filter: &AttributeFilter,
+    predicate: &dyn Fn(&AttributePredicate) -> AttributeSql<QS>,
+) -> AttributeSql<QS> {
+    match filter {
+        AttributeFilter::Predicate(p) => predicate(p),
+        AttributeFilter::And(filters) => filters
+            .iter()
+            .map(|f| attribute_filter(f, predicate))
+            .reduce(|a, b| Box::new(a.and(b)))
+            .unwrap_or_else(|| Box::new(true.into_sql::<Bool>())),
+        AttributeFilter::Or(filters) => filters
+            .iter()
+            .map(|f| attribute_filter(f, predicate))
+            .reduce(|a, b| Box::new(a.or(b)))
+            .unwrap_or_else(|| Box::new(false.into_sql::<Bool>())),
+    }
+}
+
+/// The number held by an Integer or Float attribute, read from either attribute table.  Prices are
+/// compared by `compare_amount!` instead.
+const NUMERIC_VALUE: &str = "CASE \"type\" \
+    WHEN 'Integer' THEN CAST(integer_val AS DOUBLE PRECISION) \
+    WHEN 'Float' THEN float_val END";
+
+/// How a stored amount compares with a Float: the same way for every amount, or as it compares
+/// with the Float's amount.
+enum AmountComparison {
+    Always(bool),
+    Compare(Comparison, Decimal),
+}
+
+/// A Float beyond the range of amounts compares the same way with every amount.
+fn amount_comparison(comparison: Comparison, value: f64) -> AmountComparison {
+    match Decimal::from_f64(value) {
+        Some(amount) => AmountComparison::Compare(comparison, amount),
+        // Only Ne, Lt and Le hold for every amount.
+        None if value > 0.0 => AmountComparison::Always(matches!(comparison, Comparison::Ne | Comparison::Lt | Comparison::Le)),
+        None => AmountComparison::Always(matches!(comparison, Comparison::Ne | Comparison::Gt | Comparison::Ge)),
+    }
+}
+
+/// Returns one page of the user's lists matching the selector, in sort order, starting after the
+/// cursor if given.  List attributes are only loaded when asked for, and stored rollups only when
+/// given the specification to compute them by should they be stale.  Items are never loaded.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn user_lists(
+    pool: &DbPool,
+    user_state: &UserState,
+    selector: &ListSelector,
+    paging: &PagingRequest,
+    sort: &SortRequest,
+    after: Option<&ListCursor>,
+    load_attributes: bool,
+    rollups: Option<&RollupSpecification>,
+) -> Result<Vec<ItemList>, ListManagementError> {
+    if paging.rows == 0 {
+        return Ok(vec![]);
+    }
+    let lists: Vec<ItemListDb> = {
+        let mut c = db::check_out(pool)?;
+        let query = selected_lists(&c, user_state, selector);
+        let mut query = sorted_lists(&mut c, query, user_state, sort, selector.limit_search.as_ref(), after)?;
+        // Relevance changes as lists change, so its cursors hold the position to resume from.
+        let offset = match after {
+            None => Some(paging.start),
+            Some(cursor) if sort.key == SortKey::Relevance => match cursor.value {
+                Some(ListAttribute::Integer(position)) if position >= 0 => Some(position as u64),
+                _ => return Err(invalid_cursor()),
+            },
+            Some(_) => None,
+        };
+        if let Some(offset) = offset {
+            query = query.offset(offset as i64);
+        }
+        query
             .select(ItemListDb::as_select())
-            .order(item_list::id.asc())
-            .load(&mut c)
-            .unwrap();
-        lists.append(&mut l);
+            .limit(paging.rows as i64)
+            .load(&mut c)?
+    };
+    get_lists(pool, lists, Some(user_state.user_id), load_attributes, false, rollups)
+}
+
+/// Counts all of the user's lists matching the selector.
+pub(crate) fn count_user_lists(pool: &DbPool, user_state: &UserState, selector: &ListSelector) -> Result<u64, ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    let count: i64 = selected_lists(&c, user_state, selector)
+        .count()
+        .get_result(&mut c)?;
+    Ok(count as u64)
+}
+
+/// The lists the user owns, plus the Public and Shared lists of others that are linked to any of
+/// the user's active accounts.
+macro_rules! visible_to {
+    ($user_state:expr) => {{
+        let account_ids: Vec<i32> = $user_state
+            .active_user_accounts
+            .iter()
+            .filter_map(|a| a.id)
+            .map(|id| id as i32)
+            .collect();
+        let shared_with_user = item_list_account::table
+            .filter(item_list_account::account_id.eq_any(account_ids))
+            .select(item_list_account::item_list_id);
+        let access = vec![ListAccess::Public.to_string(), ListAccess::Shared.to_string()];
+        owner_user_id
+            .eq($user_state.user_id as i32)
+            .or(item_list::access.eq_any(access).and(item_list::id.eq_any(shared_with_user)))
+    }};
+}
+
+/// Lists are read-only to all but their owner, unless they are Shared.
+fn is_read_only(list: &ItemListDb, user_id: u64) -> bool {
+    is_read_only_to(list.owner_user_id, &list.access, user_id)
+}
+
+fn is_read_only_to(owner: i32, access: &str, user_id: u64) -> bool {
+    owner as u64 != user_id && access != ListAccess::Shared.to_string()
+}
+
+/// Keeps the lists the user can see that match the selector.  `$query` is a boxed query whose
+/// FROM clause includes `item_list`, so the lists can be selected on their own or joined to
+/// their items.
+macro_rules! filter_lists {
+    ($query:expr, $qs:ty, $c:expr, $user_state:expr, $selector:expr) => {{
+        let (user_state, selector): (&UserState, &ListSelector) = ($user_state, $selector);
+        let mut query = $query.filter(visible_to!(user_state));
+        if !selector.limit_show_read_only {
+            query = query.filter(
+                owner_user_id
+                    .eq(user_state.user_id as i32)
+                    .or(item_list::access.eq(ListAccess::Shared.to_string())),
+            );
+        }
+        if !selector.limit_show_deleted {
+            query = query.filter(item_list::deleted.eq(false));
+        }
+        if !selector.limit_show_not_deleted {
+            query = query.filter(item_list::deleted.eq(true));
+        }
+        if !selector.limit_list_access.is_empty() {
+            let access: Vec<String> = selector.limit_list_access.iter().map(|a| a.to_string()).collect();
+            query = query.filter(item_list::access.eq_any(access));
+        }
+        if !selector.limit_list_types.is_empty() {
+            let list_types: Vec<String> = selector.limit_list_types.iter().map(|t| t.to_string()).collect();
+            query = query.filter(item_list::list_type.eq_any(list_types));
+        }
+        if !selector.limit_in_folders.is_empty() {
+            query = query.filter(item_list::folder.eq_any(selector.limit_in_folders.clone()));
+        }
+        if !selector.limit_list_ids.is_empty() {
+            let ids: Vec<i32> = selector.limit_list_ids.iter().map(|id| *id as i32).collect();
+            query = query.filter(item_list::id.eq_any(ids));
+        }
+        if let Some(name_keywords) = &selector.limit_name_keywords {
+            for pattern in keyword_patterns(name_keywords) {
+                let padded_name = " ".into_sql::<Text>().concat(lower(item_list::name)).concat(" ");
+                query = query.filter(padded_name.like(pattern).escape('\\'));
+            }
+        }
+        if let Some(search) = &selector.limit_search {
+            query = query.filter(search_matches($c, search));
+        }
+        for filter in &selector.limit_attributes {
+            query = query.filter(attribute_filter::<$qs>(filter, &|predicate| attribute_matches!(
+                $c,
+                item_list::id,
+                item_list_attribute,
+                item_list_attribute::item_list_id,
+                predicate
+            )));
+        }
+        query
+    }};
+}
+
+fn selected_lists(
+    c: &MultiConnection,
+    user_state: &UserState,
+    selector: &ListSelector,
+) -> item_list::BoxedQuery<'static, MultiBackend> {
+    filter_lists!(item_list::table.into_boxed(), item_list::table, c, user_state, selector)
+}
+
+type SearchSql<QS, ST> = Box<dyn BoxableExpression<QS, MultiBackend, SqlType = ST>>;
+
+/// Whether the list matches the search, by way of the `list_search` index.
+fn search_matches<QS>(c: &MultiConnection, search: &SearchQuery) -> SearchSql<QS, Bool> {
+    match c {
+        #[cfg(feature = "postgres")]
+        MultiConnection::Postgres(_) => Box::new(
+            sql::<Bool>("item_list.id IN (SELECT item_list_id FROM list_search WHERE document @@ to_tsquery('simple', ")
+                .bind::<Text, _>(search.to_tsquery())
+                .sql("))"),
+        ),
+        MultiConnection::Sqlite(_) => Box::new(
+            sql::<Bool>("item_list.id IN (SELECT rowid FROM list_search WHERE list_search MATCH ")
+                .bind::<Text, _>(search.to_fts5())
+                .sql(")"),
+        ),
+    }
+}
+
+/// How well the list matches the search, lower being better.  A match in the list's name counts
+/// for more than one in its items' names, which counts for more than one in attribute text.
+fn search_rank(c: &MultiConnection, search: &SearchQuery) -> SearchSql<item_list::table, Nullable<Double>> {
+    match c {
+        #[cfg(feature = "postgres")]
+        MultiConnection::Postgres(_) => Box::new(
+            sql::<Nullable<Double>>("(SELECT -ts_rank(document, to_tsquery('simple', ")
+                .bind::<Text, _>(search.to_tsquery())
+                .sql("))::DOUBLE PRECISION FROM list_search WHERE item_list_id = item_list.id)"),
+        ),
+        MultiConnection::Sqlite(_) => Box::new(
+            sql::<Nullable<Double>>("(SELECT bm25(list_search, 10.0, 5.0, 1.0) FROM list_search WHERE list_search MATCH ")
+                .bind::<Text, _>(search.to_fts5())
+                .sql(" AND rowid = item_list.id)"),
+        ),
     }
-    get_lists(lists)
 }
 
+/// A LIKE pattern for each keyword, to be matched against a lowercased name padded with spaces.
+/// Each keyword must match a whole word of the name, or the start of a word if it ends in "*".
+/// Padding the name with spaces lets the first and last words match the same way.
+fn keyword_patterns(keywords: &str) -> Vec<String> {
+    keywords
+        .split_whitespace()
+        .map(|a| a.to_ascii_lowercase())
+        .map(|kw| {
+            if kw.len() > 1 && kw.ends_with('*') {
+                format!("% {}%", escape_like(&kw[..kw.len() - 1]))
+            } else {
+                format!("% {} %", escape_like(&kw))
+            }
+        })
+        .collect()
+}
+
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Orders the lists by the sort key, breaking ties by id, and keeps only those after the cursor
+/// if there is one.  When sorting by an attribute, lists without the attribute come last.  The
+/// attribute is sorted as the type most of the user's lists that have it give it, the first by
+/// name if there is a tie, and lists where it has a different type sort as if they do not have it.
+fn sorted_lists(
+    c: &mut MultiConnection,
+    query: item_list::BoxedQuery<'static, MultiBackend>,
+    user_state: &UserState,
+    sort: &SortRequest,
+    search: Option<&SearchQuery>,
+    after: Option<&ListCursor>,
+) -> Result<item_list::BoxedQuery<'static, MultiBackend>, ListManagementError> {
+    let descending = sort.descending;
+    let query = match &sort.key {
+        SortKey::Attribute(name) => {
+            let attribute_type: Option<String> = item_list_attribute::table
+                .inner_join(item_list::table)
+                .filter(visible_to!(user_state))
+                .filter(item_list_attribute::name.eq(name))
+                .group_by(item_list_attribute::attribute_type)
+                .select(item_list_attribute::attribute_type)
+                .order((diesel::dsl::count_star().desc(), item_list_attribute::attribute_type.asc()))
+                .first(c)
+                .optional()?;
+            let value = item_list_attribute::table
+                .filter(item_list_attribute::item_list_id.eq(item_list::id))
+                .filter(item_list_attribute::name.eq(name.clone()))
+                .filter(item_list_attribute::attribute_type.eq(attribute_type.clone().unwrap_or_default()))
+                .limit(1);
+            match attribute_type.and_then(|t| ListAttribute::from_str(&t).ok()) {
+                Some(ListAttribute::Boolean(_)) => by_attribute!(
+                    query,
+                    value.select(item_list_attribute::bool_val).single_value(),
+                    after,
+                    descending,
+                    ListAttribute::Boolean(b) => *b
+                ),
+                Some(ListAttribute::DateTime(_)) => match c {
+                    #[cfg(feature = "postgres")]
+                    MultiConnection::Postgres(_) => by_attribute!(
+                        query,
+                        value.select(item_list_attribute::timestamp_val).single_value(),
+                        after,
+                        descending,
+                        ListAttribute::DateTime(d) => *d
+                    ),
+                    MultiConnection::Sqlite(_) => by_attribute!(
+                        query,
+                        julianday_nullable(value.select(item_list_attribute::timestamp_val).single_value()),
+                        after,
+                        descending,
+                        ListAttribute::DateTime(d) => julianday_nullable(*d)
+                    ),
+                },
+                Some(ListAttribute::Float(_)) => by_attribute!(
+                    query,
+                    value.select(item_list_attribute::float_val).single_value(),
+                    after,
+                    descending,
+                    ListAttribute::Float(f) => *f
+                ),
+                Some(ListAttribute::Integer(_)) => by_attribute!(
+                    query,
+                    value.select(item_list_attribute::integer_val).single_value(),
+                    after,
+                    descending,
+                    ListAttribute::Integer(i) => *i
+                ),
+                Some(ListAttribute::Price(_)) => match c {
+                    #[cfg(feature = "postgres")]
+                    MultiConnection::Postgres(_) => by_attribute!(
+                        query,
+                        value.select(item_list_attribute::price_amount).single_value(),
+                        after,
+                        descending,
+                        ListAttribute::Price(p) => DbAmount(p.amount)
+                    ),
+                    MultiConnection::Sqlite(_) => by_attribute!(
+                        query,
+                        AmountCollated(value.select(item_list_attribute::price_amount).single_value()),
+                        after,
+                        descending,
+                        ListAttribute::Price(p) => DbAmount(p.amount)
+                    ),
+                },
+                Some(ListAttribute::Text(_)) => by_attribute!(
+                    query,
+                    value.select(item_list_attribute::text_val).single_value(),
+                    after,
+                    descending,
+                    ListAttribute::Text(t) => t.clone()
+                ),
+                None => after_id(query, after, descending),
+            }
+        }
+        SortKey::CreatedDate => by_timestamp!(c, query, item_list::id, item_list::created, after, descending),
+        SortKey::Id => after_id(query, after, descending),
+        SortKey::ModifiedDate => by_timestamp!(c, query, item_list::id, item_list::modified, after, descending),
+        SortKey::Name => by_column!(query, item_list::id, item_list::name, after, descending, ListAttribute::Text(t) => t.clone()),
+        SortKey::Relevance => {
+            let search = search.ok_or_else(|| {
+                ListManagementError::Validation("sorting by Relevance requires a search".to_string())
+            })?;
+            then_order_by!(query, search_rank(c, search), descending)
+        }
+    };
+    Ok(then_order_by!(query, item_list::id, descending))
+}
+
+fn after_id(
+    query: item_list::BoxedQuery<'static, MultiBackend>,
+    after: Option<&ListCursor>,
+    descending: bool,
+) -> item_list::BoxedQuery<'static, MultiBackend> {
+    match after {
+        Some(cursor) if descending => query.filter(item_list::id.lt(cursor.id as i32)),
+        Some(cursor) => query.filter(item_list::id.gt(cursor.id as i32)),
+        None => query,
+    }
+}
+
+fn invalid_cursor() -> ListManagementError {
+    ListManagementError::Validation("the cursor does not match the sort".to_string())
+}
+
+/// Returns one page of the items in the user's lists that match the selector, in sort order and
+/// then by id, starting after the cursor if given.  Each comes with the id and name of its list.
+pub(crate) fn user_items(
+    pool: &DbPool,
+    user_state: &UserState,
+    selector: &ItemSelector,
+    paging: &PagingRequest,
+    sort: &ItemSortRequest,
+    after: Option<&FoundItemCursor>,
+) -> Result<Vec<FoundItem>, ListManagementError> {
+    if paging.rows == 0 {
+        return Ok(vec![]);
+    }
+    let mut c = db::check_out(pool)?;
+    let query = selected_items(&c, user_state, selector);
+    let descending = sort.descending;
+    let query = match sort.key {
+        ItemSortKey::CreatedDate => by_timestamp!(&*c, query, list_item::id, list_item::created, after, descending),
+        ItemSortKey::Id => match after {
+            Some(cursor) if descending => query.filter(list_item::id.lt(cursor.id as i32)),
+            Some(cursor) => query.filter(list_item::id.gt(cursor.id as i32)),
+            None => query,
+        },
+        ItemSortKey::ModifiedDate => by_timestamp!(&*c, query, list_item::id, list_item::modified, after, descending),
+        ItemSortKey::Name => by_column!(query, list_item::id, list_item::name, after, descending, ListAttribute::Text(t) => t.clone()),
+        ItemSortKey::Source => by_column!(query, list_item::id, list_item::source, after, descending, ListAttribute::Text(t) => t.clone()),
+    };
+    let mut query = then_order_by!(query, list_item::id, descending);
+    if after.is_none() {
+        query = query.offset(paging.start as i64);
+    }
+    let items: Vec<ListItemDb> = query
+        .select(ListItemDb::as_select())
+        .limit(paging.rows as i64)
+        .load(&mut c)?;
+    let attributes = list_item_attributes(&mut c, &items)?;
+    let list_names: HashMap<i32, String> = item_list::table
+        .filter(item_list::id.eq_any(items.iter().map(|lidb| lidb.item_list_id).collect::<Vec<_>>()))
+        .select((item_list::id, item_list::name))
+        .load::<(i32, String)>(&mut c)?
+        .into_iter()
+        .collect();
+    Ok(items
+        .iter()
+        .map(|lidb| FoundItem {
+            list_id: lidb.item_list_id as u64,
+            list_name: list_names.get(&lidb.item_list_id).cloned().unwrap_or_default(),
+            item: to_list_item(lidb, &attributes),
+        })
+        .collect())
+}
+
+/// Counts all of the items in the user's lists that match the selector.
+pub(crate) fn count_user_items(pool: &DbPool, user_state: &UserState, selector: &ItemSelector) -> Result<u64, ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    let count: i64 = selected_items(&c, user_state, selector)
+        .count()
+        .get_result(&mut c)?;
+    Ok(count as u64)
+}
+
+type ItemsQuery = IntoBoxed<'static, InnerJoin<list_item::table, item_list::table>, MultiBackend>;
+type ItemsQuerySource = InnerJoinQuerySource<list_item::table, item_list::table>;
+
+fn selected_items(c: &MultiConnection, user_state: &UserState, selector: &ItemSelector) -> ItemsQuery {
+    let items = list_item::table.inner_join(item_list::table).into_boxed();
+    let mut query = filter_lists!(items, ItemsQuerySource, c, user_state, &selector.limit_lists);
+    if let Some(name_keywords) = &selector.limit_name_keywords {
+        for pattern in keyword_patterns(name_keywords) {
+            let padded_name = " ".into_sql::<Text>().concat(lower(list_item::name)).concat(" ");
+            query = query.filter(padded_name.like(pattern).escape('\\'));
+        }
+    }
+    if !selector.limit_sources.is_empty() {
+        query = query.filter(list_item::source.eq_any(selector.limit_sources.clone()));
+    }
+    for filter in &selector.limit_attributes {
+        query = query.filter(attribute_filter::<ItemsQuerySource>(filter, &|predicate| attribute_matches!(
+            c,
+            list_item::id,
+            list_item_attribute,
+            list_item_attribute::list_item_id,
+            predicate
+        )));
+    }
+    query
+}
+
+#[cfg(test)]
 pub(crate) fn all_lists() -> Vec<ItemList> {
     let mut lists: Vec<ItemListDb> = Vec::new();
     {
-        let mut c = db::connection();
+        let mut c = db::connection().unwrap();
         let mut l: Vec<ItemListDb> = item_list::table
             .select(ItemListDb::as_select())
             .order(item_list::id.asc())
@@ -47,19 +709,776 @@ pub(crate) fn all_lists() -> Vec<ItemList> {
             .unwrap();
         lists.append(&mut l);
     }
-    get_lists(lists)
+    get_lists(db::default_pool(), lists, None, true, true, None).unwrap()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn insert_list(
+    pool: &DbPool,
+    user_id: u64,
+    name: &String,
+    folder: &String,
+    list_access: &ListAccess,
+    list_type: &ListType,
+    attributes: &HashMap<String, ListAttribute>,
+    specification: &RollupSpecification,
+    now: NaiveDateTime,
+) -> Result<u64, ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    c.transaction(|c| {
+        let item_list = ItemListDbInsert {
+            access: &list_access.to_string(),
+            deleted: &false,
+            folder,
+            list_type: &list_type.to_string(),
+            name,
+            owner_user_id: user_id as i32,
+            created: now,
+            modified: now,
+        };
+        let id: i32 = diesel::insert_into(item_list::table)
+            .values(&item_list)
+            .returning(item_list::id)
+            .get_result(c)?;
+        insert_list_attributes(c, id, attributes)?;
+        refresh_rollups(c, id, specification)?;
+        refresh_search(c, id)?;
+        Ok(id as u64)
+    })
+}
+
+/// Changes the list for the user, failing with `PreconditionFailed` if given a `version` it is no
+/// longer at.  Only the owner may change the list's access.
+pub(crate) fn update_list(
+    pool: &DbPool,
+    user_state: &UserState,
+    id: u64,
+    version: Option<u64>,
+    changes: &ItemListUpdate,
+    specification: &RollupSpecification,
+    now: NaiveDateTime,
+) -> Result<(), ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    c.transaction(|c| {
+        let list = lock_list(c, user_state, id, version, now)?;
+        if changes.list_access.as_ref().is_some_and(|access| access.to_string() != list.access) {
+            require_owner(&list, user_state, "change its access")?;
+        }
+        let item_list = ItemListDbUpdate {
+            access: changes.list_access.as_ref().map(|a| a.to_string()),
+            deleted: None,
+            folder: changes.folder.as_ref(),
+            list_type: changes.list_type.as_ref().map(|t| t.to_string()),
+            name: changes.name.as_ref(),
+            modified: now,
+        };
+        diesel::update(item_list::table)
+            .filter(item_list::id.eq(id as i32))
+            .set(&item_list)
+            .execute(c)?;
+        if let Some(attributes) = &changes.attributes {
+            diesel::delete(item_list_attribute::table)
+                .filter(item_list_attribute::item_list_id.eq(id as i32))
+                .execute(c)?;
+            insert_list_attributes(c, id as i32, attributes)?;
+        }
+        // The list's type decides which rollups it has.
+        if changes.list_type.is_some() {
+            refresh_rollups(c, id as i32, specification)?;
+        }
+        if changes.name.is_some() || changes.attributes.is_some() {
+            refresh_search(c, id as i32)?;
+        }
+        Ok(())
+    })
+}
+
+/// Marks the list as deleted, failing with `PreconditionFailed` if given a `version` it is no
+/// longer at.  Only the owner may delete the list.
+pub(crate) fn delete_list(
+    pool: &DbPool,
+    user_state: &UserState,
+    id: u64,
+    version: Option<u64>,
+    now: NaiveDateTime,
+) -> Result<(), ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    c.transaction(|c| {
+        let list = lock_list(c, user_state, id, version, now)?;
+        require_owner(&list, user_state, "delete it")?;
+        let item_list = ItemListDbUpdate {
+            access: None,
+            deleted: Some(true),
+            folder: None,
+            list_type: None,
+            name: None,
+            modified: now,
+        };
+        diesel::update(item_list::table)
+            .filter(item_list::id.eq(id as i32))
+            .set(&item_list)
+            .execute(c)?;
+        Ok(())
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn insert_list_item(
+    pool: &DbPool,
+    user_state: &UserState,
+    item_list_id: u64,
+    name: &String,
+    source: &String,
+    attributes: &HashMap<String, ListAttribute>,
+    specification: &RollupSpecification,
+    now: NaiveDateTime,
+) -> Result<u64, ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    c.transaction(|c| {
+        lock_list(c, user_state, item_list_id, None, now)?;
+        let last_position: Option<i32> = list_item::table
+            .filter(list_item::item_list_id.eq(item_list_id as i32))
+            .select(diesel::dsl::max(list_item::position))
+            .get_result(c)?;
+        let list_item = ListItemDbInsert {
+            item_list_id: &(item_list_id as i32),
+            name,
+            source,
+            position: last_position.map_or(0, |p| p + 1),
+            created: now,
+            modified: now,
+        };
+        let id: i32 = diesel::insert_into(list_item::table)
+            .values(&list_item)
+            .returning(list_item::id)
+            .get_result(c)?;
+        insert_list_item_attributes(c, id, attributes)?;
+        let added = list_item_with(c, item_list_id as i32, id)?;
+        update_rollups(c, item_list_id as i32, specification, None, added.as_ref())?;
+        refresh_search(c, item_list_id as i32)?;
+        Ok(id as u64)
+    })
+}
+
+/// Changes the item, failing with `PreconditionFailed` if given a `version` it is no longer at.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_list_item(
+    pool: &DbPool,
+    user_state: &UserState,
+    item_list_id: u64,
+    id: u64,
+    version: Option<u64>,
+    changes: &ListItemUpdate,
+    specification: &RollupSpecification,
+    now: NaiveDateTime,
+) -> Result<(), ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    c.transaction(|c| {
+        lock_list(c, user_state, item_list_id, None, now)?;
+        let current = expect_item_version(c, item_list_id, id, version)?;
+        let before = list_item_with(c, item_list_id as i32, id as i32)?;
+        let list_item = ListItemDbUpdate {
+            name: changes.name.as_ref(),
+            source: changes.source.as_ref(),
+            modified: now,
+        };
+        let num = diesel::update(list_item::table)
+            .filter(list_item::id.eq(id as i32))
+            .filter(list_item::version.eq(current))
+            .set((&list_item, list_item::version.eq(current + 1)))
+            .execute(c)?;
+        if num == 0 {
+            return Err(stale(id, current));
+        }
+        let mut replaced_names: Vec<&String> = changes.remove_attributes.iter().flatten().collect();
+        if let Some(attributes) = &changes.attributes {
+            replaced_names.extend(attributes.keys());
+        }
+        diesel::delete(list_item_attribute::table)
+            .filter(list_item_attribute::list_item_id.eq(id as i32))
+            .filter(list_item_attribute::name.eq_any(replaced_names))
+            .execute(c)?;
+        if let Some(attributes) = &changes.attributes {
+            insert_list_item_attributes(c, id as i32, attributes)?;
+        }
+        let after = list_item_with(c, item_list_id as i32, id as i32)?;
+        update_rollups(c, item_list_id as i32, specification, before.as_ref(), after.as_ref())?;
+        if before.as_ref().map(searched_text) != after.as_ref().map(searched_text) {
+            refresh_search(c, item_list_id as i32)?;
+        }
+        Ok(())
+    })
+}
+
+/// Deletes the item, failing with `PreconditionFailed` if given a `version` it is no longer at.
+pub(crate) fn delete_list_item(
+    pool: &DbPool,
+    user_state: &UserState,
+    item_list_id: u64,
+    id: u64,
+    version: Option<u64>,
+    specification: &RollupSpecification,
+    now: NaiveDateTime,
+) -> Result<(), ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    c.transaction(|c| {
+        lock_list(c, user_state, item_list_id, None, now)?;
+        let current = expect_item_version(c, item_list_id, id, version)?;
+        let before = list_item_with(c, item_list_id as i32, id as i32)?;
+        // The item's attributes go with it.
+        let num = diesel::delete(list_item::table)
+            .filter(list_item::id.eq(id as i32))
+            .filter(list_item::version.eq(current))
+            .execute(c)?;
+        if num == 0 {
+            return Err(stale(id, current));
+        }
+        update_rollups(c, item_list_id as i32, specification, before.as_ref(), None)?;
+        refresh_search(c, item_list_id as i32)?;
+        Ok(())
+    })
+}
+
+/// Puts the items of the list in the order given by `ids`, which must name every item in the list
+/// once.  The list must be at the `expected` version, if given.
+pub(crate) fn reorder_list_items(
+    pool: &DbPool,
+    user_state: &UserState,
+    item_list_id: u64,
+    expected: Option<u64>,
+    ids: &[u64],
+    now: NaiveDateTime,
+) -> Result<(), ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    c.transaction(|c| {
+        lock_list(c, user_state, item_list_id, expected, now)?;
+        let existing: HashSet<u64> = list_item::table
+            .filter(list_item::item_list_id.eq(item_list_id as i32))
+            .select(list_item::id)
+            .load::<i32>(c)?
+            .into_iter()
+            .map(|id| id as u64)
+            .collect();
+        let given: HashSet<u64> = ids.iter().copied().collect();
+        if given.len() != ids.len() || given != existing {
+            return Err(ListManagementError::Validation(
+                "the new order must name each item in the list exactly once".to_string(),
+            ));
+        }
+        for (position, id) in ids.iter().enumerate() {
+            diesel::update(list_item::table)
+                .filter(list_item::id.eq(*id as i32))
+                .filter(list_item::item_list_id.eq(item_list_id as i32))
+                .set(list_item::position.eq(position as i32))
+                .execute(c)?;
+        }
+        Ok(())
+    })
+}
+
+/// Records a change to the list or its items as a change to the list, failing if the user cannot
+/// see the list, may not change it, or it is not at `expected`.  This is the first statement of
+/// the change's transaction, so the list's row stays locked until the change is made and no other
+/// change can come in between the checks and the change.
+fn lock_list(
+    c: &mut MultiConnection,
+    user_state: &UserState,
+    id: u64,
+    expected: Option<u64>,
+    now: NaiveDateTime,
+) -> Result<LockedList, ListManagementError> {
+    let list: LockedList = diesel::update(item_list::table)
+        .filter(item_list::id.eq(id as i32))
+        .filter(item_list::deleted.eq(false))
+        .filter(visible_to!(user_state))
+        .set((item_list::modified.eq(now), item_list::version.eq(item_list::version + 1)))
+        .returning((item_list::id, item_list::owner_user_id, item_list::access, item_list::version))
+        .get_result(c)
+        .optional()?
+        .ok_or_else(|| ListManagementError::NotFound(id.to_string()))?;
+    if is_read_only_to(list.owner_user_id, &list.access, user_state.user_id) {
+        return Err(ListManagementError::Forbidden(format!("list {} is read-only", id)));
+    }
+    expect_version(id, Some(list.version - 1), expected)?;
+    Ok(list)
+}
+
+/// The list as it is once locked for a change, at the version the change moves it to.
+#[derive(Queryable)]
+struct LockedList {
+    id: i32,
+    owner_user_id: i32,
+    access: String,
+    version: i64,
+}
+
+/// Fails unless the user owns the list, as only the owner may `action` even a Shared list.
+fn require_owner(list: &LockedList, user_state: &UserState, action: &str) -> Result<(), ListManagementError> {
+    if list.owner_user_id as u64 != user_state.user_id {
+        return Err(ListManagementError::Forbidden(format!("only the owner of list {} may {}", list.id, action)));
+    }
+    Ok(())
+}
+
+/// Reads the item's version, failing if there is no such item in the list or it is not at
+/// `expected`.  The change that follows is only made at the version read, so a change made in
+/// between also fails.
+fn expect_item_version(c: &mut MultiConnection, item_list_id: u64, id: u64, expected: Option<u64>) -> Result<i64, ListManagementError> {
+    let current: Option<i64> = list_item::table
+        .filter(list_item::id.eq(id as i32))
+        .filter(list_item::item_list_id.eq(item_list_id as i32))
+        .select(list_item::version)
+        .first(c)
+        .optional()?;
+    expect_version(id, current, expected)
+}
+
+fn expect_version(id: u64, current: Option<i64>, expected: Option<u64>) -> Result<i64, ListManagementError> {
+    let current = current.ok_or_else(|| ListManagementError::NotFound(id.to_string()))?;
+    match expected {
+        Some(expected) if expected != current as u64 => Err(stale(id, expected as i64)),
+        _ => Ok(current),
+    }
+}
+
+fn stale(id: u64, version: i64) -> ListManagementError {
+    ListManagementError::PreconditionFailed(format!("{} has changed since version {}", id, version))
+}
+
+/// Recomputes the list's rollups from its items and stores them in place of those it had.  This
+/// is done in the same transaction as a change to the list's type, and whenever `update_rollups`
+/// cannot follow a change to its items, so the stored rollups are never out of step with them.
+fn refresh_rollups(
+    c: &mut MultiConnection,
+    item_list_id: i32,
+    specification: &RollupSpecification,
+) -> QueryResult<()> {
+    let list_type: String = item_list::table
+        .find(item_list_id)
+        .select(item_list::list_type)
+        .first(c)?;
+    let items = all_list_items_with(c, item_list_id)?;
+    let definitions = specification.definitions_for(
+        item_list_id as u64,
+        &ListType::from_str(&list_type).unwrap_or(ListType::Standard),
+        &items,
+    );
+    diesel::delete(item_list_rollup::table)
+        .filter(item_list_rollup::item_list_id.eq(item_list_id))
+        .execute(c)?;
+    for stored in compute_rollups(&items, &definitions) {
+        diesel::insert_into(item_list_rollup::table)
+            .values(&rollup_columns(item_list_id, &stored))
+            .execute(c)?;
+    }
+    diesel::update(item_list::table)
+        .filter(item_list::id.eq(item_list_id))
+        .set(item_list::rollups_stale.eq(false))
+        .execute(c)?;
+    Ok(())
+}
+
+/// Changes the list's stored rollups for one of its items changing from `before` to `after`,
+/// either being None when the item is added or deleted, without reading its other items unless
+/// the rollups must be recomputed from them.  This is done in the same transaction as every
+/// change to the list's items.
+fn update_rollups(
+    c: &mut MultiConnection,
+    item_list_id: i32,
+    specification: &RollupSpecification,
+    before: Option<&ListItem>,
+    after: Option<&ListItem>,
+) -> QueryResult<()> {
+    let (list_type, rollups_stale): (String, bool) = item_list::table
+        .find(item_list_id)
+        .select((item_list::list_type, item_list::rollups_stale))
+        .first(c)?;
+    if rollups_stale {
+        return refresh_rollups(c, item_list_id, specification);
+    }
+    let rows: Vec<ItemListRollupDb> = item_list_rollup::table
+        .filter(item_list_rollup::item_list_id.eq(item_list_id))
+        .select(ItemListRollupDb::as_select())
+        .order(item_list_rollup::id.asc())
+        .load(c)?;
+    let mut rollups: Vec<StoredRollup> = rows.iter().map(to_stored_rollup).collect::<QueryResult<_>>()?;
+    let list_type = ListType::from_str(&list_type).unwrap_or(ListType::Standard);
+    let definitions = match specification.given_for(item_list_id as u64, &list_type) {
+        Some(definitions) => definitions.clone(),
+        None => {
+            // Each Price attribute of the items has a total, so the stored ones name them all
+            // unless the item brings a new one.
+            let names: BTreeSet<&String> = rollups.iter().map(|stored| &stored.rollup.name).collect();
+            let new_price = after.is_some_and(|item| {
+                item.attributes.iter().any(|(name, attr)| matches!(attr, ListAttribute::Price(_)) && !names.contains(name))
+            });
+            if new_price {
+                return refresh_rollups(c, item_list_id, specification);
+            }
+            RollupSpecification::price_totals(names)
+        }
+    };
+    let unchanged = rollups.clone();
+    if !apply_item_change(&mut rollups, &definitions, before, after) {
+        return refresh_rollups(c, item_list_id, specification);
+    }
+    for ((row, stored), previous) in rows.iter().zip(&rollups).zip(&unchanged) {
+        if stored != previous {
+            diesel::update(item_list_rollup::table)
+                .filter(item_list_rollup::id.eq(row.id))
+                .set(&rollup_columns(item_list_id, stored))
+                .execute(c)?;
+        }
+    }
+    Ok(())
+}
+
+/// The columns a rollup is stored in.
+fn rollup_columns(item_list_id: i32, stored: &StoredRollup) -> ItemListRollupDbInsert<'_> {
+    let rollup = &stored.rollup;
+    let columns = AttributeColumns::from(&rollup.value);
+    let total = stored.total.as_ref();
+    ItemListRollupDbInsert {
+        item_list_id,
+        name: &rollup.name,
+        function: rollup.function.to_string(),
+        value_type: rollup.value.to_string(),
+        float_val: columns.float_val,
+        integer_val: columns.integer_val,
+        price_amount: columns.price_amount,
+        price_source: columns.price_source,
+        total_lines: rollup.total_lines as i64,
+        total_units: rollup.total_units as i64,
+        currency: total.and_then(|total| total.currency.as_ref()),
+        exact_total: total.and_then(|total| total.exact).map(DbAmount),
+        float_total: total.and_then(|total| total.float),
+        float_lines: total.map_or(0, |total| total.float_lines as i64),
+    }
+}
+
+/// Replaces the list's entry in the `list_search` index with its current name, item names and
+/// Text attribute values.  This is done in the same transaction as every change to any of them,
+/// reading only those columns of the list's items.
+fn refresh_search(c: &mut MultiConnection, item_list_id: i32) -> QueryResult<()> {
+    let text_type = ListAttribute::Text(String::new()).to_string();
+    let name: String = item_list::table
+        .find(item_list_id)
+        .select(item_list::name)
+        .first(c)?;
+    let item_names: Vec<String> = list_item::table
+        .filter(list_item::item_list_id.eq(item_list_id))
+        .select(list_item::name)
+        .order((list_item::position.asc(), list_item::id.asc()))
+        .load(c)?;
+    let list_text: Vec<Option<String>> = item_list_attribute::table
+        .filter(item_list_attribute::item_list_id.eq(item_list_id))
+        .filter(item_list_attribute::attribute_type.eq(&text_type))
+        .select(item_list_attribute::text_val)
+        .load(c)?;
+    let item_text: Vec<Option<String>> = list_item_attribute::table
+        .inner_join(list_item::table)
+        .filter(list_item::item_list_id.eq(item_list_id))
+        .filter(list_item_attribute::attribute_type.eq(&text_type))
+        .select(list_item_attribute::text_val)
+        .order(list_item_attribute::id.asc())
+        .load(c)?;
+    let text: Vec<&str> = list_text.iter().chain(&item_text).flatten().map(String::as_str).collect();
+    let (item_names, text) = (item_names.join("\n"), text.join("\n"));
+    match c {
+        #[cfg(feature = "postgres")]
+        MultiConnection::Postgres(c) => {
+            diesel::sql_query(
+                "INSERT INTO list_search (item_list_id, document) VALUES ($1, \
+                 setweight(to_tsvector('simple', $2), 'A') || \
+                 setweight(to_tsvector('simple', $3), 'B') || \
+                 setweight(to_tsvector('simple', $4), 'C')) \
+                 ON CONFLICT (item_list_id) DO UPDATE SET document = EXCLUDED.document",
+            )
+            .bind::<Integer, _>(item_list_id)
+            .bind::<Text, _>(&name)
+            .bind::<Text, _>(&item_names)
+            .bind::<Text, _>(&text)
+            .execute(c)?;
+        }
+        MultiConnection::Sqlite(c) => {
+            diesel::sql_query("DELETE FROM list_search WHERE rowid = ?")
+                .bind::<Integer, _>(item_list_id)
+                .execute(c)?;
+            diesel::sql_query("INSERT INTO list_search (rowid, name, items, text) VALUES (?, ?, ?, ?)")
+                .bind::<Integer, _>(item_list_id)
+                .bind::<Text, _>(&name)
+                .bind::<Text, _>(&item_names)
+                .bind::<Text, _>(&text)
+                .execute(c)?;
+        }
+    }
+    Ok(())
 }
 
+/// What of an item goes into its list's entry in the `list_search` index.
+fn searched_text(item: &ListItem) -> (&str, BTreeMap<&str, &str>) {
+    let text = item.attributes.iter().filter_map(|(name, attr)| match attr {
+        ListAttribute::Text(t) => Some((name.as_str(), t.as_str())),
+        _ => None,
+    });
+    (item.name.as_str(), text.collect())
+}
+
+/// Recomputes the stored rollups of every list, deleted or not, returning how many lists there
+/// were.  Needed after the rollup definitions change.
+pub(crate) fn rebuild_rollups(pool: &DbPool, specification: &RollupSpecification) -> Result<u64, ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    let ids: Vec<i32> = item_list::table
+        .select(item_list::id)
+        .order(item_list::id.asc())
+        .load(&mut c)?;
+    for id in &ids {
+        c.transaction(|c| refresh_rollups(c, *id, specification))?;
+    }
+    Ok(ids.len() as u64)
+}
 
-fn get_lists(lists: Vec<ItemListDb>) -> Vec<ItemList> {
-    let account_types_by_id: HashMap<i32, AccountType> = all_account_types();
-    let mut c = db::connection();
+fn insert_list_item_attributes(
+    c: &mut MultiConnection,
+    list_item_id: i32,
+    attributes: &HashMap<String, ListAttribute>,
+) -> QueryResult<()> {
+    for (name, attr) in attributes {
+        let columns = AttributeColumns::from(attr);
+        diesel::insert_into(list_item_attribute::table)
+            .values(&ListItemAttributeDbInsert {
+                list_item_id,
+                name,
+                attribute_type: attr.to_string(),
+                bool_val: columns.bool_val,
+                timestamp_val: columns.timestamp_val,
+                float_val: columns.float_val,
+                integer_val: columns.integer_val,
+                text_val: columns.text_val,
+                price_amount: columns.price_amount,
+                price_source: columns.price_source,
+            })
+            .execute(c)?;
+    }
+    Ok(())
+}
+
+fn insert_list_attributes(
+    c: &mut MultiConnection,
+    item_list_id: i32,
+    attributes: &HashMap<String, ListAttribute>,
+) -> QueryResult<()> {
+    for (name, attr) in attributes {
+        let columns = AttributeColumns::from(attr);
+        diesel::insert_into(item_list_attribute::table)
+            .values(&ItemListAttributeDbInsert {
+                item_list_id,
+                name,
+                attribute_type: attr.to_string(),
+                bool_val: columns.bool_val,
+                timestamp_val: columns.timestamp_val,
+                float_val: columns.float_val,
+                integer_val: columns.integer_val,
+                text_val: columns.text_val,
+                price_amount: columns.price_amount,
+                price_source: columns.price_source,
+            })
+            .execute(c)?;
+    }
+    Ok(())
+}
+
+/// The value columns shared by `item_list_attribute` and `list_item_attribute`.  Exactly one is
+/// set, depending on the type of the attribute, except that a price sets both its amount and
+/// source.
+#[derive(Default)]
+struct AttributeColumns {
+    bool_val: Option<bool>,
+    timestamp_val: Option<NaiveDateTime>,
+    float_val: Option<f64>,
+    integer_val: Option<i64>,
+    text_val: Option<String>,
+    price_amount: Option<DbAmount>,
+    price_source: Option<String>,
+}
+
+impl From<&ListAttribute> for AttributeColumns {
+    fn from(attr: &ListAttribute) -> Self {
+        match attr {
+            ListAttribute::Boolean(b) => AttributeColumns { bool_val: Some(*b), ..Default::default() },
+            ListAttribute::DateTime(dt) => AttributeColumns { timestamp_val: Some(*dt), ..Default::default() },
+            ListAttribute::Float(f) => AttributeColumns { float_val: Some(*f), ..Default::default() },
+            ListAttribute::Integer(i) => AttributeColumns { integer_val: Some(*i), ..Default::default() },
+            ListAttribute::Price(p) => AttributeColumns {
+                price_amount: Some(DbAmount(p.amount)),
+                price_source: Some(p.source.clone()),
+                ..Default::default()
+            },
+            ListAttribute::Text(s) => AttributeColumns { text_val: Some(s.clone()), ..Default::default() },
+        }
+    }
+}
+
+/// Returns up to `rows` of the list's items in list order, starting after the cursor if given,
+/// along with the cursor of the last one returned.
+pub(crate) fn list_items(
+    pool: &DbPool,
+    item_list_id: u64,
+    after: Option<&ItemCursor>,
+    start: u64,
+    rows: u64,
+) -> Result<(Vec<ListItem>, Option<ItemCursor>), ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    let mut query: list_item::BoxedQuery<'static, MultiBackend> = list_item::table
+        .filter(list_item::item_list_id.eq(item_list_id as i32))
+        .into_boxed();
+    query = match after {
+        Some(cursor) => query.filter(
+            list_item::position
+                .gt(cursor.position)
+                .or(list_item::position.eq(cursor.position).and(list_item::id.gt(cursor.id as i32))),
+        ),
+        None => query.offset(start as i64),
+    };
+    let items: Vec<ListItemDb> = query
+        .select(ListItemDb::as_select())
+        .order((list_item::position.asc(), list_item::id.asc()))
+        .limit(rows as i64)
+        .load(&mut c)?;
+    let last = items.last().map(|lidb| ItemCursor {
+        id: lidb.id as u64,
+        list_id: item_list_id,
+        position: lidb.position,
+    });
+    let attributes = list_item_attributes(&mut c, &items)?;
+    Ok((items.iter().map(|lidb| to_list_item(lidb, &attributes)).collect(), last))
+}
+
+/// Returns the item of the list with the given id.
+pub(crate) fn list_item(pool: &DbPool, item_list_id: u64, id: u64) -> Result<ListItem, ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    list_item_with(&mut c, item_list_id as i32, id as i32)?.ok_or_else(|| ListManagementError::NotFound(id.to_string()))
+}
+
+fn list_item_with(c: &mut MultiConnection, item_list_id: i32, id: i32) -> QueryResult<Option<ListItem>> {
+    let items: Vec<ListItemDb> = list_item::table
+        .filter(list_item::id.eq(id))
+        .filter(list_item::item_list_id.eq(item_list_id))
+        .select(ListItemDb::as_select())
+        .load(c)?;
+    let attributes = list_item_attributes(c, &items)?;
+    Ok(items.first().map(|lidb| to_list_item(lidb, &attributes)))
+}
+
+/// Returns every one of the list's items in list order.
+pub(crate) fn all_list_items(pool: &DbPool, item_list_id: u64) -> Result<Vec<ListItem>, ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    Ok(all_list_items_with(&mut c, item_list_id as i32)?)
+}
+
+fn all_list_items_with(c: &mut MultiConnection, item_list_id: i32) -> QueryResult<Vec<ListItem>> {
+    let items: Vec<ListItemDb> = list_item::table
+        .filter(list_item::item_list_id.eq(item_list_id))
+        .select(ListItemDb::as_select())
+        .order((list_item::position.asc(), list_item::id.asc()))
+        .load(c)?;
+    let attributes = list_item_attributes(c, &items)?;
+    Ok(items.iter().map(|lidb| to_list_item(lidb, &attributes)).collect())
+}
+
+pub(crate) fn count_list_items(pool: &DbPool, item_list_id: u64) -> Result<u64, ListManagementError> {
+    let mut c = db::check_out(pool)?;
+    let count: i64 = list_item::table
+        .filter(list_item::item_list_id.eq(item_list_id as i32))
+        .count()
+        .get_result(&mut c)?;
+    Ok(count as u64)
+}
+
+fn list_item_attributes(
+    c: &mut MultiConnection,
+    items: &[ListItemDb],
+) -> QueryResult<HashMap<i32, HashMap<String, ListAttribute>>> {
+    let list_item_attributes: Vec<ListItemAttributeDb> = ListItemAttributeDb::belonging_to(items)
+        .select(ListItemAttributeDb::as_select())
+        .order(list_item_attribute::id.asc())
+        .load(c)?;
+
+    let mut list_item_attribute_map: HashMap<i32, HashMap<String, ListAttribute>> = HashMap::new();
+    for liadb in list_item_attributes {
+        let lia_type = liadb.attribute_type;
+        let lia_attr: ListAttribute = ListAttribute::from_str(&lia_type)
+            .unwrap_or_else(|_| ListAttribute::Text("".to_string()));
+        let lia_attr: ListAttribute = match lia_attr {
+            ListAttribute::Boolean(_) => ListAttribute::Boolean(liadb.bool_val.unwrap_or(false)),
+            ListAttribute::DateTime(_) => ListAttribute::DateTime(
+                liadb
+                    .timestamp_val
+                    .unwrap_or(Utc::now().naive_utc()),
+            ),
+            ListAttribute::Float(_) => ListAttribute::Float(liadb.float_val.unwrap_or(0f64)),
+            ListAttribute::Integer(_) => {
+                ListAttribute::Integer(liadb.integer_val.unwrap_or(0))
+            }
+            ListAttribute::Price(_) => {
+                ListAttribute::Price(to_price(liadb.price_amount, liadb.price_source)?)
+            }
+            ListAttribute::Text(_) => ListAttribute::Text(liadb.text_val.unwrap_or("".to_string())),
+        };
+        list_item_attribute_map
+            .entry(liadb.list_item_id)
+            .or_default()
+            .insert(liadb.name, lia_attr);
+    }
+    Ok(list_item_attribute_map)
+}
+
+fn to_list_item(lidb: &ListItemDb, attributes: &HashMap<i32, HashMap<String, ListAttribute>>) -> ListItem {
+    ListItem {
+        id: Some(lidb.id as u64),
+        attributes: attributes.get(&lidb.id).cloned().unwrap_or_default(),
+        created: lidb.created,
+        modified: lidb.modified,
+        name: lidb.name.clone(),
+        source: lidb.source.clone(),
+        version: lidb.version as u64,
+    }
+}
+
+/// Completes the lists, marking them read-only as seen by the given user.  Stored rollups are
+/// loaded when given a specification, which is used to first recompute any that are stale.
+fn get_lists(
+    pool: &DbPool,
+    lists: Vec<ItemListDb>,
+    user_id: Option<u64>,
+    load_attributes: bool,
+    load_items: bool,
+    rollups: Option<&RollupSpecification>,
+) -> Result<Vec<ItemList>, ListManagementError> {
+    let account_types_by_id: HashMap<i32, AccountType> = all_account_types(pool)?;
+    let mut c = db::check_out(pool)?;
+
+    let mut rollups_per_list: HashMap<i32, Vec<ItemListRollup>> = HashMap::new();
+    if let Some(specification) = rollups {
+        for ildb in lists.iter().filter(|ildb| ildb.rollups_stale) {
+            c.transaction(|c| refresh_rollups(c, ildb.id, specification))?;
+        }
+        let stored: Vec<ItemListRollupDb> = ItemListRollupDb::belonging_to(&lists)
+            .select(ItemListRollupDb::as_select())
+            .order(item_list_rollup::id.asc())
+            .load(&mut c)?;
+        for ilrdb in stored {
+            rollups_per_list
+                .entry(ilrdb.item_list_id)
+                .or_default()
+                .push(to_rollup(&ilrdb)?);
+        }
+    }
 
     let accounts: Vec<(ItemListAccountDb, AccountDb)> = ItemListAccountDb::belonging_to(&lists)
         .inner_join(account::table)
         .select((ItemListAccountDb::as_select(), AccountDb::as_select()))
-        .load(&mut c)
-        .unwrap();
+        .load(&mut c)?;
 
     let accounts_per_lists: HashMap<i32, Vec<Account>> = accounts
         .grouped_by(&lists)
@@ -87,50 +1506,25 @@ fn get_lists(lists: Vec<ItemListDb>) -> Vec<ItemList> {
         })
         .collect();
 
-    let items: Vec<ListItemDb> = ListItemDb::belonging_to(&lists)
-        .select(ListItemDb::as_select())
-        .order(list_item::id.asc())
-        .load(&mut c)
-        .unwrap();
+    let items: Vec<ListItemDb> = if load_items {
+        ListItemDb::belonging_to(&lists)
+            .select(ListItemDb::as_select())
+            .order((list_item::position.asc(), list_item::id.asc()))
+            .load(&mut c)?
+    } else {
+        vec![]
+    };
 
-    let list_attributes: Vec<ItemListAttributeDb> = ItemListAttributeDb::belonging_to(&lists)
-        .select(ItemListAttributeDb::as_select())
-        .order(item_list_attribute::id.asc())
-        .load(&mut c)
-        .unwrap();
+    let list_attributes: Vec<ItemListAttributeDb> = if load_attributes {
+        ItemListAttributeDb::belonging_to(&lists)
+            .select(ItemListAttributeDb::as_select())
+            .order(item_list_attribute::id.asc())
+            .load(&mut c)?
+    } else {
+        vec![]
+    };
 
-    let list_item_attributes: Vec<ListItemAttributeDb> = ListItemAttributeDb::belonging_to(&items)
-        .select(ListItemAttributeDb::as_select())
-        .order(list_item_attribute::id.asc())
-        .load(&mut c)
-        .unwrap();
-
-    let mut list_item_attribute_map: HashMap<i32, HashMap<String, ListAttribute>> = HashMap::new();
-    for liadb in list_item_attributes {
-        let lia_type = liadb.attribute_type;
-        let lia_attr: ListAttribute = ListAttribute::from_str(&lia_type)
-            .unwrap_or_else(|_| ListAttribute::Text("".to_string()));
-        let lia_attr: ListAttribute = match lia_attr {
-            ListAttribute::Boolean(_) => ListAttribute::Boolean(liadb.bool_val.unwrap_or(false)),
-            ListAttribute::DateTime(_) => ListAttribute::DateTime(NaiveDateTime::from(
-                liadb
-                    .timestamp_val
-                    .unwrap_or(Utc::now().naive_utc()),
-            )),
-            ListAttribute::Float(_) => ListAttribute::Float(liadb.float_val.unwrap_or(0f32) as f64),
-            ListAttribute::Integer(_) => {
-                ListAttribute::Integer(liadb.integer_val.unwrap_or(0) as i64)
-            }
-            ListAttribute::Price(_) => {
-                ListAttribute::Price(to_price(liadb.text_val.unwrap_or("".to_string())))
-            }
-            ListAttribute::Text(_) => ListAttribute::Text(liadb.text_val.unwrap_or("".to_string())),
-        };
-        list_item_attribute_map
-            .entry(liadb.list_item_id)
-            .or_default()
-            .insert(liadb.name, lia_attr);
-    }
+    let list_item_attribute_map = list_item_attributes(&mut c, &items)?;
 
     let mut list_attribute_map: HashMap<i32, HashMap<String, ListAttribute>> = HashMap::new();
     for iladb in list_attributes {
@@ -139,17 +1533,17 @@ fn get_lists(lists: Vec<ItemListDb>) -> Vec<ItemList> {
             .unwrap_or_else(|_| ListAttribute::Text("".to_string()));
         let ila_attr: ListAttribute = match ila_attr {
             ListAttribute::Boolean(_) => ListAttribute::Boolean(iladb.bool_val.unwrap_or(false)),
-            ListAttribute::DateTime(_) => ListAttribute::DateTime(NaiveDateTime::from(
+            ListAttribute::DateTime(_) => ListAttribute::DateTime(
                 iladb
                     .timestamp_val
                     .unwrap_or(Utc::now().naive_utc()),
-            )),
-            ListAttribute::Float(_) => ListAttribute::Float(iladb.float_val.unwrap_or(0f32) as f64),
+            ),
+            ListAttribute::Float(_) => ListAttribute::Float(iladb.float_val.unwrap_or(0f64)),
             ListAttribute::Integer(_) => {
-                ListAttribute::Integer(iladb.integer_val.unwrap_or(0) as i64)
+                ListAttribute::Integer(iladb.integer_val.unwrap_or(0))
             }
             ListAttribute::Price(_) => {
-                ListAttribute::Price(to_price(iladb.text_val.unwrap_or("".to_string())))
+                ListAttribute::Price(to_price(iladb.price_amount, iladb.price_source)?)
             }
             ListAttribute::Text(_) => ListAttribute::Text(iladb.text_val.unwrap_or("".to_string())),
         };
@@ -166,7 +1560,7 @@ fn get_lists(lists: Vec<ItemListDb>) -> Vec<ItemList> {
         .map(|(i, l)| (l, i))
         .collect::<Vec<(ItemListDb, Vec<ListItemDb>)>>();
 
-    items_per_list
+    Ok(items_per_list
         .iter()
         .map(|ildb| -> ItemList {
             let list_attr_map_opt: Option<&HashMap<String, ListAttribute>> =
@@ -180,71 +1574,78 @@ fn get_lists(lists: Vec<ItemListDb>) -> Vec<ItemList> {
             ItemList {
                 id: Some(il_id as u64),
                 attributes: list_attr_map.to_owned(),
-                created: NaiveDateTime::from(ildb.0.created),
+                created: ildb.0.created,
                 deleted: ildb.0.deleted,
                 folder: ildb.0.folder.clone(),
-                items: ildb
-                    .1
-                    .iter()
-                    .map(|lidb| -> Option<ListItem> {
-                        let item_list_attr_map_opt: Option<&HashMap<String, ListAttribute>> =
-                            list_item_attribute_map.get(&lidb.id);
-                        let item_attr_map = match item_list_attr_map_opt {
-                            None => &HashMap::with_capacity(0),
-                            Some(_) => item_list_attr_map_opt.unwrap(),
-                        };
-                        let li = ListItem {
-                            id: Some(lidb.id as u64),
-                            attributes: item_attr_map.to_owned(),
-                            created: NaiveDateTime::from(lidb.created),
-                            modified: NaiveDateTime::from(lidb.modified),
-                            name: lidb.name.clone(),
-                            source: lidb.source.clone(),
-                        };
-                        Some(li)
-                    })
-                    .collect(),
+                items: if !load_items {
+                    None
+                } else {
+                    Some(ildb.1.iter().map(|lidb| to_list_item(lidb, &list_item_attribute_map)).collect())
+                },
                 list_access: ListAccess::from_str(&ildb.0.access).unwrap_or(ListAccess::Public),
                 list_accounts: accounts_per_lists.get(&il_id).unwrap_or(&vec![]).to_owned(),
                 list_type: ListType::from_str(&ildb.0.list_type).unwrap_or(ListType::Standard),
-                modified: NaiveDateTime::from(ildb.0.modified),
+                modified: ildb.0.modified,
                 name: ildb.0.name.clone(),
-                read_only: false,
-                rollups: None,
+                read_only: user_id.is_some_and(|user_id| is_read_only(&ildb.0, user_id)),
+                rollups: rollups.map(|_| rollups_per_list.get(&il_id).cloned().unwrap_or_default()),
+                converted_rollups: None,
+                version: ildb.0.version as u64,
             }
         })
-        .collect()
+        .collect())
 }
 
-impl Display for Price {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "PRICE: _{} _{}", self.amount, self.source)
-    }
+/// Reads a stored price, with its amount as it was written.  One that is missing is an error
+/// rather than a zero.
+fn to_price(amount: Option<DbAmount>, source: Option<String>) -> QueryResult<Price> {
+    let amount = amount.ok_or_else(|| DeserializationError("a stored price has no amount".into()))?;
+    Ok(Price {
+        amount: amount.0,
+        source: source.unwrap_or_default(),
+    })
 }
 
-fn to_price(str: String) -> Price {
-    let re = Regex::new(r"^PRICE[:]\s_([^\s]+)\s_([^\s]+)$").unwrap();
-    let Some(caps) = re.captures(&str) else {
-        return Price {
-            amount: Decimal::from_str("0.00").unwrap(),
-            source: "".to_string(),
-        };
+/// Reads a stored rollup, whose value is always an Integer, Float or Price.
+fn to_rollup(ilrdb: &ItemListRollupDb) -> QueryResult<ItemListRollup> {
+    let function = RollupFunction::from_str(&ilrdb.function).map_err(|e| DeserializationError(Box::new(e)))?;
+    let value = match ListAttribute::from_str(&ilrdb.value_type) {
+        Ok(ListAttribute::Float(_)) => ListAttribute::Float(ilrdb.float_val.unwrap_or(0f64)),
+        Ok(ListAttribute::Integer(_)) => ListAttribute::Integer(ilrdb.integer_val.unwrap_or(0)),
+        Ok(ListAttribute::Price(_)) => ListAttribute::Price(to_price(ilrdb.price_amount, ilrdb.price_source.clone())?),
+        _ => return Err(DeserializationError(format!("a stored rollup has a {} value", ilrdb.value_type).into())),
     };
-    let amount_str = &caps[1];
-    let source = &caps[2];
-    Price {
-        amount: Decimal::from_str(amount_str).unwrap(),
-        source: source.to_string(),
-    }
+    Ok(ItemListRollup {
+        name: ilrdb.name.clone(),
+        function,
+        value,
+        total_lines: ilrdb.total_lines as u64,
+        total_units: ilrdb.total_units as u64,
+    })
+}
+
+/// Reads a stored rollup along with the running total it was evaluated from, which rollups that
+/// count items do not have.
+fn to_stored_rollup(ilrdb: &ItemListRollupDb) -> QueryResult<StoredRollup> {
+    let rollup = to_rollup(ilrdb)?;
+    let counted = matches!(rollup.function, RollupFunction::Count | RollupFunction::CountDistinct);
+    let total = (!counted).then(|| RollupTotal {
+        currency: ilrdb.currency.clone(),
+        exact: ilrdb.exact_total.map(|amount| amount.0),
+        float: ilrdb.float_total,
+        float_lines: ilrdb.float_lines as u64,
+        lines: rollup.total_lines,
+        units: rollup.total_units,
+    });
+    Ok(StoredRollup { rollup, total })
 }
 
-pub(crate) fn all_account_types() -> HashMap<i32, AccountType> {
-    let mut c = db::connection();
+pub(crate) fn all_account_types(pool: &DbPool) -> Result<HashMap<i32, AccountType>, ListManagementError> {
+    let mut c = db::check_out(pool)?;
     let mut m: HashMap<i32, AccountType> = HashMap::new();
     let v = account_type::table
         .select(AccountTypeDb::as_select())
-        .load(&mut c)
-        .unwrap();
+        .load(&mut c)?;
     for atdb in v {
         let id = atdb.id;
         let at = AccountType {
@@ -254,13 +1655,14 @@ pub(crate) fn all_account_types() -> HashMap<i32, AccountType> {
         };
         m.insert(id, at);
     }
-    m
+    Ok(m)
 }
 
 #[cfg(test)]
 pub mod tests {
     use serial_test::serial;
 
+    use crate::clock::{Clock, SystemClock};
     use crate::common::ListAttribute;
     use crate::db;
     use crate::models::UserDb;
@@ -321,7 +1723,7 @@ pub mod tests {
     fn test_user_lists() {
         setup();
         let user_ids = {
-            let c = &mut db::connection();
+            let c = &mut db::connection().unwrap();
             let users_vec = user::table
                 .select(UserDb::as_select())
                 .order(user::id.asc())
@@ -333,15 +1735,24 @@ pub mod tests {
             active_user_accounts: vec![],
             user_id: user_ids.0 as u64,
         };
-        let v = user_lists(us);
+        let v = every_user_list(&us, true, true);
         assert_eq!(1, v.len());
         assert_eq!("Item List One", v[0].name);
+        assert_eq!(1, v[0].attributes.len());
+        assert!(v[0].items.is_none());
+        assert!(v[0].rollups.is_some());
+
+        let v = every_user_list(&us, false, false);
+        assert_eq!(1, v.len());
+        assert!(v[0].attributes.is_empty());
+        assert!(v[0].items.is_none());
+        assert!(v[0].rollups.is_none());
 
         let us1: UserState = UserState {
             active_user_accounts: vec![],
             user_id: user_ids.1 as u64,
         };
-        let v1 = user_lists(us1);
+        let v1 = every_user_list(&us1, true, true);
         assert_eq!(1, v1.len());
         assert_eq!("Item List Two", v1[0].name);
 
@@ -349,10 +1760,89 @@ pub mod tests {
             active_user_accounts: vec![],
             user_id: (user_ids.0 + user_ids.1) as u64,
         };
-        let v2 = user_lists(us2);
+        let v2 = every_user_list(&us2, true, true);
         assert_eq!(0, v2.len());
     }
 
+    #[test]
+    #[serial]
+    fn test_user_lists_shared_through_accounts() {
+        setup_db();
+        let (a1_id, a2_id) = setup_accounts();
+        let user_id_1 = insert_user("name", "source", "source-1");
+        let user_id_2 = insert_user("name", "source", "source-2");
+        setup_lists(vec![a2_id], vec![a1_id], user_id_1, user_id_2);
+        let list_1_id = all_lists()[0].id.unwrap();
+        let with_accounts = |account_ids: Vec<i32>| UserState {
+            active_user_accounts: account_ids
+                .into_iter()
+                .map(|id| Account {
+                    id: Some(id as u64),
+                    account_type: AccountType { id: None, name: "".to_string(), source: "".to_string() },
+                    account_source_id: "".to_string(),
+                })
+                .collect(),
+            user_id: user_id_2 as u64,
+        };
+
+        // Public lists of others are read-only.
+        let v = every_user_list(&with_accounts(vec![a2_id]), false, false);
+        assert_eq!(2, v.len());
+        assert_eq!("Item List One", v[0].name);
+        assert!(v[0].read_only);
+        assert_eq!("Item List Two", v[1].name);
+        assert!(!v[1].read_only);
+        assert_eq!(1, every_user_list(&with_accounts(vec![]), false, false).len());
+        assert_eq!(1, every_user_list(&with_accounts(vec![a1_id]), false, false).len());
+
+        let mut selector = every_list_selector();
+        selector.limit_show_read_only = false;
+        let paging = PagingRequest { start: 0, rows: 100, cursor: None };
+        let sort = SortRequest { descending: false, key: SortKey::Id };
+        let v = user_lists(db::default_pool(), &with_accounts(vec![a2_id]), &selector, &paging, &sort, None, false, None).unwrap();
+        assert_eq!(1, v.len());
+        assert_eq!("Item List Two", v[0].name);
+
+        // Shared lists may be changed by anyone they are shared with.
+        let owner = UserState { active_user_accounts: vec![], user_id: user_id_1 as u64 };
+        update_list(db::default_pool(), &owner, list_1_id, None, &ItemListUpdate {
+            list_access: Some(ListAccess::Shared),
+            ..Default::default()
+        }, &RollupSpecification::default(), SystemClock.now()).unwrap();
+        let v = user_lists(db::default_pool(), &with_accounts(vec![a2_id]), &selector, &paging, &sort, None, false, None).unwrap();
+        assert_eq!(2, v.len());
+        assert!(!v[0].read_only);
+
+        update_list(db::default_pool(), &owner, list_1_id, None, &ItemListUpdate {
+            list_access: Some(ListAccess::Private),
+            ..Default::default()
+        }, &RollupSpecification::default(), SystemClock.now()).unwrap();
+        assert_eq!(1, every_user_list(&with_accounts(vec![a2_id]), false, false).len());
+    }
+
+    fn every_list_selector() -> ListSelector {
+        ListSelector {
+            limit_show_read_only: true,
+            limit_list_types: vec![],
+            limit_list_access: vec![],
+            limit_show_deleted: true,
+            limit_show_not_deleted: true,
+            limit_in_folders: vec![],
+            limit_name_keywords: None,
+            limit_list_ids: vec![],
+            limit_search: None,
+            limit_attributes: vec![],
+        }
+    }
+
+    fn every_user_list(user_state: &UserState, load_attributes: bool, load_rollups: bool) -> Vec<ItemList> {
+        let selector = every_list_selector();
+        let paging = PagingRequest { start: 0, rows: 100, cursor: None };
+        let sort = SortRequest { descending: false, key: SortKey::Id };
+        let specification = RollupSpecification::default();
+        user_lists(db::default_pool(), user_state, &selector, &paging, &sort, None, load_attributes, load_rollups.then_some(&specification)).unwrap()
+    }
+
     fn setup() {
         setup_db();
         let (a1_id, a2_id) = setup_accounts();
@@ -1,29 +1,70 @@
 use std::env;
 
-use actix_web::{App, HttpServer};
-use dotenvy::dotenv;
+use actix_web::{App, HttpServer, web};
 use tracing::dispatcher::set_global_default;
 use tracing_actix_web::TracingLogger;
 use tracing_log::LogTracer;
 
+use list_management::app_state::AppState;
+use list_management::config::AppConfig;
+use list_management::db;
 use list_management::helpers::tracing_subscriber;
+use list_management::list_service;
 use list_management::route_config;
 
+/// Serves the API, or with the argument `rebuild-rollups`, recomputes the stored rollups of every
+/// list and exits.  Settings may be given as flags, such as `--server.port=8081`, before or after
+/// the command; see `AppConfig::load`.
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    dotenv().ok();
-    let host = env::var("SERVER_HOST").expect("SERVER_HOST must be set");
-    let port_str = env::var("SERVER_PORT").expect("SERVER_PORT must be set");
-    let port: u16 = port_str.parse().expect("SERVER_PORT must be a positive integer.");
-    let log_level = env::var("LOG_LEVEL").unwrap_or("warn".to_string());
+    let (flags, commands): (Vec<String>, Vec<String>) = env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let config = match AppConfig::load(&flags) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
 
     LogTracer::init().expect("Failed to initalize the LogTracer.");
-    set_global_default(tracing_subscriber(log_level, std::io::stdout).into()).expect("Failed to set subscriber");
+    set_global_default(tracing_subscriber(config.log_level.clone(), std::io::stdout).into()).expect("Failed to set subscriber");
 
-    let _ = HttpServer::new(|| {
+    let state = AppState::new(config).expect("Could not connect to the database");
+    db::migrate(&state.pool, state.config.database.migrations).expect("Could not start with the database schema as it is");
+
+    match commands.first().map(String::as_str) {
+        None => {}
+        Some("rebuild-rollups") => {
+            let lists = list_service::rebuild_rollups(&state.pool, &state.config.rollups.specification).expect("Could not rebuild the rollups");
+            println!("Rebuilt the rollups of {} lists", lists);
+            return Ok(());
+        }
+        Some(command) => {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Unknown command '{}': the only command is rebuild-rollups", command),
+            ));
+        }
+    }
+
+    let server_config = state.config.server.clone();
+    let state = web::Data::new(state);
+    let mut server = HttpServer::new(move || {
         App::new()
+            .app_data(state.clone())
             .wrap(TracingLogger::default())
             .configure(route_config::config)
-    }).bind((host, port)).unwrap().run().await;
-    Ok(())
+    });
+    if let Some(workers) = server_config.workers {
+        server = server.workers(workers);
+    }
+    let address = (server_config.host, server_config.port);
+    let server = match &server_config.tls {
+        Some(tls) => {
+            let tls = tls.server_config().expect("The configuration was checked as it was read");
+            server.bind_rustls_0_23(address, tls)?
+        }
+        None => server.bind(address)?,
+    };
+    server.run().await
 }
@@ -2,6 +2,8 @@ use chrono::{NaiveDateTime};
 use diesel::prelude::*;
 use std::hash::{Hash, Hasher};
 
+use crate::db::DbAmount;
+
 #[derive(Queryable, Selectable, Identifiable, PartialEq, Eq, Hash, Debug)]
 #[diesel(table_name = crate::schema::account)]
 pub struct AccountDb {
@@ -30,6 +32,8 @@ pub struct ItemListDb {
     pub name: String,
     pub modified: NaiveDateTime,
     pub owner_user_id: i32,
+    pub rollups_stale: bool,
+    pub version: i64,
 }
 
 #[derive(Insertable)]
@@ -41,6 +45,19 @@ pub struct ItemListDbInsert<'a> {
     pub list_type: &'a String,
     pub name: &'a String,
     pub owner_user_id: i32,
+    pub created: NaiveDateTime,
+    pub modified: NaiveDateTime,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::item_list)]
+pub struct ItemListDbUpdate<'a> {
+    pub access: Option<String>,
+    pub deleted: Option<bool>,
+    pub folder: Option<&'a String>,
+    pub list_type: Option<String>,
+    pub name: Option<&'a String>,
+    pub modified: NaiveDateTime,
 }
 
 #[derive(Identifiable, Selectable, Queryable, Associations, Debug)]
@@ -63,9 +80,27 @@ pub struct ItemListAttributeDb {
     pub attribute_type: String,
     pub bool_val: Option<bool>,
     pub timestamp_val: Option<NaiveDateTime>,
-    pub float_val: Option<f32>,
-    pub integer_val: Option<i32>,
+    pub float_val: Option<f64>,
+    pub integer_val: Option<i64>,
+    pub text_val: Option<String>,
+    pub price_amount: Option<DbAmount>,
+    pub price_source: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::item_list_attribute)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct ItemListAttributeDbInsert<'a> {
+    pub item_list_id: i32,
+    pub name: &'a String,
+    pub attribute_type: String,
+    pub bool_val: Option<bool>,
+    pub timestamp_val: Option<NaiveDateTime>,
+    pub float_val: Option<f64>,
+    pub integer_val: Option<i64>,
     pub text_val: Option<String>,
+    pub price_amount: Option<DbAmount>,
+    pub price_source: Option<String>,
 }
 
 impl Eq for ItemListAttributeDb {}
@@ -76,6 +111,47 @@ impl Hash for ItemListAttributeDb {
     }
 }
 
+#[derive(Queryable, Selectable, Identifiable, Associations, PartialEq, Debug)]
+#[diesel(table_name = crate::schema::item_list_rollup)]
+#[diesel(belongs_to(ItemListDb, foreign_key = item_list_id))]
+pub struct ItemListRollupDb {
+    pub id: i32,
+    pub item_list_id: i32,
+    pub name: String,
+    pub function: String,
+    pub value_type: String,
+    pub float_val: Option<f64>,
+    pub integer_val: Option<i64>,
+    pub price_amount: Option<DbAmount>,
+    pub price_source: Option<String>,
+    pub total_lines: i64,
+    pub total_units: i64,
+    pub currency: Option<String>,
+    pub exact_total: Option<DbAmount>,
+    pub float_total: Option<f64>,
+    pub float_lines: i64,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = crate::schema::item_list_rollup)]
+#[diesel(treat_none_as_default_value = false, treat_none_as_null = true)]
+pub struct ItemListRollupDbInsert<'a> {
+    pub item_list_id: i32,
+    pub name: &'a String,
+    pub function: String,
+    pub value_type: String,
+    pub float_val: Option<f64>,
+    pub integer_val: Option<i64>,
+    pub price_amount: Option<DbAmount>,
+    pub price_source: Option<String>,
+    pub total_lines: i64,
+    pub total_units: i64,
+    pub currency: Option<&'a String>,
+    pub exact_total: Option<DbAmount>,
+    pub float_total: Option<f64>,
+    pub float_lines: i64,
+}
+
 #[derive(Queryable, Selectable, Identifiable, Associations, PartialEq, Eq, Hash, Debug)]
 #[diesel(table_name = crate::schema::list_item)]
 #[diesel(belongs_to(ItemListDb, foreign_key = item_list_id))]
@@ -86,6 +162,8 @@ pub struct ListItemDb {
     pub name: String,
     pub modified: NaiveDateTime,
     pub source: String,
+    pub position: i32,
+    pub version: i64,
 }
 
 #[derive(Insertable)]
@@ -94,6 +172,17 @@ pub struct ListItemDbInsert<'a> {
     pub item_list_id: &'a i32,
     pub name: &'a String,
     pub source: &'a String,
+    pub position: i32,
+    pub created: NaiveDateTime,
+    pub modified: NaiveDateTime,
+}
+
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::list_item)]
+pub struct ListItemDbUpdate<'a> {
+    pub name: Option<&'a String>,
+    pub source: Option<&'a String>,
+    pub modified: NaiveDateTime,
 }
 
 #[derive(Queryable, Selectable, Identifiable, Associations, PartialEq, Debug)]
@@ -106,9 +195,27 @@ pub struct ListItemAttributeDb {
     pub attribute_type: String,
     pub bool_val: Option<bool>,
     pub timestamp_val: Option<NaiveDateTime>,
-    pub float_val: Option<f32>,
-    pub integer_val: Option<i32>,
+    pub float_val: Option<f64>,
+    pub integer_val: Option<i64>,
+    pub text_val: Option<String>,
+    pub price_amount: Option<DbAmount>,
+    pub price_source: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::list_item_attribute)]
+#[diesel(treat_none_as_default_value = false)]
+pub struct ListItemAttributeDbInsert<'a> {
+    pub list_item_id: i32,
+    pub name: &'a String,
+    pub attribute_type: String,
+    pub bool_val: Option<bool>,
+    pub timestamp_val: Option<NaiveDateTime>,
+    pub float_val: Option<f64>,
+    pub integer_val: Option<i64>,
     pub text_val: Option<String>,
+    pub price_amount: Option<DbAmount>,
+    pub price_source: Option<String>,
 }
 
 impl Eq for ListItemAttributeDb {}
@@ -0,0 +1,194 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+use crate::common::{ATTRIBUTE_QUANTITY, ListAttribute, ListItem, ListType};
+
+/// How a rollup combines the values of a list's items.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum RollupFunction {
+    Sum,
+    Min,
+    Max,
+    Avg,
+    /// The number of items with the field.  A Boolean attribute is only counted when true.
+    Count,
+    /// The number of different values the field takes.
+    CountDistinct,
+}
+
+/// The part of each item that a rollup reads.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum RollupField {
+    Attribute(String),
+    Name,
+    Source,
+}
+
+/// One rollup to compute over a list's items.  `Sum`, `Min`, `Max` and `Avg` read an Integer,
+/// Float or Price attribute, each item's value first being multiplied by its `multiply_by`
+/// Integer attribute, or by zero when the item does not have it.  Prices are rolled up separately
+/// for each currency.
+#[derive(Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct RollupDefinition {
+    pub name: String,
+    pub function: RollupFunction,
+    pub of: RollupField,
+    #[serde(default)]
+    pub multiply_by: Option<String>,
+}
+
+/// The rollups to compute for each list: those given for the list's id, else those given for its
+/// type, else the total of each Price attribute multiplied by the item's `quantity`.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct RollupSpecification {
+    #[serde(default)]
+    pub by_list: HashMap<u64, Vec<RollupDefinition>>,
+    #[serde(default)]
+    pub by_list_type: HashMap<ListType, Vec<RollupDefinition>>,
+}
+
+impl RollupSpecification {
+    /// Reads a specification written as JSON, such as
+    /// `{"by_list_type": {"Standard": [{"name": "weight", "function": "Sum", "of": {"Attribute": "weight"}}]}}`.
+    pub fn parse(json: &str) -> Result<RollupSpecification, String> {
+        let specification: RollupSpecification = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        specification.validate()?;
+        Ok(specification)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for definitions in self.by_list.values().chain(self.by_list_type.values()) {
+            let mut names = HashSet::new();
+            for definition in definitions {
+                if !names.insert(&definition.name) {
+                    return Err(format!("rollup '{}' is defined twice for the same lists", definition.name));
+                }
+                let numeric = matches!(
+                    definition.function,
+                    RollupFunction::Sum | RollupFunction::Min | RollupFunction::Max | RollupFunction::Avg
+                );
+                if numeric && !matches!(definition.of, RollupField::Attribute(_)) {
+                    return Err(format!("rollup '{}' must be of an attribute", definition.name));
+                }
+                if !numeric && definition.multiply_by.is_some() {
+                    return Err(format!("rollup '{}' counts items, so cannot multiply them", definition.name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The rollups to compute for the list with the given id, type and items.
+    pub fn definitions_for(&self, list_id: u64, list_type: &ListType, items: &[ListItem]) -> Vec<RollupDefinition> {
+        if let Some(definitions) = self.given_for(list_id, list_type) {
+            return definitions.clone();
+        }
+        let price_attributes: BTreeSet<&String> = items
+            .iter()
+            .flat_map(|item| &item.attributes)
+            .filter(|(_, attr)| matches!(attr, ListAttribute::Price(_)))
+            .map(|(name, _)| name)
+            .collect();
+        RollupSpecification::price_totals(price_attributes)
+    }
+
+    /// The rollups given for the list with the given id, else for its type, if any.
+    pub fn given_for(&self, list_id: u64, list_type: &ListType) -> Option<&Vec<RollupDefinition>> {
+        self.by_list.get(&list_id).or_else(|| self.by_list_type.get(list_type))
+    }
+
+    /// The rollups of a list with none given, for items with Price attributes of the given names.
+    pub fn price_totals<'a>(names: impl IntoIterator<Item = &'a String>) -> Vec<RollupDefinition> {
+        names
+            .into_iter()
+            .map(|name| RollupDefinition {
+                name: name.clone(),
+                function: RollupFunction::Sum,
+                of: RollupField::Attribute(name.clone()),
+                multiply_by: Some(ATTRIBUTE_QUANTITY.to_string()),
+            })
+            .collect()
+    }
+
+    /// Reads a specification from a JSON file, as written for `parse`.
+    pub fn from_file(path: &str) -> Result<RollupSpecification, String> {
+        let json = fs::read_to_string(path).map_err(|e| format!("could not be read: {}", e))?;
+        RollupSpecification::parse(&json).map_err(|e| format!("is not a valid rollup specification: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use rust_decimal::Decimal;
+
+    use crate::common::Price;
+
+    use super::*;
+
+    #[test]
+    fn test_definitions_for() {
+        let count = |name: &str| RollupDefinition {
+            name: name.to_string(),
+            function: RollupFunction::Count,
+            of: RollupField::Name,
+            multiply_by: None,
+        };
+        let specification = RollupSpecification {
+            by_list: HashMap::from([(1, vec![count("one")])]),
+            by_list_type: HashMap::from([(ListType::System, vec![count("system")])]),
+        };
+        let price = ListAttribute::Price(Price { amount: Decimal::from_str("1.00").unwrap(), source: "USD".to_string() });
+        let item = |name: &str| ListItem {
+            id: None,
+            attributes: HashMap::from([(name.to_string(), price.clone())]),
+            created: Default::default(),
+            modified: Default::default(),
+            name: "item".to_string(),
+            source: "source".to_string(),
+            version: 1,
+        };
+        let items = vec![item("cost"), item("tax"), item("cost")];
+
+        assert_eq!(vec![count("one")], specification.definitions_for(1, &ListType::System, &items));
+        assert_eq!(vec![count("system")], specification.definitions_for(2, &ListType::System, &items));
+        let defaults = specification.definitions_for(2, &ListType::Standard, &items);
+        assert_eq!(vec!["cost", "tax"], defaults.iter().map(|d| d.name.as_str()).collect::<Vec<_>>());
+        assert_eq!(RollupFunction::Sum, defaults[0].function);
+        assert_eq!(RollupField::Attribute("cost".to_string()), defaults[0].of);
+        assert_eq!(Some(ATTRIBUTE_QUANTITY.to_string()), defaults[0].multiply_by);
+    }
+
+    #[test]
+    fn test_parse() {
+        let specification = RollupSpecification::parse(r#"{
+            "by_list": {"7": [{"name": "sources", "function": "CountDistinct", "of": "Source"}]},
+            "by_list_type": {"Standard": [
+                {"name": "weight", "function": "Sum", "of": {"Attribute": "weight"}, "multiply_by": "quantity"}
+            ]}
+        }"#).unwrap();
+        assert_eq!(RollupField::Source, specification.by_list[&7][0].of);
+        assert_eq!(Some("quantity".to_string()), specification.by_list_type[&ListType::Standard][0].multiply_by);
+
+        for invalid in [
+            r#"{"by_list_type": {"Archived": []}}"#,
+            r#"{"by_list": {"1": [{"name": "n", "function": "Median", "of": "Name"}]}}"#,
+            r#"{"by_list": {"1": [{"name": "n", "function": "Sum", "of": "Name"}]}}"#,
+            r#"{"by_list": {"1": [{"name": "n", "function": "Count", "of": "Name", "multiply_by": "quantity"}]}}"#,
+            r#"{"by_list": {"1": [
+                {"name": "n", "function": "Count", "of": "Name"},
+                {"name": "n", "function": "Count", "of": "Source"}
+            ]}}"#,
+        ] {
+            assert!(RollupSpecification::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -1,13 +1,62 @@
+use actix_web::middleware::from_fn;
 use actix_web::web;
 
+use crate::auth::authenticate;
+use crate::common::ListManagementError;
+use crate::routes::admin::schema_version;
 use crate::routes::health_check::health_check;
+use crate::routes::items::find_items;
+use crate::routes::list_items::{add_item, delete_item, reorder_items, retrieve_items, update_item};
 use crate::routes::list_of_lists::list_of_lists;
+use crate::routes::lists::{create_list, delete_list, replace_list, retrieve_list, update_list};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
+    // Malformed bodies, query strings and paths are answered like any other invalid request.
+    cfg.app_data(web::JsonConfig::default().error_handler(|e, _| invalid_request(e)));
+    cfg.app_data(web::QueryConfig::default().error_handler(|e, _| invalid_request(e)));
+    cfg.app_data(web::PathConfig::default().error_handler(|e, _| invalid_request(e)));
     cfg.service(
         web::resource("/health_check").route(web::get().to(health_check))
     );
+    // Everything but the health check requires an authenticated caller.
     cfg.service(
-        web::resource("/list_of_lists").route(web::get().to(list_of_lists))
+        web::scope("")
+            .wrap(from_fn(authenticate))
+            .service(
+                web::resource("/admin/schema_version").route(web::get().to(schema_version))
+            )
+            .service(
+                web::resource("/items").route(web::get().to(find_items))
+            )
+            .service(
+                web::resource("/list_of_lists").route(web::get().to(list_of_lists))
+            )
+            .service(
+                web::resource("/lists").route(web::post().to(create_list))
+            )
+            .service(
+                web::resource("/lists/{id}")
+                    .route(web::get().to(retrieve_list))
+                    .route(web::put().to(replace_list))
+                    .route(web::patch().to(update_list))
+                    .route(web::delete().to(delete_list))
+            )
+            .service(
+                web::resource("/lists/{id}/items")
+                    .route(web::get().to(retrieve_items))
+                    .route(web::post().to(add_item))
+            )
+            .service(
+                web::resource("/lists/{id}/items/order").route(web::put().to(reorder_items))
+            )
+            .service(
+                web::resource("/lists/{id}/items/{item_id}")
+                    .route(web::patch().to(update_item))
+                    .route(web::delete().to(delete_item))
+            )
     );
-}
\ No newline at end of file
+}
+
+fn invalid_request(e: impl ToString) -> actix_web::Error {
+    ListManagementError::Validation(e.to_string()).into()
+}
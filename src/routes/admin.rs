@@ -0,0 +1,20 @@
+use actix_web::HttpResponse;
+
+use crate::common::{LMContext, ListManagementError};
+use crate::db;
+use crate::routes::Context;
+
+/// Not found when the admin endpoints are turned off, and forbidden to users who are not among
+/// the configured `auth.admins`.
+pub async fn schema_version(context: Context) -> Result<HttpResponse, ListManagementError> {
+    if !context.config().features.admin {
+        return Err(ListManagementError::NotFound("/admin/schema_version".to_string()));
+    }
+    Ok(HttpResponse::Ok().json(db::blocking(move || {
+        let user = context.current_user()?;
+        if !context.config().auth.is_admin(&user) {
+            return Err(ListManagementError::Forbidden("only administrators may see the schema version".to_string()));
+        }
+        db::schema_version(context.pool())
+    }).await?))
+}
@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+use actix_web::{HttpResponse, web};
+
+use crate::common::{ItemSortKey, ItemSortRequest, ListManagementError, PagingRequest};
+use crate::config::PagingConfig;
+use crate::db::blocking;
+use crate::list_item_service;
+use crate::list_item_service::ItemSelector;
+use crate::list_of_lists_service::ListSelector;
+use crate::routes::{Context, invalid, parse_attribute_filter, parse_bool, parse_rows, parse_u64};
+use crate::routes::list_of_lists::parse_selector_param;
+
+pub async fn find_items(context: Context, query: web::Query<Vec<(String, String)>>) -> Result<HttpResponse, ListManagementError> {
+    let params = FindItemsParams::parse(&query, &context.config().paging).map_err(ListManagementError::Validation)?;
+    let found = blocking(move || list_item_service::find_items(&context, params.selector, params.paging, params.sort)).await?;
+    Ok(HttpResponse::Ok().json(found))
+}
+
+/// The arguments to `list_item_service::find_items`, as read from the query string.
+///
+/// Items are selected by `name_keywords`, `source` and `attribute`, and their lists by the same
+/// parameters as `/list_of_lists` uses to select lists, except that lists which are deleted are
+/// left out unless `show_deleted=true`, and that lists' attributes are filtered by
+/// `list_attribute`.  `source`, `attribute`, `list_attribute` and the multi-valued list
+/// parameters are given by repeating the parameter.  Every other parameter may appear at most
+/// once, and `start` may not be combined with `cursor`.
+struct FindItemsParams {
+    selector: ItemSelector,
+    paging: PagingRequest,
+    sort: ItemSortRequest,
+}
+
+impl FindItemsParams {
+    fn parse(query: &[(String, String)], paging: &PagingConfig) -> Result<FindItemsParams, String> {
+        let mut params = FindItemsParams {
+            selector: ItemSelector {
+                limit_lists: ListSelector {
+                    limit_show_read_only: false,
+                    limit_list_types: vec![],
+                    limit_list_access: vec![],
+                    limit_show_deleted: false,
+                    limit_show_not_deleted: true,
+                    limit_in_folders: vec![],
+                    limit_name_keywords: None,
+                    limit_list_ids: vec![],
+                    limit_search: None,
+                    limit_attributes: vec![],
+                },
+                limit_name_keywords: None,
+                limit_sources: vec![],
+                limit_attributes: vec![],
+            },
+            paging: PagingRequest {
+                start: 0,
+                rows: paging.default_rows,
+                cursor: None,
+            },
+            sort: ItemSortRequest {
+                descending: false,
+                key: ItemSortKey::Id,
+            },
+        };
+        let mut seen: Vec<&str> = Vec::new();
+
+        for (name, value) in query {
+            let name = name.as_str();
+            let value = value.as_str();
+            let repeatable = matches!(
+                name,
+                "source" | "attribute" | "list_attribute" | "list_type" | "list_access" | "folder" | "list_id"
+            );
+            if !repeatable {
+                if seen.contains(&name) {
+                    return Err(format!("Query parameter '{}' may only be specified once.", name));
+                }
+                seen.push(name);
+            }
+            if parse_selector_param(&mut params.selector.limit_lists, name, value)? {
+                continue;
+            }
+            match name {
+                "name_keywords" => {
+                    if value.trim().is_empty() {
+                        return Err(invalid(name, value, "at least one keyword"));
+                    }
+                    params.selector.limit_name_keywords = Some(value.to_string());
+                }
+                "source" => {
+                    if value.is_empty() {
+                        return Err(invalid(name, value, "a non-empty source"));
+                    }
+                    params.selector.limit_sources.push(value.to_string());
+                }
+                "attribute" => params.selector.limit_attributes.push(parse_attribute_filter(name, value)?),
+                "list_attribute" => params.selector.limit_lists.limit_attributes.push(parse_attribute_filter(name, value)?),
+                "start" => params.paging.start = parse_u64(name, value)?,
+                "rows" => params.paging.rows = parse_rows(name, value, paging)?,
+                "cursor" => {
+                    if value.is_empty() {
+                        return Err(invalid(name, value, "the next_cursor of a previous page"));
+                    }
+                    params.paging.cursor = Some(value.to_string());
+                }
+                "sort_key" => {
+                    params.sort.key = ItemSortKey::from_str(value)
+                        .map_err(|_| invalid(name, value, "CreatedDate, Id, ModifiedDate, Name or Source"))?;
+                }
+                "descending" => params.sort.descending = parse_bool(name, value)?,
+                _ => return Err(format!("Unknown query parameter '{}'.", name)),
+            }
+        }
+
+        if params.paging.cursor.is_some() && seen.contains(&"start") {
+            return Err("Query parameters 'start' and 'cursor' may not be combined.".to_string());
+        }
+        Ok(params)
+    }
+}
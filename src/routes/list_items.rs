@@ -0,0 +1,64 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+use serde::Deserialize;
+
+use crate::common::{ListItemUpdate, ListManagementError, PagingRequest};
+use crate::db::blocking;
+use crate::list_item_service;
+use crate::routes::{Context, etag, if_match};
+
+#[derive(Deserialize)]
+pub struct ItemOrder {
+    item_ids: Vec<u64>,
+}
+
+/// Paging for a list's items.  `start` may not be combined with `cursor`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemPaging {
+    cursor: Option<String>,
+    rows: Option<u64>,
+    start: Option<u64>,
+}
+
+pub async fn retrieve_items(context: Context, list_id: web::Path<u64>, paging: web::Query<ItemPaging>) -> Result<HttpResponse, ListManagementError> {
+    let paging = paging.into_inner();
+    if paging.cursor.is_some() && paging.start.is_some() {
+        return Err(ListManagementError::Validation("query parameters 'start' and 'cursor' may not be combined".to_string()));
+    }
+    let limits = &context.config().paging;
+    if paging.rows.is_some_and(|rows| rows > limits.max_rows) {
+        return Err(ListManagementError::Validation(format!("query parameter 'rows' may be at most {}", limits.max_rows)));
+    }
+    let paging = PagingRequest {
+        start: paging.start.unwrap_or(0),
+        rows: paging.rows.unwrap_or(limits.default_item_rows),
+        cursor: paging.cursor,
+    };
+    let page = list_item_service::retrieve_item_page(&context, list_id.into_inner(), paging).await?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+pub async fn add_item(context: Context, list_id: web::Path<u64>, item: web::Json<ListItemUpdate>) -> Result<HttpResponse, ListManagementError> {
+    let item = blocking(move || list_item_service::add_item(&context, list_id.into_inner(), item.into_inner())).await?;
+    Ok(HttpResponse::Created().insert_header(etag(item.version)).json(item))
+}
+
+pub async fn update_item(context: Context, req: HttpRequest, ids: web::Path<(u64, u64)>, changes: web::Json<ListItemUpdate>) -> Result<HttpResponse, ListManagementError> {
+    let (list_id, id) = ids.into_inner();
+    let version = if_match(&req)?;
+    let item = blocking(move || list_item_service::update_item(&context, list_id, id, version, changes.into_inner())).await?;
+    Ok(HttpResponse::Ok().insert_header(etag(item.version)).json(item))
+}
+
+pub async fn delete_item(context: Context, req: HttpRequest, ids: web::Path<(u64, u64)>) -> Result<HttpResponse, ListManagementError> {
+    let (list_id, id) = ids.into_inner();
+    let version = if_match(&req)?;
+    blocking(move || list_item_service::delete_item(&context, list_id, id, version)).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+pub async fn reorder_items(context: Context, req: HttpRequest, list_id: web::Path<u64>, order: web::Json<ItemOrder>) -> Result<HttpResponse, ListManagementError> {
+    let version = if_match(&req)?;
+    let items = blocking(move || list_item_service::reorder_items(&context, list_id.into_inner(), version, order.into_inner().item_ids)).await?;
+    Ok(HttpResponse::Ok().json(items))
+}
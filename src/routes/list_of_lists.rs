@@ -1,54 +1,184 @@
+use std::str::FromStr;
 
-use actix_web::{HttpRequest, HttpResponse};
+use actix_web::{HttpResponse, web};
 
-use crate::common::{LMContext, PagingRequest, SortKey, SortRequest, User, UserState};
-use crate::list_of_lists_service::{ListSelector, ListProvider};
+use crate::common::{LMContext, ListAccess, ListManagementError, ListType, PagingRequest, RollupRequest, SortKey, SortRequest};
+use crate::config::AppConfig;
+use crate::list_of_lists_service::ListSelector;
+use crate::routes::{Context, invalid, parse_attribute_filter, parse_bool, parse_rows, parse_u64};
+use crate::search::SearchQuery;
 
-pub async fn list_of_lists(req: HttpRequest) -> HttpResponse {
-    let user_id: u64 = req.headers().get("user_id").unwrap()
-        .to_str().unwrap().parse().unwrap();
+pub async fn list_of_lists(context: Context, query: web::Query<Vec<(String, String)>>) -> Result<HttpResponse, ListManagementError> {
+    let params = ListOfListsParams::parse(&query, context.config()).map_err(ListManagementError::Validation)?;
 
-    let context = Context {
-        user_state: UserState { active_user_accounts: vec![], user_id },
-    };
+    let a = context.list_provider().retrieve_lists(
+        &context,
+        params.selector,
+        params.paging,
+        params.sort,
+        params.return_attributes,
+        params.rollups,
+    ).await?;
+    Ok(HttpResponse::Ok().json(a))
+}
 
-    let selector = ListSelector {
-        limit_show_read_only: false,
-        limit_list_types: vec![],
-        limit_list_access: vec![],
-        limit_show_deleted: true,
-        limit_show_not_deleted: true,
-        limit_in_folders: vec![],
-        limit_name_keywords: None,
-        limit_list_ids: vec![],
-    };
+/// The arguments to `ListProvider::retrieve_lists`, as read from the query string.
+///
+/// Multi-valued selector fields (`list_type`, `list_access`, `folder`, `list_id`, `attribute`)
+/// are given by repeating the parameter, a list having to pass every `attribute` filter.  Every
+/// other parameter may appear at most once, `start` may not be combined with `cursor`, and
+/// `rollup_currency` may not be combined with `return_rollups=false`.
+struct ListOfListsParams {
+    selector: ListSelector,
+    paging: PagingRequest,
+    sort: SortRequest,
+    return_attributes: bool,
+    rollups: Option<RollupRequest>,
+}
 
-    let paging = PagingRequest {
-        start: 0,
-        rows: 10,
-    };
+impl ListOfListsParams {
+    fn parse(query: &[(String, String)], config: &AppConfig) -> Result<ListOfListsParams, String> {
+        let mut params = ListOfListsParams {
+            selector: ListSelector {
+                limit_show_read_only: false,
+                limit_list_types: vec![],
+                limit_list_access: vec![],
+                limit_show_deleted: true,
+                limit_show_not_deleted: true,
+                limit_in_folders: vec![],
+                limit_name_keywords: None,
+                limit_list_ids: vec![],
+                limit_search: None,
+                limit_attributes: vec![],
+            },
+            paging: PagingRequest {
+                start: 0,
+                rows: config.paging.default_rows,
+                cursor: None,
+            },
+            sort: SortRequest {
+                descending: false,
+                key: SortKey::Id,
+            },
+            return_attributes: true,
+            rollups: None,
+        };
+        let mut seen: Vec<&str> = Vec::new();
+        let mut sort_key: Option<&str> = None;
+        let mut sort_attribute: Option<&str> = None;
+        let mut return_rollups = true;
+        let mut rollup_currency: Option<String> = None;
 
-    let sort = SortRequest {
-        descending: false,
-        key: SortKey::Id,
-    };
+        for (name, value) in query {
+            let name = name.as_str();
+            let value = value.as_str();
+            let repeatable = matches!(name, "attribute" | "list_type" | "list_access" | "folder" | "list_id");
+            if !repeatable {
+                if seen.contains(&name) {
+                    return Err(format!("Query parameter '{}' may only be specified once.", name));
+                }
+                seen.push(name);
+            }
+            if parse_selector_param(&mut params.selector, name, value)? {
+                continue;
+            }
+            match name {
+                "name_keywords" => {
+                    if value.trim().is_empty() {
+                        return Err(invalid(name, value, "at least one keyword"));
+                    }
+                    params.selector.limit_name_keywords = Some(value.to_string());
+                }
+                "search" if !config.features.search => {
+                    return Err("Query parameter 'search' is not available: full-text search is turned off.".to_string());
+                }
+                "search" => params.selector.limit_search = Some(
+                    SearchQuery::from_str(value)
+                        .map_err(|e| format!("Invalid value '{}' for query parameter '{}': {}.", value, name, e))?,
+                ),
+                "attribute" => params.selector.limit_attributes.push(parse_attribute_filter(name, value)?),
+                "start" => params.paging.start = parse_u64(name, value)?,
+                "rows" => params.paging.rows = parse_rows(name, value, &config.paging)?,
+                "cursor" => {
+                    if value.is_empty() {
+                        return Err(invalid(name, value, "the next_cursor of a previous page"));
+                    }
+                    params.paging.cursor = Some(value.to_string());
+                }
+                "sort_key" => sort_key = Some(value),
+                "sort_attribute" => sort_attribute = Some(value),
+                "descending" => params.sort.descending = parse_bool(name, value)?,
+                "return_attributes" => params.return_attributes = parse_bool(name, value)?,
+                "return_rollups" => return_rollups = parse_bool(name, value)?,
+                "rollup_currency" => {
+                    if value.is_empty() {
+                        return Err(invalid(name, value, "a non-empty currency"));
+                    }
+                    rollup_currency = Some(value.to_string());
+                }
+                _ => return Err(format!("Unknown query parameter '{}'.", name)),
+            }
+        }
 
-    let a = context.list_provider().retrieve_lists(&context, selector, paging, sort, true, true);
+        if params.paging.cursor.is_some() && seen.contains(&"start") {
+            return Err("Query parameters 'start' and 'cursor' may not be combined.".to_string());
+        }
 
-    HttpResponse::Ok().body(serde_json::to_string(&a).unwrap())
-}
+        params.rollups = match (return_rollups, rollup_currency) {
+            (true, currency) => Some(RollupRequest { currency }),
+            (false, None) => None,
+            (false, Some(_)) => {
+                return Err("Query parameter 'rollup_currency' is not allowed when 'return_rollups' is false.".to_string());
+            }
+        };
 
-struct Context {
-    user_state: UserState,
-}
-
-impl LMContext for Context {
-   fn current_user(&self) -> User {
-        let state = self.current_user_state();
-        crate::user_storage::retrieve_user_by_id(&state.user_id).unwrap()
+        params.sort.key = match (sort_key, sort_attribute) {
+            (Some("Attribute"), Some(attribute)) if !attribute.is_empty() => SortKey::Attribute(attribute.to_string()),
+            (Some("Attribute"), _) => {
+                return Err("Query parameter 'sort_attribute' is required when 'sort_key' is 'Attribute'.".to_string());
+            }
+            (_, Some(_)) => {
+                return Err("Query parameter 'sort_attribute' is only allowed when 'sort_key' is 'Attribute'.".to_string());
+            }
+            (Some("CreatedDate"), None) => SortKey::CreatedDate,
+            (Some("Id"), None) | (None, None) => SortKey::Id,
+            (Some("ModifiedDate"), None) => SortKey::ModifiedDate,
+            (Some("Name"), None) => SortKey::Name,
+            (Some("Relevance"), None) if params.selector.limit_search.is_some() => SortKey::Relevance,
+            (Some("Relevance"), None) => {
+                return Err("Query parameter 'search' is required when 'sort_key' is 'Relevance'.".to_string());
+            }
+            (Some(key), None) => {
+                return Err(invalid("sort_key", key, "Attribute, CreatedDate, Id, ModifiedDate, Name or Relevance"));
+            }
+        };
+        Ok(params)
     }
+}
 
-    fn current_user_state(&self) -> UserState {
-        self.user_state.clone()
+/// Reads the query parameters that select lists by their type, access, folder, id and state,
+/// returning false for any other parameter.
+pub(super) fn parse_selector_param(selector: &mut ListSelector, name: &str, value: &str) -> Result<bool, String> {
+    match name {
+        "show_read_only" => selector.limit_show_read_only = parse_bool(name, value)?,
+        "show_deleted" => selector.limit_show_deleted = parse_bool(name, value)?,
+        "show_not_deleted" => selector.limit_show_not_deleted = parse_bool(name, value)?,
+        "list_type" => selector.limit_list_types.push(
+            ListType::from_str(value)
+                .map_err(|_| invalid(name, value, "Standard, System or Transient"))?,
+        ),
+        "list_access" => selector.limit_list_access.push(
+            ListAccess::from_str(value)
+                .map_err(|_| invalid(name, value, "Private, Public or Shared"))?,
+        ),
+        "folder" => {
+            if value.is_empty() {
+                return Err(invalid(name, value, "a non-empty folder name"));
+            }
+            selector.limit_in_folders.push(value.to_string());
+        }
+        "list_id" => selector.limit_list_ids.push(parse_u64(name, value)?),
+        _ => return Ok(false),
     }
-}
\ No newline at end of file
+    Ok(true)
+}
@@ -0,0 +1,34 @@
+use actix_web::{HttpRequest, HttpResponse, web};
+
+use crate::common::{ItemListUpdate, ListManagementError};
+use crate::db::blocking;
+use crate::list_service;
+use crate::routes::{Context, etag, if_match};
+
+pub async fn create_list(context: Context, list: web::Json<ItemListUpdate>) -> Result<HttpResponse, ListManagementError> {
+    let item_list = list_service::create_list(&context, list.into_inner()).await?;
+    Ok(HttpResponse::Created().insert_header(etag(item_list.version)).json(item_list))
+}
+
+pub async fn retrieve_list(context: Context, id: web::Path<u64>) -> Result<HttpResponse, ListManagementError> {
+    let item_list = list_service::retrieve_list(&context, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().insert_header(etag(item_list.version)).json(item_list))
+}
+
+pub async fn replace_list(context: Context, req: HttpRequest, id: web::Path<u64>, list: web::Json<ItemListUpdate>) -> Result<HttpResponse, ListManagementError> {
+    let version = if_match(&req)?;
+    let item_list = list_service::replace_list(&context, id.into_inner(), version, list.into_inner()).await?;
+    Ok(HttpResponse::Ok().insert_header(etag(item_list.version)).json(item_list))
+}
+
+pub async fn update_list(context: Context, req: HttpRequest, id: web::Path<u64>, changes: web::Json<ItemListUpdate>) -> Result<HttpResponse, ListManagementError> {
+    let version = if_match(&req)?;
+    let item_list = list_service::update_list(&context, id.into_inner(), version, changes.into_inner()).await?;
+    Ok(HttpResponse::Ok().insert_header(etag(item_list.version)).json(item_list))
+}
+
+pub async fn delete_list(context: Context, req: HttpRequest, id: web::Path<u64>) -> Result<HttpResponse, ListManagementError> {
+    let version = if_match(&req)?;
+    blocking(move || list_service::delete_list(&context, id.into_inner(), version)).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,2 +1,250 @@
+use std::future::{ready, Ready};
+use std::str::FromStr;
+
+use actix_web::{FromRequest, HttpMessage, HttpRequest, HttpResponse, ResponseError, web};
+use actix_web::dev::Payload;
+use actix_web::http::header::{ETag, EntityTag, Header, IF_MATCH, IfMatch, RETRY_AFTER, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
+use tracing::error;
+
+use crate::app_state::{AppState, DEFAULT_STATE};
+use crate::clock::Clock;
+use crate::config::{AppConfig, PagingConfig};
+use crate::common::{AttributeCondition, AttributeFilter, AttributePredicate, Comparison, ErrorResponse, LMContext, ListAttribute, ListManagementError, Price, User, UserState};
+use crate::currency::CurrencyConverter;
+use crate::db::DbPool;
+use crate::list_of_lists_service::ListProvider;
+use crate::rollup::RollupSpecification;
+
+pub mod admin;
 pub mod health_check;
-pub mod list_of_lists;
\ No newline at end of file
+pub mod items;
+pub mod list_items;
+pub mod list_of_lists;
+pub mod lists;
+
+/// The authenticated caller, as stored in the request by `auth::authenticate`, and the
+/// application's state.
+pub(crate) struct Context {
+    user_state: UserState,
+    state: web::Data<AppState>,
+}
+
+impl Context {
+    pub(crate) fn config(&self) -> &AppConfig {
+        &self.state.config
+    }
+}
+
+impl LMContext for Context {
+   fn current_user(&self) -> Result<User, ListManagementError> {
+        let state = self.current_user_state();
+        self.state.user_service.retrieve_user_by_id(state.user_id)?
+            .ok_or_else(|| ListManagementError::Unauthorized("the user no longer exists".to_string()))
+    }
+
+    fn current_user_state(&self) -> UserState {
+        self.user_state.clone()
+    }
+
+    fn list_provider(&self) -> &dyn ListProvider {
+        self.state.list_provider.as_ref()
+    }
+
+    fn pool(&self) -> &DbPool {
+        &self.state.pool
+    }
+
+    fn clock(&self) -> &dyn Clock {
+        self.state.clock.as_ref()
+    }
+
+    fn rollup_specification(&self) -> &RollupSpecification {
+        self.state.rollup_specification.as_ref()
+    }
+
+    fn currency_converter(&self) -> Option<&dyn CurrencyConverter> {
+        self.state.currency_converter.as_deref()
+    }
+}
+
+impl FromRequest for Context {
+    type Error = ListManagementError;
+    type Future = Ready<Result<Context, ListManagementError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(match req.extensions().get::<UserState>() {
+            Some(user_state) => Ok(Context {
+                user_state: user_state.clone(),
+                state: req.app_data::<web::Data<AppState>>().cloned().unwrap_or_else(|| DEFAULT_STATE.clone()),
+            }),
+            None => Err(ListManagementError::Unauthorized("the request was not authenticated".to_string())),
+        })
+    }
+}
+
+impl ResponseError for ListManagementError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ListManagementError::Conflict(_) => StatusCode::CONFLICT,
+            ListManagementError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ListManagementError::NotFound(_) => StatusCode::NOT_FOUND,
+            ListManagementError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ListManagementError::PreconditionRequired(_) => StatusCode::PRECONDITION_REQUIRED,
+            ListManagementError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ListManagementError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ListManagementError::Validation(_) => StatusCode::BAD_REQUEST,
+            ListManagementError::Database(_) | ListManagementError::Migration(_) | ListManagementError::Other => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Server errors are logged with their cause, which is not passed on to the client.
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        if status.is_server_error() {
+            error!("{:?}", self);
+        }
+        let mut response = HttpResponse::build(status);
+        if status == StatusCode::UNAUTHORIZED {
+            response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+        }
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            response.insert_header((RETRY_AFTER, "1"));
+        }
+        let error: &'static str = self.into();
+        response.json(ErrorResponse {
+            error: error.to_string(),
+            message: self.to_string(),
+        })
+    }
+}
+
+/// The `ETag` of a list or item at the given version.
+fn etag(version: u64) -> ETag {
+    ETag(EntityTag::new_strong(version.to_string()))
+}
+
+/// Reads the version a change is based on from the `If-Match` header, which must be given and
+/// hold the `ETag` the list or item was read with, or `*` to change it whatever its version.
+fn if_match(req: &HttpRequest) -> Result<Option<u64>, ListManagementError> {
+    if !req.headers().contains_key(IF_MATCH) {
+        return Err(ListManagementError::PreconditionRequired(
+            "the If-Match header must give the ETag of the version being changed".to_string(),
+        ));
+    }
+    let tags = match IfMatch::parse(req) {
+        Ok(IfMatch::Any) => return Ok(None),
+        Ok(IfMatch::Items(tags)) if tags.len() == 1 => tags,
+        _ => return Err(ListManagementError::Validation("the If-Match header must give a single ETag or *".to_string())),
+    };
+    // Only a strong tag written by `etag` can match.
+    let tag = &tags[0];
+    tag.tag().parse::<u64>().ok().filter(|_| !tag.weak).map(Some).ok_or_else(|| {
+        ListManagementError::PreconditionFailed(format!("the ETag {} is not a version", tag))
+    })
+}
+
+fn parse_bool(name: &str, value: &str) -> Result<bool, String> {
+    bool::from_str(value).map_err(|_| invalid(name, value, "true or false"))
+}
+
+fn parse_u64(name: &str, value: &str) -> Result<u64, String> {
+    u64::from_str(value).map_err(|_| invalid(name, value, "a non-negative integer"))
+}
+
+/// Reads the rows in a page, which may be no more than `paging.max_rows`.
+fn parse_rows(name: &str, value: &str, paging: &PagingConfig) -> Result<u64, String> {
+    let rows = parse_u64(name, value)?;
+    if rows > paging.max_rows {
+        return Err(invalid(name, value, &format!("at most {}", paging.max_rows)));
+    }
+    Ok(rows)
+}
+
+/// Reads an attribute filter written as one or more predicates separated by `|`, any one of which
+/// must hold.  A predicate is written as `NAME` or `NAME:Exists` for the attribute to be there,
+/// `NAME:Missing` for it not to be, `NAME:Contains:TEXT` for Text containing `TEXT` without regard
+/// to case, or `NAME:COMPARISON:VALUE` with the comparison one of `Eq`, `Ne`, `Lt`, `Le`, `Gt` and
+/// `Ge`.  The value is a Boolean if `true` or `false`, an Integer or Float if it is a number, a
+/// Price if a number and a currency such as `9.99 USD`, a DateTime if written like
+/// `2024-05-01T09:30:00`, and Text otherwise.  Text in double quotes is always Text, and may hold
+/// a `|`.
+fn parse_attribute_filter(name: &str, value: &str) -> Result<AttributeFilter, String> {
+    let mut alternatives = Vec::new();
+    let (mut quoted, mut start) = (false, 0);
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '|' if !quoted => {
+                alternatives.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&value[start..]);
+    let mut predicates: Vec<AttributeFilter> = alternatives
+        .into_iter()
+        .map(|predicate| parse_attribute_predicate(predicate).map(AttributeFilter::Predicate))
+        .collect::<Option<_>>()
+        .ok_or_else(|| invalid(
+            name,
+            value,
+            "NAME, NAME:Exists, NAME:Missing, NAME:Contains:TEXT or NAME:COMPARISON:VALUE, \
+             with COMPARISON one of Eq, Ne, Lt, Le, Gt or Ge, separated by |",
+        ))?;
+    Ok(match predicates.len() {
+        1 => predicates.remove(0),
+        _ => AttributeFilter::Or(predicates),
+    })
+}
+
+fn parse_attribute_predicate(predicate: &str) -> Option<AttributePredicate> {
+    let mut parts = predicate.splitn(3, ':');
+    let name = parts.next().filter(|name| !name.is_empty())?;
+    let condition = match (parts.next(), parts.next()) {
+        (None | Some("Exists"), None) => AttributeCondition::Exists,
+        (Some("Missing"), None) => AttributeCondition::Missing,
+        (Some("Contains"), Some(text)) => AttributeCondition::Contains(unquote(text).unwrap_or(text).to_string()),
+        (Some(comparison), Some(operand)) => {
+            AttributeCondition::Compare(Comparison::from_str(comparison).ok()?, parse_attribute_value(operand))
+        }
+        _ => return None,
+    };
+    Some(AttributePredicate { name: name.to_string(), condition })
+}
+
+fn parse_attribute_value(value: &str) -> ListAttribute {
+    if let Some(text) = unquote(value) {
+        return ListAttribute::Text(text.to_string());
+    }
+    let price = value.split_once(' ').and_then(|(amount, currency)| {
+        let currency_like = !currency.is_empty() && currency.chars().all(|c| c.is_ascii_uppercase());
+        Some(Price { amount: Decimal::from_str(amount).ok()?, source: currency.to_string() }).filter(|_| currency_like)
+    });
+    if let Ok(b) = bool::from_str(value) {
+        ListAttribute::Boolean(b)
+    } else if let Ok(i) = i64::from_str(value) {
+        ListAttribute::Integer(i)
+    } else if let Some(f) = f64::from_str(value).ok().filter(|f| f.is_finite()) {
+        ListAttribute::Float(f)
+    } else if let Some(price) = price {
+        ListAttribute::Price(price)
+    } else if let Ok(d) = NaiveDateTime::from_str(value) {
+        ListAttribute::DateTime(d)
+    } else {
+        ListAttribute::Text(value.to_string())
+    }
+}
+
+fn unquote(value: &str) -> Option<&str> {
+    value.strip_prefix('"')?.strip_suffix('"')
+}
+
+fn invalid(name: &str, value: &str, expected: &str) -> String {
+    format!("Invalid value '{}' for query parameter '{}': expected {}.", value, name, expected)
+}
@@ -1,3 +1,13 @@
+pub mod sql_types {
+    /// An exact decimal amount, held as NUMERIC on Postgres and on SQLite as its text in a column
+    /// with the `db::AMOUNT_COLLATION` collation, so that it keeps its scale and compares exactly
+    /// on both.
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(oid = 1700, array_oid = 1231))]
+    #[diesel(sqlite_type(name = "Text"))]
+    pub struct Amount;
+}
+
 diesel::table! {
     account (id) {
         id -> Integer,
@@ -25,6 +35,8 @@ diesel::table! {
         list_type -> Text,
         name -> Text,
         modified -> Timestamp,
+        rollups_stale -> Bool,
+        version -> BigInt,
     }
 }
 
@@ -36,6 +48,9 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Amount;
+
     item_list_attribute (id) {
         id -> Integer,
         item_list_id -> Integer,
@@ -43,9 +58,34 @@ diesel::table! {
         #[sql_name = "type"] attribute_type -> Text,
         bool_val -> Nullable<Bool>,
         timestamp_val -> Nullable<Timestamp>,
-        float_val -> Nullable<Float>,
-        integer_val -> Nullable<Integer>,
+        float_val -> Nullable<Double>,
+        integer_val -> Nullable<BigInt>,
         text_val -> Nullable<Text>,
+        price_amount -> Nullable<Amount>,
+        price_source -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Amount;
+
+    item_list_rollup (id) {
+        id -> Integer,
+        item_list_id -> Integer,
+        name -> Text,
+        function -> Text,
+        #[sql_name = "type"] value_type -> Text,
+        float_val -> Nullable<Double>,
+        integer_val -> Nullable<BigInt>,
+        price_amount -> Nullable<Amount>,
+        price_source -> Nullable<Text>,
+        total_lines -> BigInt,
+        total_units -> BigInt,
+        currency -> Nullable<Text>,
+        exact_total -> Nullable<Amount>,
+        float_total -> Nullable<Double>,
+        float_lines -> BigInt,
     }
 }
 
@@ -57,10 +97,15 @@ diesel::table! {
         name -> Text,
         modified -> Timestamp,
         source -> Text,
+        position -> Integer,
+        version -> BigInt,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Amount;
+
     list_item_attribute (id) {
         id -> Integer,
         list_item_id -> Integer,
@@ -68,9 +113,11 @@ diesel::table! {
         #[sql_name = "type"] attribute_type -> Text,
         bool_val -> Nullable<Bool>,
         timestamp_val -> Nullable<Timestamp>,
-        float_val -> Nullable<Float>,
-        integer_val -> Nullable<Integer>,
+        float_val -> Nullable<Double>,
+        integer_val -> Nullable<BigInt>,
         text_val -> Nullable<Text>,
+        price_amount -> Nullable<Amount>,
+        price_source -> Nullable<Text>,
     }
 }
 
@@ -97,6 +144,7 @@ diesel::joinable!(item_list -> user (owner_user_id));
 diesel::joinable!(item_list_account -> item_list (item_list_id));
 diesel::joinable!(item_list_account -> account (account_id));
 diesel::joinable!(item_list_attribute -> item_list (item_list_id));
+diesel::joinable!(item_list_rollup -> item_list (item_list_id));
 diesel::joinable!(list_item -> item_list (item_list_id));
 diesel::joinable!(list_item_attribute -> list_item (list_item_id));
 
@@ -106,6 +154,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     item_list,
     item_list_account,
     item_list_attribute,
+    item_list_rollup,
     list_item,
     list_item_attribute,
     user,
@@ -0,0 +1,255 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A full-text search over lists' names, their items' names and the text of their Text
+/// attributes.  Queries are written as in SQLite FTS5: words, `"quoted phrases"`, a trailing `*`
+/// for a prefix, `AND` (or just a space), `OR`, binary `NOT` and parentheses, with `NOT` binding
+/// tightest and `OR` loosest.  Words are runs of letters and digits, matched without regard to
+/// case; anything else only separates them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchQuery {
+    expr: SearchExpr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum SearchExpr {
+    /// One or more words that must appear together, the last only as a prefix if `prefix` is set.
+    Phrase { words: Vec<String>, prefix: bool },
+    And(Box<SearchExpr>, Box<SearchExpr>),
+    Or(Box<SearchExpr>, Box<SearchExpr>),
+    /// Matches the first but not the second.
+    Not(Box<SearchExpr>, Box<SearchExpr>),
+}
+
+impl SearchQuery {
+    /// The query as an FTS5 `MATCH` expression.
+    pub(crate) fn to_fts5(&self) -> String {
+        self.expr.to_fts5()
+    }
+
+    /// The query as Postgres `to_tsquery` text.
+    #[cfg_attr(not(feature = "postgres"), allow(dead_code))]
+    pub(crate) fn to_tsquery(&self) -> String {
+        self.expr.to_tsquery()
+    }
+}
+
+impl SearchExpr {
+    fn to_fts5(&self) -> String {
+        match self {
+            SearchExpr::Phrase { words, prefix } => {
+                format!("\"{}\"{}", words.join(" "), if *prefix { " *" } else { "" })
+            }
+            SearchExpr::And(a, b) => format!("({} AND {})", a.to_fts5(), b.to_fts5()),
+            SearchExpr::Or(a, b) => format!("({} OR {})", a.to_fts5(), b.to_fts5()),
+            SearchExpr::Not(a, b) => format!("({} NOT {})", a.to_fts5(), b.to_fts5()),
+        }
+    }
+
+    fn to_tsquery(&self) -> String {
+        match self {
+            SearchExpr::Phrase { words, prefix } => {
+                let last = words.len() - 1;
+                let lexemes: Vec<String> = words
+                    .iter()
+                    .enumerate()
+                    .map(|(i, w)| format!("'{}'{}", w, if *prefix && i == last { ":*" } else { "" }))
+                    .collect();
+                format!("({})", lexemes.join(" <-> "))
+            }
+            SearchExpr::And(a, b) => format!("({} & {})", a.to_tsquery(), b.to_tsquery()),
+            SearchExpr::Or(a, b) => format!("({} | {})", a.to_tsquery(), b.to_tsquery()),
+            SearchExpr::Not(a, b) => format!("({} & !{})", a.to_tsquery(), b.to_tsquery()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Phrase { words: Vec<String>, prefix: bool },
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::And => write!(f, "AND"),
+            Token::Or => write!(f, "OR"),
+            Token::Not => write!(f, "NOT"),
+            Token::Phrase { words, .. } => write!(f, "'{}'", words.join(" ")),
+        }
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = tokenize(s)?;
+        if tokens.is_empty() {
+            return Err("the search has no words".to_string());
+        }
+        let mut parser = Parser { tokens, next: 0 };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(SearchQuery { expr }),
+            Some(token) => Err(format!("unexpected {} in the search", token)),
+        }
+    }
+}
+
+fn words(s: &str) -> Vec<String> {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Splits the query into operators and phrases.  An unquoted term is a phrase of the words in
+/// it, and one with no words at all is dropped.
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '"' => {
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err("a quoted phrase in the search is not closed".to_string()),
+                    }
+                }
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                let prefix = chars.next_if_eq(&'*').is_some();
+                let words = words(&phrase);
+                if !words.is_empty() {
+                    tokens.push(Token::Phrase { words, prefix });
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut term = String::from(c);
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, '(' | ')' | '"')) {
+                    term.push(c);
+                }
+                match term.as_str() {
+                    "AND" => tokens.push(Token::And),
+                    "OR" => tokens.push(Token::Or),
+                    "NOT" => tokens.push(Token::Not),
+                    _ => {
+                        let words = words(&term);
+                        if !words.is_empty() {
+                            tokens.push(Token::Phrase { words, prefix: term.ends_with('*') });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn or(&mut self) -> Result<SearchExpr, String> {
+        let mut expr = self.and()?;
+        while self.take(&Token::Or) {
+            expr = SearchExpr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    /// Terms side by side are joined by an implied `AND`.
+    fn and(&mut self) -> Result<SearchExpr, String> {
+        let mut expr = self.not()?;
+        loop {
+            let implied = matches!(self.tokens.get(self.next), Some(Token::Open | Token::Phrase { .. }));
+            if !implied && !self.take(&Token::And) {
+                return Ok(expr);
+            }
+            expr = SearchExpr::And(Box::new(expr), Box::new(self.not()?));
+        }
+    }
+
+    fn not(&mut self) -> Result<SearchExpr, String> {
+        let mut expr = self.primary()?;
+        while self.take(&Token::Not) {
+            expr = SearchExpr::Not(Box::new(expr), Box::new(self.primary()?));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<SearchExpr, String> {
+        match self.tokens.get(self.next).cloned() {
+            Some(Token::Phrase { words, prefix }) => {
+                self.next += 1;
+                Ok(SearchExpr::Phrase { words, prefix })
+            }
+            Some(Token::Open) => {
+                self.next += 1;
+                let expr = self.or()?;
+                if !self.take(&Token::Close) {
+                    return Err("a '(' in the search is not closed".to_string());
+                }
+                Ok(expr)
+            }
+            Some(token) => Err(format!("expected a word or phrase in the search, not {}", token)),
+            None => Err("the search ends where a word or phrase was expected".to_string()),
+        }
+    }
+
+    fn take(&mut self, token: &Token) -> bool {
+        if self.tokens.get(self.next) == Some(token) {
+            self.next += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let fts5 = |s: &str| SearchQuery::from_str(s).unwrap().to_fts5();
+        let tsquery = |s: &str| SearchQuery::from_str(s).unwrap().to_tsquery();
+
+        assert_eq!("\"apples\"", fts5("Apples"));
+        assert_eq!("(\"green\" AND \"apples\")", fts5("green apples"));
+        assert_eq!("\"green apples\"", fts5("\"Green  apples\""));
+        assert_eq!("\"e mail\"", fts5("e-mail"));
+        assert_eq!("\"app\" *", fts5("app*"));
+        assert_eq!("\"green app\" *", fts5("\"green app\" *"));
+        assert_eq!("(\"a\" OR (\"b\" AND \"c\"))", fts5("a OR b AND c"));
+        assert_eq!("((\"a\" OR \"b\") AND (\"c\" NOT \"d\"))", fts5("(a OR b) c NOT d"));
+        assert_eq!("\"and\"", fts5("and"));
+        assert_eq!("\"fruit\"", fts5("fruit & ..."));
+
+        assert_eq!("('apples')", tsquery("apples"));
+        assert_eq!("('green' <-> 'app':*)", tsquery("\"green app\"*"));
+        assert_eq!("(('a') | (('b') & !('c')))", tsquery("a OR b NOT c"));
+
+        for invalid in ["", "  ", "...", "a AND", "OR a", "a NOT", "(a", "a)", "\"a", "NOT a", "()"] {
+            assert!(SearchQuery::from_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -2,16 +2,19 @@ use std::env;
 use std::str::FromStr;
 
 use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper};
-use diesel::r2d2::{ConnectionManager, PooledConnection};
-use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use dotenvy::dotenv;
+use jsonwebtoken::{EncodingKey, Header};
 use rust_decimal::Decimal;
 use tracing::dispatcher::set_global_default;
 use tracing_log::LogTracer;
 
+use crate::auth::Claims;
+use crate::clock::{Clock, SystemClock};
 use crate::common::{ListAttribute, Price, User};
 use crate::db;
-use crate::db::{connection, MultiConnection};
+use crate::db::{connection, DbAmount, DbConnection};
+#[cfg(feature = "postgres")]
+use crate::db::MultiConnection;
 use crate::helpers::tracing_subscriber;
 use crate::models::{AccountDb, AccountTypeDb, ItemListDb, ItemListDbInsert, ListItemDb, ListItemDbInsert};
 use crate::schema::{account, account_type, item_list, item_list_account, item_list_attribute, list_item, list_item_attribute, user, user_account};
@@ -29,7 +32,7 @@ pub fn setup_logging() {
     }
 }
 
-pub(crate) fn cleanup_db(c: &mut PooledConnection<ConnectionManager<MultiConnection>>) {
+pub(crate) fn cleanup_db(c: &mut DbConnection) {
     diesel::delete(list_item_attribute::table)
         .execute(c)
         .unwrap();
@@ -39,19 +42,36 @@ pub(crate) fn cleanup_db(c: &mut PooledConnection<ConnectionManager<MultiConnect
         .unwrap();
     diesel::delete(item_list_account::table).execute(c).unwrap();
     diesel::delete(item_list::table).execute(c).unwrap();
+    diesel::sql_query("DELETE FROM list_search").execute(c).unwrap();
     diesel::delete(user_account::table).execute(c).unwrap();
-    diesel::delete(account_type::table).execute(c).unwrap();
     diesel::delete(account::table).execute(c).unwrap();
+    diesel::delete(account_type::table).execute(c).unwrap();
     diesel::delete(user::table).execute(c).unwrap();
+    sync_id_sequences(c);
 }
 
-pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
+/// SQLite gives each new row the next id after the highest in its table, while Postgres draws ids
+/// from sequences that know nothing of rows deleted, or inserted with explicit ids.  Tests that do
+/// either call this so Postgres ids behave the same.
+pub(crate) fn sync_id_sequences(c: &mut DbConnection) {
+    #[cfg(feature = "postgres")]
+    if let MultiConnection::Postgres(pg) = &mut **c {
+        for table in ["account", "account_type", "item_list", "item_list_attribute", "list_item", "list_item_attribute", "user"] {
+            diesel::sql_query(format!(
+                r#"SELECT setval(pg_get_serial_sequence('"{0}"', 'id'), COALESCE(MAX(id), 0) + 1, false) FROM "{0}""#,
+                table
+            ))
+            .execute(pg)
+            .unwrap();
+        }
+    }
+    #[cfg(not(feature = "postgres"))]
+    let _ = c;
+}
 
 pub fn setup_db() {
-    let mut c = connection();
-    c.run_pending_migrations(MIGRATIONS)
-        .expect("Could not run migrations");
-    cleanup_db(&mut c);
+    db::migrate(db::default_pool(), db::MigrationMode::Apply).expect("Could not run migrations");
+    cleanup_db(&mut connection().unwrap());
 }
 
 pub fn setup_accounts() -> (i32, i32) {
@@ -73,7 +93,7 @@ pub fn insert_account_type(
     name: String,
     source: String,
 ) -> i32 {
-    let mut c = connection();
+    let mut c = connection().unwrap();
     diesel::insert_into(account_type::table)
         .values((
             account_type::name.eq(&name),
@@ -94,7 +114,7 @@ pub fn insert_account(
     account_type_id: i32,
     source_id: String,
 ) -> i32 {
-    let mut c = connection();
+    let mut c = connection().unwrap();
     diesel::insert_into(account::table)
         .values((
             account::account_type_id.eq(&account_type_id),
@@ -112,7 +132,7 @@ pub fn insert_account(
 }
 
 pub fn setup_lists(item_list_1_account_ids: Vec<i32>, item_list_2_account_ids: Vec<i32>, user_id_1: i32, user_id_2: i32) {
-    let c = &mut db::connection();
+    let c = &mut db::connection().unwrap();
 
     let item_list_1_id = insert_item_list(c, user_id_1, "Item List One".to_string());
     insert_item_list_attribute(
@@ -176,7 +196,7 @@ pub fn setup_lists(item_list_1_account_ids: Vec<i32>, item_list_2_account_ids: V
 }
 
 fn insert_list_item_attribute(
-    c: &mut PooledConnection<ConnectionManager<MultiConnection>>,
+    c: &mut DbConnection,
     list_item_id: i32,
     name: String,
     attr: ListAttribute,
@@ -210,7 +230,7 @@ fn insert_list_item_attribute(
                     list_item_attribute::list_item_id.eq(&list_item_id),
                     list_item_attribute::attribute_type.eq("Float"),
                     list_item_attribute::name.eq(name),
-                    list_item_attribute::float_val.eq(f as f32),
+                    list_item_attribute::float_val.eq(f),
                 ))
                 .execute(c)
                 .expect("Could not insert float");
@@ -221,19 +241,19 @@ fn insert_list_item_attribute(
                     list_item_attribute::list_item_id.eq(&list_item_id),
                     list_item_attribute::attribute_type.eq("Integer"),
                     list_item_attribute::name.eq(name),
-                    list_item_attribute::integer_val.eq(i as i32),
+                    list_item_attribute::integer_val.eq(i),
                 ))
                 .execute(c)
                 .expect("Could not insert integer");
         }
         ListAttribute::Price(p) => {
-            let str = format!("{}", p);
             diesel::insert_into(list_item_attribute::table)
                 .values((
                     list_item_attribute::list_item_id.eq(&list_item_id),
                     list_item_attribute::attribute_type.eq("Price"),
                     list_item_attribute::name.eq(name),
-                    list_item_attribute::text_val.eq(str),
+                    list_item_attribute::price_amount.eq(DbAmount(p.amount)),
+                    list_item_attribute::price_source.eq(p.source),
                 ))
                 .execute(c)
                 .expect("Could not insert price");
@@ -253,7 +273,7 @@ fn insert_list_item_attribute(
 }
 
 fn insert_item_list_attribute(
-    c: &mut PooledConnection<ConnectionManager<MultiConnection>>,
+    c: &mut DbConnection,
     item_list_id: i32,
     name: String,
     attr: ListAttribute,
@@ -287,7 +307,7 @@ fn insert_item_list_attribute(
                     item_list_attribute::item_list_id.eq(&item_list_id),
                     item_list_attribute::attribute_type.eq("Float"),
                     item_list_attribute::name.eq(name),
-                    item_list_attribute::float_val.eq(f as f32),
+                    item_list_attribute::float_val.eq(f),
                 ))
                 .execute(c)
                 .expect("Could not insert float");
@@ -298,19 +318,19 @@ fn insert_item_list_attribute(
                     item_list_attribute::item_list_id.eq(&item_list_id),
                     item_list_attribute::attribute_type.eq("Integer"),
                     item_list_attribute::name.eq(name),
-                    item_list_attribute::integer_val.eq(i as i32),
+                    item_list_attribute::integer_val.eq(i),
                 ))
                 .execute(c)
                 .expect("Could not insert integer");
         }
         ListAttribute::Price(p) => {
-            let str = format!("{}", p);
             diesel::insert_into(item_list_attribute::table)
                 .values((
                     item_list_attribute::item_list_id.eq(&item_list_id),
                     item_list_attribute::attribute_type.eq("Price"),
                     item_list_attribute::name.eq(name),
-                    item_list_attribute::text_val.eq(str),
+                    item_list_attribute::price_amount.eq(DbAmount(p.amount)),
+                    item_list_attribute::price_source.eq(p.source),
                 ))
                 .execute(c)
                 .expect("Could not insert price");
@@ -329,8 +349,8 @@ fn insert_item_list_attribute(
     }
 }
 
-fn insert_item_list_account(
-    c: &mut PooledConnection<ConnectionManager<MultiConnection>>,
+pub fn insert_item_list_account(
+    c: &mut DbConnection,
     item_list_id: i32,
     account_id: i32,
 ) {
@@ -344,18 +364,43 @@ fn insert_item_list_account(
 }
 
 pub fn insert_user(name: &str, source: &str, source_id: &str) -> i32 {
-         crate::user_storage::create_or_update_user(User {
+         crate::user_storage::create_or_update_user(db::default_pool(), User {
             id: None,
             name: name.to_string(),
             source: source.to_string(),
             source_id: source_id.to_string(),
             user_accounts: vec![],
         })
+        .expect("Could not insert user")
         .id.unwrap() as i32
 }
 
+pub fn insert_user_account(user_id: i32, account_id: i32) {
+    diesel::insert_into(user_account::table)
+        .values((
+            user_account::user_id.eq(&user_id),
+            user_account::account_id.eq(&account_id),
+        ))
+        .execute(&mut connection().unwrap())
+        .expect("Could not insert user_account");
+}
+
+/// An `Authorization` header value for the user, signed with the HS256 `AUTH_JWT_SECRET`.
+pub fn bearer_token(source: &str, source_id: &str) -> String {
+    dotenv().ok();
+    let secret = env::var("AUTH_JWT_SECRET").expect("AUTH_JWT_SECRET must be set");
+    let claims = Claims {
+        exp: (chrono::Utc::now().timestamp() + 3600) as u64,
+        iss: source.to_string(),
+        sub: source_id.to_string(),
+    };
+    let token = jsonwebtoken::encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
+        .expect("Could not sign token");
+    format!("Bearer {}", token)
+}
+
 pub fn insert_item_list(
-    c: &mut PooledConnection<ConnectionManager<MultiConnection>>,
+    c: &mut DbConnection,
     user_id: i32,
     name1: String,
 ) -> i32 {
@@ -366,6 +411,8 @@ pub fn insert_item_list(
         list_type: &"Standard".to_string(),
         name: &name1,
         owner_user_id: user_id,
+        created: SystemClock.now(),
+        modified: SystemClock.now(),
     };
     diesel::insert_into(item_list::table)
         .values(&item_list)
@@ -380,7 +427,7 @@ pub fn insert_item_list(
 }
 
 pub fn insert_list_item(
-    c: &mut PooledConnection<ConnectionManager<MultiConnection>>,
+    c: &mut DbConnection,
     item_list_id: i32,
     name1: String,
 ) -> i32 {
@@ -388,6 +435,9 @@ pub fn insert_list_item(
         item_list_id: &item_list_id,
         name: &name1,
         source: &"My Source".to_string(),
+        position: 0,
+        created: SystemClock.now(),
+        modified: SystemClock.now(),
     };
     diesel::insert_into(list_item::table)
         .values(&list_item)
@@ -1,11 +1,39 @@
-use crate::common::{LMContext, User};
+use crate::common::{ListManagementError, User};
+use crate::db::DbPool;
 
-pub fn create_user(user: User) -> User {
-    crate::user_storage::create_or_update_user(user)
+/// Stores and finds the users that lists belong to.  The default is `DbUserService`; an
+/// application may use another by setting `AppState::user_service`.  It is called on the blocking
+/// thread pool, so may wait on the database.
+pub trait UserService: Send + Sync {
+    /// Creates the user, or replaces the one with the same id, returning the user as stored.
+    fn create_user(&self, user: User) -> Result<User, ListManagementError>;
+    fn retrieve_user(&self, source: &str, source_id: &str) -> Result<Option<User>, ListManagementError>;
+    fn retrieve_user_by_id(&self, id: u64) -> Result<Option<User>, ListManagementError>;
 }
 
-pub fn retrieve_user(_context: impl LMContext, source: &str, source_id: &str) -> Option<User> {
-    crate::user_storage::retrieve_user(source, source_id)
+/// Keeps users in the database alongside their lists.
+pub struct DbUserService {
+    pool: DbPool,
+}
+
+impl DbUserService {
+    pub fn new(pool: DbPool) -> DbUserService {
+        DbUserService { pool }
+    }
+}
+
+impl UserService for DbUserService {
+    fn create_user(&self, user: User) -> Result<User, ListManagementError> {
+        crate::user_storage::create_or_update_user(&self.pool, user)
+    }
+
+    fn retrieve_user(&self, source: &str, source_id: &str) -> Result<Option<User>, ListManagementError> {
+        crate::user_storage::retrieve_user(&self.pool, source, source_id)
+    }
+
+    fn retrieve_user_by_id(&self, id: u64) -> Result<Option<User>, ListManagementError> {
+        crate::user_storage::retrieve_user_by_id(&self.pool, &id)
+    }
 }
 
 #[cfg(test)]
@@ -14,7 +42,6 @@ mod tests {
     use serial_test::serial;
 
     use crate::common::*;
-    use crate::common::tests::context;
     use crate::db;
 
     use super::*;
@@ -23,7 +50,7 @@ mod tests {
     #[serial]
     fn test_retrieve_user() {
         {
-            let mut c = db::connection();
+            let mut c = db::connection().unwrap();
             let _ = sql_query(r#"
         insert into account_type (id, name, source) values (1000, 'AT1', 'AT1 SOURCE')
             "#).execute(&mut c);
@@ -40,10 +67,10 @@ mod tests {
         insert into account (id, account_type_id, account_source_id) values (102, 1000, 'AT1-TWO')
             "#).execute(&mut c);
             let _ = sql_query(r#"
-        insert into USER (id, name, source, source_id) values (5, 'User Five', 'source', 'source-id-5')
+        insert into "user" (id, name, source, source_id) values (5, 'User Five', 'source', 'source-id-5')
             "#).execute(&mut c);
             let _ = sql_query(r#"
-        insert into USER (id, name, source, source_id) values (6, 'User Six', 'source', 'source-id-6')
+        insert into "user" (id, name, source, source_id) values (6, 'User Six', 'source', 'source-id-6')
             "#).execute(&mut c);
             let _ = sql_query(r#"
         insert into USER_ACCOUNT (user_id, account_id) values (5, 100)
@@ -60,15 +87,10 @@ mod tests {
             let _ = sql_query(r#"
         insert into USER_ACCOUNT (user_id, account_id) values (6, 102)
             "#).execute(&mut c);
+            crate::test_helpers::sync_id_sequences(&mut c);
         }
 
-        let u5 = retrieve_user(us_context(User {
-            id: Some(5),
-            name: "".to_string(),
-            source: "".to_string(),
-            source_id: "".to_string(),
-            user_accounts: vec![],
-        }), "source", "source-id-5").unwrap();
+        let u5 = users().retrieve_user("source", "source-id-5").unwrap().unwrap();
         assert_eq!(5, u5.id.unwrap());
         assert_eq!("User Five", u5.name);
         assert_eq!(3, u5.user_accounts.len());
@@ -82,13 +104,7 @@ mod tests {
             u5.user_accounts[2].account_type.source
         );
 
-        let u6 = retrieve_user(us_context(User {
-            id: Some(6),
-            name: "".to_string(),
-            source: "".to_string(),
-            source_id: "".to_string(),
-            user_accounts: vec![],
-        }), "source", "source-id-6").unwrap();
+        let u6 = users().retrieve_user("source", "source-id-6").unwrap().unwrap();
         assert_eq!(6, u6.id.unwrap());
         assert_eq!("User Six", u6.name);
         assert_eq!(2, u6.user_accounts.len());
@@ -106,7 +122,7 @@ mod tests {
     #[serial]
     fn test_create_user() {
         {
-            let mut c = db::connection();
+            let mut c = db::connection().unwrap();
             let _ = sql_query(r#"
         insert into account_type (id, name, source) values (1000, 'AT1', 'AT1 SOURCE')
             "#).execute(&mut c);
@@ -129,7 +145,7 @@ mod tests {
                 account_source_id: "".to_string(),
             }],
         };
-        let u1 = create_user(u1);
+        let u1 = users().create_user(u1).unwrap();
         assert!(u1.id.is_some());
         assert_eq!("My Name", u1.name);
         assert_eq!("My Source", u1.source);
@@ -142,16 +158,7 @@ mod tests {
     }
 
 
-    fn us_context(user: User) -> impl LMContext {
-        let current_user = user;
-        let user_state = user_state(current_user.id.unwrap().clone(), current_user.user_accounts.clone());
-        context(current_user, user_state)
-    }
-
-    fn user_state(id: u64, active_accounts: Vec<Account>) -> UserState {
-        UserState {
-            user_id: id,
-            active_user_accounts: active_accounts,
-        }
+    fn users() -> DbUserService {
+        DbUserService::new(db::default_pool().clone())
     }
 }
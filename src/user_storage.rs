@@ -1,126 +1,108 @@
 use diesel::prelude::*;
 
-use crate::common::{Account, AccountType, User};
+use crate::common::{Account, AccountType, ListManagementError, User};
 use crate::db;
+use crate::db::DbPool;
 use crate::list_storage::all_account_types;
 use crate::models::{AccountDb, UserAccountDb, UserDb};
 use crate::schema::{account, user, user_account};
 
-pub(crate) fn create_or_update_user(u1: User) -> User {
-    let prior_val: Option<User> = if *(&u1.id.is_some()) {
-        retrieve_user_by_id(&u1.id.unwrap())
-    } else {
-        None
+pub(crate) fn create_or_update_user(pool: &DbPool, u1: User) -> Result<User, ListManagementError> {
+    let prior_val: Option<User> = match u1.id {
+        Some(id) => retrieve_user_by_id(pool, &id)?,
+        None => None,
     };
     let user_id: i32 = {
-        let mut c = db::connection();
+        let mut c = db::check_out(pool)?;
 
-        let my_user_id = if prior_val.is_none() {
-            diesel::insert_into(user::table)
-                .values((
+        let my_user_id = if let Some(prior_user) = prior_val {
+            let my_user_id_1 = prior_user.id.unwrap() as i32;
+            diesel::update(user::table)
+                .filter(user::id.eq(my_user_id_1))
+                .set((
                     user::name.eq(&u1.name),
                     user::source.eq(&u1.source),
                     user::source_id.eq(&u1.source_id),
                 ))
-                .execute(&mut c)
-                .expect("Could not insert user");
-            user::table
-                .select(UserDb::as_select())
-                .filter(user::name.eq(&u1.name))
-                .filter(user::source.eq(&u1.source))
-                .filter(user::source_id.eq(&u1.source_id))
-                .load(&mut c)
-                .unwrap()[0]
-                .id
+                .execute(&mut c)?;
+            diesel::delete(user_account::table)
+                .filter(user_account::user_id.eq(&my_user_id_1))
+                .execute(&mut c)?;
+            my_user_id_1
         } else {
-            let my_user_id_1 = prior_val.unwrap().id.unwrap() as i32;
-            let _ = diesel::update(user::table)
-                .filter(user::id.eq(my_user_id_1))
-                .set((
+            diesel::insert_into(user::table)
+                .values((
                     user::name.eq(&u1.name),
                     user::source.eq(&u1.source),
                     user::source_id.eq(&u1.source_id),
                 ))
-                .execute(&mut c);
-            let _ = diesel::delete(user_account::table)
-                .filter(user_account::user_id.eq(&my_user_id_1))
-                .execute(&mut c)
-                .expect("could not delete old accounts");
-            my_user_id_1
+                .returning(user::id)
+                .get_result(&mut c)?
         };
         for acct in u1.user_accounts {
-            let _ = diesel::insert_into(user_account::table)
+            let account_id = acct
+                .id
+                .ok_or_else(|| ListManagementError::Validation("user accounts must have an id".to_string()))?;
+            diesel::insert_into(user_account::table)
                 .values((
                     user_account::user_id.eq(&my_user_id),
-                    user_account::account_id.eq(&(acct.id.unwrap() as i32)),
+                    user_account::account_id.eq(&(account_id as i32)),
                 ))
-                .execute(&mut c)
-                .expect("could not insert user account.");
+                .execute(&mut c)?;
         }
         my_user_id
     };
-    retrieve_user_by_id(&(user_id as u64)).unwrap()
+    retrieve_user_by_id(pool, &(user_id as u64))?
+        .ok_or_else(|| ListManagementError::NotFound(user_id.to_string()))
 }
 
-fn delete_user(user_id: &u64) -> bool {
-    let mut c = db::connection();
+#[cfg(test)]
+fn delete_user(pool: &DbPool, user_id: &u64) -> Result<bool, ListManagementError> {
+    let mut c = db::check_out(pool)?;
     let uid: i32 = *user_id as i32;
-    let _ = diesel::delete(user_account::table)
+    diesel::delete(user_account::table)
         .filter(user_account::user_id.eq(&uid))
-        .execute(&mut c)
-        .expect("Could not delete user account relation");
+        .execute(&mut c)?;
     let num = diesel::delete(user::table)
         .filter(user::id.eq(uid))
-        .execute(&mut c)
-        .expect("Could not delete user");
-    num > 0
+        .execute(&mut c)?;
+    Ok(num > 0)
 }
 
-pub(crate) fn retrieve_user(source: &str, source_id: &str) -> Option<User> {
+pub(crate) fn retrieve_user(pool: &DbPool, source: &str, source_id: &str) -> Result<Option<User>, ListManagementError> {
     let udb = {
-        let mut c = db::connection();
-        let udb_opt = user::table
+        let mut c = db::check_out(pool)?;
+        user::table
             .select(UserDb::as_select())
             .filter(user::source.eq(source))
             .filter(user::source_id.eq(source_id))
             .get_result(&mut c)
-            .optional()
-            .unwrap();
-        if udb_opt.is_none() {
-            return None;
-        }
-        udb_opt.unwrap()
+            .optional()?
     };
-    Some(udb_to_user(udb))
+    udb.map(|udb| udb_to_user(pool, udb)).transpose()
 }
 
-pub(crate) fn retrieve_user_by_id(id: &u64) -> Option<User> {
+pub(crate) fn retrieve_user_by_id(pool: &DbPool, id: &u64) -> Result<Option<User>, ListManagementError> {
     let udb = {
-        let mut c = db::connection();
-        let udb_opt = user::table
+        let mut c = db::check_out(pool)?;
+        user::table
             .select(UserDb::as_select())
             .filter(user::id.eq(*id as i32))
             .get_result(&mut c)
-            .optional()
-            .unwrap();
-        if udb_opt.is_none() {
-            return None;
-        }
-        udb_opt.unwrap()
+            .optional()?
     };
-    Some(udb_to_user(udb))
+    udb.map(|udb| udb_to_user(pool, udb)).transpose()
 }
 
-fn udb_to_user(udb: UserDb) -> User {
-    let account_types = all_account_types();
-    let mut c = db::connection();
+fn udb_to_user(pool: &DbPool, udb: UserDb) -> Result<User, ListManagementError> {
+    let account_types = all_account_types(pool)?;
+    let mut c = db::check_out(pool)?;
     let accounts: Vec<AccountDb> = UserAccountDb::belonging_to(&udb)
         .inner_join(account::table)
         .select(AccountDb::as_select())
-        .load(&mut c)
-        .unwrap();
+        .load(&mut c)?;
 
-    User {
+    Ok(User {
         id: Some(udb.id as u64),
         name: udb.name.clone(),
         source: udb.source.clone(),
@@ -137,7 +119,7 @@ fn udb_to_user(udb: UserDb) -> User {
                 account_source_id: a.account_source_id,
             })
             .collect(),
-    }
+    })
 }
 
 
@@ -154,6 +136,7 @@ pub mod tests {
     #[serial]
     fn test_user_storage() {
         let (a1_id, a2_id) = setup_accounts();
+        let pool = db::default_pool();
         let ac0 = AccountType {
             id: None,
             name: "".to_string(),
@@ -183,13 +166,13 @@ pub mod tests {
             source_id: "s2-1".to_string(),
             user_accounts: vec![a2.clone()],
         };
-        let _ = create_or_update_user(u1);
-        let _ = create_or_update_user(u2);
+        create_or_update_user(pool, u1).unwrap();
+        create_or_update_user(pool, u2).unwrap();
 
-        let s1_11_opt = retrieve_user("s1", "s1-1");
-        let s2_21_opt = retrieve_user("s2", "s2-1");
-        let s2_11_opt = retrieve_user("s2", "s1-1");
-        let s1_21_opt = retrieve_user("s1", "s2-1");
+        let s1_11_opt = retrieve_user(pool, "s1", "s1-1").unwrap();
+        let s2_21_opt = retrieve_user(pool, "s2", "s2-1").unwrap();
+        let s2_11_opt = retrieve_user(pool, "s2", "s1-1").unwrap();
+        let s1_21_opt = retrieve_user(pool, "s1", "s2-1").unwrap();
 
         assert!(s1_11_opt.is_some());
         assert!(s2_21_opt.is_some());
@@ -210,12 +193,12 @@ pub mod tests {
         assert_eq!("s2-1", s2_21.source_id);
         assert_eq!(2, s1_11.user_accounts.len());
 
-        assert!(delete_user(&s1_11.id.unwrap()));
-        assert!(!delete_user(&s1_11.id.unwrap()));
-        assert!(delete_user(&s2_21.id.unwrap()));
-        assert!(!delete_user(&s2_21.id.unwrap()));
+        assert!(delete_user(pool, &s1_11.id.unwrap()).unwrap());
+        assert!(!delete_user(pool, &s1_11.id.unwrap()).unwrap());
+        assert!(delete_user(pool, &s2_21.id.unwrap()).unwrap());
+        assert!(!delete_user(pool, &s2_21.id.unwrap()).unwrap());
 
-        let c = &mut db::connection();
+        let c = &mut db::connection().unwrap();
         let count = user_account::table
             .select(UserAccountDb::as_select())
             .load(c)
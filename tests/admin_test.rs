@@ -0,0 +1,72 @@
+use actix_web::{App, test, web};
+use actix_web::http::StatusCode;
+use serial_test::serial;
+
+use list_management::app_state::AppState;
+use list_management::config::AppConfig;
+use list_management::db::SchemaVersion;
+use list_management::route_config;
+use list_management::test_helpers::{bearer_token, insert_user, setup_db};
+
+#[actix_web::test]
+#[serial]
+async fn test_schema_version() {
+    setup_db();
+    insert_user("User One", "s1", "s1-1");
+    insert_user("User Two", "s1", "s1-2");
+    let token = bearer_token("s1", "s1-1");
+
+    let mut config = AppConfig::from_env();
+    config.auth.admins = vec!["s1:s1-1".to_string()];
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState { config, ..AppState::from_env() }))
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/admin/schema_version")
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::get()
+        .uri("/admin/schema_version")
+        .insert_header(("Authorization", bearer_token("s1", "s1-2").as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::get()
+        .uri("/admin/schema_version")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let schema_version: SchemaVersion = test::read_body_json(service_response).await;
+    assert!(schema_version.version.is_some());
+    assert!(schema_version.pending.is_empty());
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_admin_turned_off() {
+    setup_db();
+    insert_user("User One", "s1", "s1-1");
+    let token = bearer_token("s1", "s1-1");
+
+    let mut config = AppConfig::from_env();
+    config.features.admin = false;
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState { config, ..AppState::from_env() }))
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/admin/schema_version")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,144 @@
+use std::env;
+use std::fs;
+use std::sync::Arc;
+
+use actix_web::{App, test, web};
+use actix_web::http::StatusCode;
+use chrono::{NaiveDate, NaiveDateTime};
+use jsonwebtoken::{EncodingKey, Header};
+use serde_json::json;
+use serial_test::serial;
+use tracing_actix_web::TracingLogger;
+
+use list_management::app_state::AppState;
+use list_management::auth::Claims;
+use list_management::clock::Clock;
+use list_management::common::{ItemList, ListItem, User};
+use list_management::config::AppConfig;
+use list_management::db;
+use list_management::db::MigrationMode;
+use list_management::route_config;
+use list_management::test_helpers::{bearer_token, insert_user, setup_db, setup_logging};
+
+struct FixedClock(NaiveDateTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> NaiveDateTime {
+        self.0
+    }
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_server_with_own_state() {
+    setup_logging();
+    setup_db();
+    insert_user("User One", "s1", "s1-1");
+    let token = bearer_token("s1", "s1-1");
+
+    let path = env::temp_dir().join(format!("list_management_app_state_{}.db", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let mut config = AppConfig::from_env();
+    config.database.url = path.to_str().unwrap().to_string();
+    let now = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap().and_hms_opt(12, 30, 0).unwrap();
+    let state = AppState {
+        clock: Arc::new(FixedClock(now)),
+        ..AppState::new(config).unwrap()
+    };
+    db::migrate(&state.pool, MigrationMode::Apply).unwrap();
+    // The caller must be a user of the state's own database.
+    state.user_service.create_user(User {
+        id: None,
+        name: "Other User One".to_string(),
+        source: "s1".to_string(),
+        source_id: "s1-1".to_string(),
+        user_accounts: vec![],
+    }).unwrap();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .app_data(web::Data::new(state))
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/lists")
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "name": "Groceries" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::CREATED);
+    let created: ItemList = test::read_body_json(service_response).await;
+    assert_eq!(now, created.created);
+    assert_eq!(now, created.modified);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/lists/{}/items", created.id.unwrap()))
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "name": "Apples", "source": "orchard" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::CREATED);
+    let item: ListItem = test::read_body_json(service_response).await;
+    assert_eq!(now, item.created);
+
+    // The list is only in the state's database, not the one configured by the environment.
+    let default_app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let lists: serde_json::Value = test::call_and_read_body_json(&default_app, req).await;
+    assert_eq!(0, lists["total"]);
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let lists: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(1, lists["total"]);
+    assert_eq!("Groceries", lists["lists"][0]["name"]);
+
+    drop(app);
+    let _ = fs::remove_file(&path);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_server_with_own_auth() {
+    setup_logging();
+    setup_db();
+    insert_user("User One", "s1", "s1-1");
+
+    let mut config = AppConfig::from_env();
+    config.auth.jwt_secret = Some("the state's own secret".to_string());
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .app_data(web::Data::new(AppState::with_pool(config, db::default_pool().clone())))
+            .configure(route_config::config)
+    ).await;
+
+    let claims = Claims {
+        exp: (chrono::Utc::now().timestamp() + 3600) as u64,
+        iss: "s1".to_string(),
+        sub: "s1-1".to_string(),
+    };
+    let own_token = jsonwebtoken::encode(&Header::default(), &claims, &EncodingKey::from_secret(b"the state's own secret")).unwrap();
+    // Tokens are verified with the state's settings, not those of the environment.
+    for (token, status) in [
+        (format!("Bearer {}", own_token), StatusCode::OK),
+        (bearer_token("s1", "s1-1"), StatusCode::UNAUTHORIZED),
+    ] {
+        let req = test::TestRequest::get()
+            .uri("/list_of_lists")
+            .insert_header(("Authorization", token.as_str()))
+            .to_request();
+        assert_eq!(status, test::call_service(&app, req).await.status());
+    }
+}
@@ -0,0 +1,274 @@
+use actix_web::{App, test};
+use actix_web::http::StatusCode;
+use serde_json::json;
+use serial_test::serial;
+use tracing_actix_web::TracingLogger;
+
+use list_management::common::{ItemList, ListAttribute, ListItem};
+use list_management::list_item_service::{FoundItemsResponse, ListItemsResponse};
+use list_management::route_config;
+use list_management::test_helpers::{bearer_token, insert_user, setup_db, setup_logging};
+
+#[actix_web::test]
+#[serial]
+async fn test_list_items() {
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/lists")
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "name": "Groceries" }))
+        .to_request();
+    let list: ItemList = test::call_and_read_body_json(&app, req).await;
+    let items_uri = format!("/lists/{}/items", list.id.unwrap());
+
+    let mut ids: Vec<u64> = vec![];
+    for name in ["Apples", "Pears"] {
+        let req = test::TestRequest::post()
+            .uri(&items_uri)
+            .insert_header(("Authorization", token.as_str()))
+            .set_json(json!({
+                "name": name,
+                "source": "grocer",
+                "attributes": { "quantity": { "Integer": 1 } }
+            }))
+            .to_request();
+        let service_response = test::call_service(&app, req).await;
+        assert_eq!(service_response.status(), StatusCode::CREATED);
+        assert_eq!("\"1\"", service_response.headers().get("ETag").unwrap());
+        let item: ListItem = test::read_body_json(service_response).await;
+        assert_eq!(name, item.name);
+        ids.push(item.id.unwrap());
+    }
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("{}/{}", items_uri, ids[0]))
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "\"1\""))
+        .set_json(json!({ "attributes": { "quantity": { "Integer": 6 } } }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    assert_eq!("\"2\"", service_response.headers().get("ETag").unwrap());
+    let item: ListItem = test::read_body_json(service_response).await;
+    assert_eq!(ListAttribute::Integer(6), item.attributes["quantity"]);
+    assert_eq!(2, item.version);
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("{}/{}", items_uri, ids[0]))
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "\"1\""))
+        .set_json(json!({ "name": "Crab Apples" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::PRECONDITION_FAILED);
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", items_uri, ids[0]))
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    // Reordering changes the list, so must be based on its current version.
+    let req = test::TestRequest::get()
+        .uri(&format!("/lists/{}", list.id.unwrap()))
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    let list_etag = service_response.headers().get("ETag").unwrap().clone();
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/order", items_uri))
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "item_ids": [ids[1], ids[0]] }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::PRECONDITION_REQUIRED);
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/order", items_uri))
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "\"1\""))
+        .set_json(json!({ "item_ids": [ids[1], ids[0]] }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/order", items_uri))
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", list_etag))
+        .set_json(json!({ "item_ids": [ids[1], ids[0]] }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let items: Vec<ListItem> = test::read_body_json(service_response).await;
+    assert_eq!("Pears", items[0].name);
+    assert_eq!("Apples", items[1].name);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?rows=1", items_uri))
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let page: ListItemsResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(2, page.total);
+    assert_eq!("Pears", page.items[0].name);
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?rows=1&cursor={}", items_uri, page.next_cursor.unwrap()))
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let page: ListItemsResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!("Apples", page.items[0].name);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?start=1&cursor={}", items_uri, page.next_cursor.unwrap()))
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}?rows=1001", items_uri))
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::put()
+        .uri(&format!("{}/order", items_uri))
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "*"))
+        .set_json(json!({ "item_ids": [ids[1]] }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", items_uri, ids[1]))
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "\"1\""))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&items_uri)
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let page: ListItemsResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(1, page.total);
+    assert_eq!(1, page.items.len());
+    assert_eq!("Apples", page.items[0].name);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("{}/{}", items_uri, ids[1]))
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "\"1\""))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_find_items() {
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    for (list, attributes, items) in [
+        ("Groceries", json!({ "theme": { "Text": "food" } }), json!([
+            { "name": "Apples", "source": "grocer", "attributes": { "priority": { "Text": "high" }, "quantity": { "Integer": 6 } } },
+            { "name": "Pears", "source": "market", "attributes": { "priority": { "Text": "low" }, "quantity": { "Integer": 2 } } }
+        ])),
+        ("Hardware", json!({}), json!([
+            { "name": "Nails", "source": "grocer", "attributes": { "priority": { "Text": "high" } } }
+        ])),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/lists")
+            .insert_header(("Authorization", token.as_str()))
+            .set_json(json!({ "name": list, "attributes": attributes }))
+            .to_request();
+        let list: ItemList = test::call_and_read_body_json(&app, req).await;
+        for item in items.as_array().unwrap() {
+            let req = test::TestRequest::post()
+                .uri(&format!("/lists/{}/items", list.id.unwrap()))
+                .insert_header(("Authorization", token.as_str()))
+                .set_json(item)
+                .to_request();
+            assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+        }
+    }
+
+    for (query, expected_names) in [
+        ("", vec!["Apples", "Pears", "Nails"]),
+        ("source=grocer&sort_key=Name&descending=true", vec!["Nails", "Apples"]),
+        ("attribute=priority:Eq:high&attribute=quantity", vec!["Apples"]),
+        ("attribute=quantity:Ge:3&attribute=priority:Ne:%22low%22", vec!["Apples"]),
+        ("name_keywords=pear*", vec!["Pears"]),
+        ("source=market&source=grocer&sort_key=Source&rows=1", vec!["Apples"]),
+        ("attribute=priority:Eq:low|quantity:Gt:5", vec!["Apples", "Pears"]),
+        ("attribute=priority:Eq:high&list_attribute=theme:Missing", vec!["Nails"]),
+        ("attribute=priority:Eq:high&list_attribute=theme:Eq:food", vec!["Apples"]),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/items?{}", query))
+            .insert_header(("Authorization", token.as_str()))
+            .to_request();
+        let service_response = test::call_service(&app, req).await;
+        assert_eq!(service_response.status(), StatusCode::OK, "{}", query);
+        let found: FoundItemsResponse = test::read_body_json(service_response).await;
+        assert_eq!(expected_names, found.items.iter().map(|f| f.item.name.as_str()).collect::<Vec<_>>(), "{}", query);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/items?sort_key=Name&rows=2")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let page: FoundItemsResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(3, page.total);
+    assert_eq!("Groceries", page.items[0].list_name);
+    let req = test::TestRequest::get()
+        .uri(&format!("/items?sort_key=Name&rows=2&cursor={}", page.next_cursor.unwrap()))
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let page: FoundItemsResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!("Pears", page.items[0].item.name);
+
+    for (query, expected_message) in [
+        ("attribute=priority:Equals:high", "Invalid value 'priority:Equals:high' for query parameter 'attribute'"),
+        ("attribute=priority:Eq", "Invalid value 'priority:Eq' for query parameter 'attribute'"),
+        ("sort_key=Relevance", "Invalid value 'Relevance' for query parameter 'sort_key'"),
+        ("source=", "Invalid value '' for query parameter 'source'"),
+        ("search=apples", "Unknown query parameter 'search'"),
+        ("rows=1&rows=2", "'rows' may only be specified once"),
+        ("rows=1001", "'rows': expected at most 1000"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/items?{}", query))
+            .insert_header(("Authorization", token.as_str()))
+            .to_request();
+        let service_response = test::call_service(&app, req).await;
+        assert_eq!(service_response.status(), StatusCode::BAD_REQUEST, "{}", query);
+        let body_bytes = test::read_body(service_response).await;
+        let body = std::str::from_utf8(&body_bytes).unwrap();
+        assert!(body.contains(expected_message), "{} => {}", query, body);
+    }
+}
+
+/// Sets up the database and returns a bearer token for the test user.
+fn setup() -> String {
+    setup_logging();
+    setup_db();
+    insert_user("User One", "s1", "s1-1");
+    bearer_token("s1", "s1-1")
+}
@@ -1,15 +1,195 @@
-use actix_web::{App, test};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::{App, test, web};
 use actix_web::http::StatusCode;
 use tracing_actix_web::TracingLogger;
 
-use list_management::common::ItemList;
-use list_management::common::ListAccess;
+use list_management::app_state::AppState;
+use list_management::common::{ItemList, LMContext, ListAccess, ListAttribute, ListManagementError, ListType, PagingRequest, RollupRequest, SortRequest};
+use list_management::config::AppConfig;
+use list_management::currency::{CurrencyConverter, StaticRateConverter};
+use list_management::list_of_lists_service::{ListOfListsResponse, ListProvider, ListSelector, ListsFuture};
+use list_management::rollup::RollupSpecification;
 use list_management::route_config;
-use list_management::test_helpers::{insert_account, insert_account_type, insert_user, setup_db, setup_lists, setup_logging};
+use list_management::test_helpers::{bearer_token, insert_account, insert_account_type, insert_user, setup_db, setup_lists, setup_logging};
+use serde_json::json;
+use serial_test::serial;
 
 #[actix_web::test]
+#[serial]
 async fn test_list_of_lists() {
-    let user_id = setup();
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+    assert_eq!(2, lr.total);
+    assert_eq!(0, lr.start);
+    assert_eq!(10, lr.rows);
+    assert_eq!(2, lr.lists.len());
+    assert_eq!("Item List One", lr.lists[0].name);
+    assert_eq!("Item List Two", lr.lists[1].name);
+    assert_eq!(ListAccess::Public, lr.lists[0].list_access);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_of_lists_with_query_parameters() {
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists?sort_key=Name&descending=true&start=1&rows=1")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+    assert_eq!(2, lr.total);
+    assert_eq!(1, lr.start);
+    assert_eq!(1, lr.rows);
+    assert_eq!(1, lr.lists.len());
+    assert_eq!("Item List One", lr.lists[0].name);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/list_of_lists?sort_key=Name&descending=true&rows=1&cursor={}", lr.next_cursor.unwrap()))
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+    assert!(lr.lists.is_empty());
+    assert!(lr.next_cursor.is_none());
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists?name_keywords=two&list_access=Public&list_access=Shared&folder=default&return_attributes=false")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+    assert_eq!(1, lr.total);
+    assert_eq!(1, lr.lists.len());
+    assert_eq!("Item List Two", lr.lists[0].name);
+    assert!(lr.lists[0].attributes.is_empty());
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists?sort_key=Attribute&sort_attribute=Number&list_type=System")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+    assert_eq!(0, lr.total);
+    assert!(lr.lists.is_empty());
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_of_lists_with_search() {
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    for name in ["Picnic Supplies", "Picnic Ideas", "Camping Supplies"] {
+        let req = test::TestRequest::post()
+            .uri("/lists")
+            .insert_header(("Authorization", token.as_str()))
+            .set_json(json!({"name": name}))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists?search=%22picnic%20supplies%22%20OR%20camp*&sort_key=Name")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+    assert_eq!(vec!["Camping Supplies", "Picnic Supplies"], lr.lists.iter().map(|l| l.name.as_str()).collect::<Vec<_>>());
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists?search=picnic%20NOT%20ideas&sort_key=Relevance")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+    assert_eq!(1, lr.total);
+    assert_eq!("Picnic Supplies", lr.lists[0].name);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_of_lists_with_attribute_filters() {
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    for (name, attributes) in [
+        ("Party", json!({ "budget": { "Price": { "amount": "50.00", "source": "USD" } }, "theme": { "Text": "Pirates | Parrots" } })),
+        ("Trip", json!({ "budget": { "Price": { "amount": "900.00", "source": "EUR" } }, "nights": { "Integer": 3 } })),
+        ("Chores", json!({ "urgent": { "Boolean": true } })),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/lists")
+            .insert_header(("Authorization", token.as_str()))
+            .set_json(json!({ "name": name, "attributes": attributes }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+    }
+
+    for (query, expected_names) in [
+        ("attribute=budget:Lt:100", vec!["Party"]),
+        ("attribute=budget:Ge:100.00%20EUR", vec!["Trip"]),
+        ("attribute=budget:Gt:10%20USD&attribute=budget:Lt:60%20USD", vec!["Party"]),
+        ("attribute=theme:Contains:pirate", vec!["Party"]),
+        ("attribute=theme:Eq:%22Pirates%20|%20Parrots%22", vec!["Party"]),
+        ("attribute=nights:Gt:2|urgent:Eq:true", vec!["Trip", "Chores"]),
+        ("attribute=budget&attribute=theme:Missing", vec!["Trip"]),
+        ("attribute=urgent:Exists&list_type=Standard", vec!["Chores"]),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/list_of_lists?{}", query))
+            .insert_header(("Authorization", token.as_str()))
+            .to_request();
+        let service_response = test::call_service(&app, req).await;
+        assert_eq!(service_response.status(), StatusCode::OK, "{}", query);
+        let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+        assert_eq!(expected_names, lr.lists.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(), "{}", query);
+    }
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_of_lists_with_invalid_query_parameters() {
+    let token = setup();
 
     let app = test::init_service(
         App::new()
@@ -17,20 +197,267 @@ async fn test_list_of_lists() {
             .configure(route_config::config)
     ).await;
 
+    for (query, expected_message) in [
+        ("rows=ten", "Invalid value 'ten' for query parameter 'rows'"),
+        ("rows=1001", "'rows': expected at most 1000"),
+        ("start=-1", "Invalid value '-1' for query parameter 'start'"),
+        ("show_deleted=yes", "Invalid value 'yes' for query parameter 'show_deleted'"),
+        ("list_type=Archived", "Invalid value 'Archived' for query parameter 'list_type'"),
+        ("list_access=Secret", "Invalid value 'Secret' for query parameter 'list_access'"),
+        ("list_id=one", "Invalid value 'one' for query parameter 'list_id'"),
+        ("sort_key=Color", "Invalid value 'Color' for query parameter 'sort_key'"),
+        ("sort_key=Attribute", "'sort_attribute' is required"),
+        ("sort_key=Relevance", "'search' is required"),
+        ("search=%22picnic", "a quoted phrase in the search is not closed"),
+        ("search=picnic%20OR", "Invalid value 'picnic OR' for query parameter 'search'"),
+        ("attribute=:Eq:1", "Invalid value ':Eq:1' for query parameter 'attribute'"),
+        ("attribute=size:Between:1", "Invalid value 'size:Between:1' for query parameter 'attribute'"),
+        ("attribute=size:Missing|", "Invalid value 'size:Missing|' for query parameter 'attribute'"),
+        ("attribute=size:Contains", "Invalid value 'size:Contains' for query parameter 'attribute'"),
+        ("sort_attribute=Number", "'sort_attribute' is only allowed"),
+        ("rows=1&rows=2", "'rows' may only be specified once"),
+        ("color=blue", "Unknown query parameter 'color'"),
+        ("start=1&cursor=abc", "'start' and 'cursor' may not be combined"),
+        ("cursor=abc", "'abc' is not a valid cursor"),
+        ("return_rollups=false&rollup_currency=USD", "'rollup_currency' is not allowed"),
+        ("rollup_currency=USD", "no currency rates are configured"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/list_of_lists?{}", query))
+            .insert_header(("Authorization", token.as_str()))
+            .to_request();
+        let service_response = test::call_service(&app, req).await;
+        assert_eq!(service_response.status(), StatusCode::BAD_REQUEST, "{}", query);
+        let body_bytes = test::read_body(service_response).await;
+        let body = std::str::from_utf8(&body_bytes).unwrap();
+        assert!(body.contains(expected_message), "{} => {}", query, body);
+    }
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_of_lists_with_configured_limits() {
+    let token = setup();
+
+    let mut config = AppConfig::from_env();
+    config.paging.default_rows = 1;
+    config.paging.max_rows = 2;
+    config.features.search = false;
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .app_data(web::Data::new(AppState { config, ..AppState::from_env() }))
+            .configure(route_config::config)
+    ).await;
+
     let req = test::TestRequest::get()
         .uri("/list_of_lists")
-        .insert_header(("user_id", user_id))
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let lr: ListOfListsResponse = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(1, lr.rows);
+    assert_eq!(1, lr.lists.len());
+
+    for (query, expected_message) in [
+        ("rows=3", "'rows': expected at most 2"),
+        ("search=picnic", "full-text search is turned off"),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/list_of_lists?{}", query))
+            .insert_header(("Authorization", token.as_str()))
+            .to_request();
+        let service_response = test::call_service(&app, req).await;
+        assert_eq!(service_response.status(), StatusCode::BAD_REQUEST, "{}", query);
+        let body_bytes = test::read_body(service_response).await;
+        let body = std::str::from_utf8(&body_bytes).unwrap();
+        assert!(body.contains(expected_message), "{} => {}", query, body);
+    }
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_of_lists_with_converted_rollups() {
+    let token = setup();
+    let converter: Arc<dyn CurrencyConverter> = Arc::new(StaticRateConverter::parse("KAU=1.00,USD=0.50").unwrap());
+    let state = AppState { currency_converter: Some(converter), ..AppState::from_env() };
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .app_data(web::Data::new(state))
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists?rollup_currency=USD")
+        .insert_header(("Authorization", token.as_str()))
         .to_request();
     let service_response = test::call_service(&app, req).await;
     assert_eq!(service_response.status(), StatusCode::OK);
-    let lr: Vec<ItemList> = test::read_body_json(service_response).await;
-    assert_eq!(2, lr.len());
-    assert_eq!("Item List One", lr[0].name);
-    assert_eq!("Item List Two", lr[1].name);
-    assert_eq!(ListAccess::Public, lr[0].list_access);
+    let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+    let rollups = lr.lists[0].rollups.as_ref().unwrap();
+    assert_eq!(1, rollups.len());
+    assert_eq!("USD_US", rollups[0].name);
+    assert!(matches!(&rollups[0].value, ListAttribute::Price(p) if p.source == "KAU"));
+    assert_eq!(2, rollups[0].total_lines);
+    let converted = lr.lists[0].converted_rollups.as_ref().unwrap();
+    assert_eq!(1, converted.len());
+    assert!(matches!(&converted[0].value, ListAttribute::Price(p) if p.source == "USD"));
+    assert_eq!(2, converted[0].total_lines);
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let lr: ListOfListsResponse = test::read_body_json(test::call_service(&app, req).await).await;
+    assert!(lr.lists[0].rollups.is_some());
+    assert!(lr.lists[0].converted_rollups.is_none());
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_of_lists_with_rollup_definitions() {
+    let token = setup();
+    let specification = RollupSpecification::parse(r#"{"by_list_type": {"Standard": [
+        {"name": "items", "function": "Count", "of": "Name"},
+        {"name": "most", "function": "Max", "of": {"Attribute": "USD_US"}}
+    ]}}"#).unwrap();
+    let state = AppState { rollup_specification: Arc::new(specification), ..AppState::from_env() };
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .app_data(web::Data::new(state))
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+    let rollups = lr.lists[0].rollups.as_ref().unwrap();
+    assert_eq!(2, rollups.len());
+    assert_eq!("items", rollups[0].name);
+    assert_eq!(ListAttribute::Integer(2), rollups[0].value);
+    assert_eq!("most", rollups[1].name);
+    assert!(matches!(&rollups[1].value, ListAttribute::Price(p) if p.amount.to_string() == "3.45"));
+}
+
+/// Serves one list per page asked for, named for the caller, as a remote source might.
+struct RemoteListProvider {
+    available: bool,
+}
+
+impl ListProvider for RemoteListProvider {
+    fn retrieve_lists<'a>(
+        &'a self,
+        context: &'a dyn LMContext,
+        selector: ListSelector,
+        paging: PagingRequest,
+        _sort: SortRequest,
+        _return_attributes: bool,
+        _rollups: Option<RollupRequest>,
+    ) -> ListsFuture<'a> {
+        Box::pin(async move {
+            actix_web::rt::time::sleep(Duration::from_millis(1)).await;
+            if !self.available {
+                return Err(ListManagementError::Unavailable("the remote source is down".to_string()));
+            }
+            let list = ItemList {
+                id: selector.limit_list_ids.first().copied().or(Some(99)),
+                attributes: HashMap::new(),
+                created: Default::default(),
+                deleted: false,
+                folder: "remote".to_string(),
+                items: Some(vec![]),
+                list_access: ListAccess::Private,
+                list_accounts: vec![],
+                list_type: ListType::Standard,
+                modified: Default::default(),
+                name: format!("Remote list for user {}", context.current_user_state().user_id),
+                read_only: true,
+                rollups: None,
+                converted_rollups: None,
+                version: 1,
+            };
+            Ok(ListOfListsResponse { total: 1, start: paging.start, rows: paging.rows, lists: vec![list], next_cursor: None })
+        })
+    }
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_of_lists_from_injected_provider() {
+    let token = setup();
+
+    for (available, expected_status) in [(true, StatusCode::OK), (false, StatusCode::SERVICE_UNAVAILABLE)] {
+        let state = AppState {
+            list_provider: Arc::new(RemoteListProvider { available }),
+            ..AppState::from_env()
+        };
+        let app = test::init_service(
+            App::new()
+                .wrap(TracingLogger::default())
+                .app_data(web::Data::new(state))
+                .configure(route_config::config)
+        ).await;
+
+        let req = test::TestRequest::get()
+            .uri("/list_of_lists")
+            .insert_header(("Authorization", token.as_str()))
+            .to_request();
+        let service_response = test::call_service(&app, req).await;
+        assert_eq!(service_response.status(), expected_status);
+        if !available {
+            continue;
+        }
+        let lr: ListOfListsResponse = test::read_body_json(service_response).await;
+        assert_eq!(1, lr.total);
+        assert_eq!(Some(99), lr.lists[0].id);
+        assert!(lr.lists[0].name.starts_with("Remote list for user "));
+
+        // Single lists are found through the provider too.
+        let req = test::TestRequest::get()
+            .uri("/lists/7")
+            .insert_header(("Authorization", token.as_str()))
+            .to_request();
+        let list: ItemList = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(Some(7), list.id);
+        assert_eq!("remote", list.folder);
+    }
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_of_lists_requires_authentication() {
+    setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    for authorization in [
+        None,
+        Some("Bearer not-a-token".to_string()),
+        Some(bearer_token("s1", "unknown")),
+    ] {
+        let mut req = test::TestRequest::get().uri("/list_of_lists");
+        if let Some(authorization) = &authorization {
+            req = req.insert_header(("Authorization", authorization.as_str()));
+        }
+        let service_response = test::call_service(&app, req.to_request()).await;
+        assert_eq!(service_response.status(), StatusCode::UNAUTHORIZED, "{:?}", authorization);
+        assert_eq!("Bearer", service_response.headers().get("WWW-Authenticate").unwrap());
+    }
 }
 
-fn setup() -> i32 {
+/// Sets up the database and returns a bearer token for the test user.
+fn setup() -> String {
     setup_logging();
     setup_db();
     let at1_id = insert_account_type(
@@ -40,7 +467,7 @@ fn setup() -> i32 {
     let a1_id = insert_account(at1_id, "as1".to_string());
     let u1_id = insert_user("User One", "s1", "s1-1");
     setup_lists(vec![a1_id], vec![a1_id], u1_id, u1_id);
-    u1_id
+    bearer_token("s1", "s1-1")
 }
 
 #[cfg(test)]
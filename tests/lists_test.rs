@@ -0,0 +1,356 @@
+use actix_web::{App, test};
+use actix_web::http::StatusCode;
+use serde_json::json;
+use serial_test::serial;
+use tracing_actix_web::TracingLogger;
+
+use list_management::common::{ErrorResponse, ItemList, ListAccess, ListAttribute, ListType};
+use list_management::db;
+use list_management::list_of_lists_service::ListOfListsResponse;
+use list_management::route_config;
+use list_management::test_helpers::{bearer_token, insert_user, insert_user_account, setup_accounts, setup_db, setup_lists, setup_logging};
+
+#[actix_web::test]
+#[serial]
+async fn test_list_crud() {
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/lists")
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({
+            "name": "Groceries",
+            "folder": "home",
+            "attributes": { "store": { "Text": "corner" } }
+        }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::CREATED);
+    assert_eq!("\"1\"", service_response.headers().get("ETag").unwrap());
+    let created: ItemList = test::read_body_json(service_response).await;
+    assert_eq!("Groceries", created.name);
+    assert_eq!("home", created.folder);
+    assert_eq!(ListAccess::Public, created.list_access);
+    assert_eq!(ListType::Standard, created.list_type);
+    assert_eq!(ListAttribute::Text("corner".to_string()), created.attributes["store"]);
+    let uri = format!("/lists/{}", created.id.unwrap());
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let etag = service_response.headers().get("ETag").unwrap().clone();
+    let retrieved: ItemList = test::read_body_json(service_response).await;
+    assert_eq!(created.id, retrieved.id);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", etag))
+        .set_json(json!({ "list_access": "Private" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let patched: ItemList = test::read_body_json(service_response).await;
+    assert_eq!("Groceries", patched.name);
+    assert_eq!("home", patched.folder);
+    assert_eq!(ListAccess::Private, patched.list_access);
+    assert_eq!(1, patched.attributes.len());
+
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", format!("\"{}\"", patched.version)))
+        .set_json(json!({ "name": "Hardware", "list_type": "Transient" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let replaced: ItemList = test::read_body_json(service_response).await;
+    assert_eq!("Hardware", replaced.name);
+    assert_eq!("default", replaced.folder);
+    assert_eq!(ListAccess::Public, replaced.list_access);
+    assert_eq!(ListType::Transient, replaced.list_type);
+    assert!(replaced.attributes.is_empty());
+
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", format!("\"{}\"", replaced.version)))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_crud_errors() {
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/lists")
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "folder": "home" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/lists")
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "name": "Groceries", "list_access": "Secret" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::BAD_REQUEST);
+    let error: ErrorResponse = test::read_body_json(service_response).await;
+    assert_eq!("Validation", error.error);
+
+    let req = test::TestRequest::get()
+        .uri("/lists/not-a-number")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::patch()
+        .uri("/lists/12345")
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "*"))
+        .set_json(json!({ "name": "Hardware" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::NOT_FOUND);
+    let error: ErrorResponse = test::read_body_json(service_response).await;
+    assert_eq!("NotFound", error.error);
+    assert_eq!("Not Found: 12345", error.message);
+
+    let req = test::TestRequest::delete()
+        .uri("/lists/12345")
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "*"))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_database_unavailable() {
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    // The test pool has a single connection, so holding it leaves none for the request.
+    let held = db::connection().unwrap();
+    let req = test::TestRequest::get()
+        .uri("/lists/1")
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!("1", service_response.headers().get("Retry-After").unwrap());
+    let error: ErrorResponse = test::read_body_json(service_response).await;
+    assert_eq!("Unavailable", error.error);
+    drop(held);
+
+    let req = test::TestRequest::post()
+        .uri("/lists")
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "name": "Groceries" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::CREATED);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_read_only_list() {
+    setup_logging();
+    setup_db();
+    let (a1_id, a2_id) = setup_accounts();
+    let user_id_1 = insert_user("User One", "s1", "s1-1");
+    let user_id_2 = insert_user("User Two", "s1", "s1-2");
+    insert_user_account(user_id_2, a2_id);
+    setup_lists(vec![a1_id, a2_id], vec![a1_id], user_id_1, user_id_2);
+    let token = bearer_token("s1", "s1-1");
+    let token_2 = bearer_token("s1", "s1-2");
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::get()
+        .uri("/list_of_lists?show_read_only=true&return_rollups=false")
+        .insert_header(("Authorization", token_2.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let lists: ListOfListsResponse = test::read_body_json(service_response).await;
+    assert_eq!(2, lists.total);
+    let read_only = lists.lists.iter().find(|l| l.read_only).unwrap();
+    assert_eq!("Item List One", read_only.name);
+    let uri = format!("/lists/{}", read_only.id.unwrap());
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("Authorization", token_2.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    let retrieved: ItemList = test::read_body_json(service_response).await;
+    assert!(retrieved.read_only);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("Authorization", token_2.as_str()))
+        .insert_header(("If-Match", "*"))
+        .set_json(json!({ "name": "Hardware" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::FORBIDDEN);
+    let error: ErrorResponse = test::read_body_json(service_response).await;
+    assert_eq!("Forbidden", error.error);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", format!("\"{}\"", retrieved.version)))
+        .set_json(json!({ "name": "Hardware" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+#[serial]
+async fn test_list_versions() {
+    let token = setup();
+
+    let app = test::init_service(
+        App::new()
+            .wrap(TracingLogger::default())
+            .configure(route_config::config)
+    ).await;
+
+    let req = test::TestRequest::post()
+        .uri("/lists")
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "name": "Groceries" }))
+        .to_request();
+    let created: ItemList = test::call_and_read_body_json(&app, req).await;
+    let uri = format!("/lists/{}", created.id.unwrap());
+
+    // A change must say which version it is based on.
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "name": "Hardware" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::PRECONDITION_REQUIRED);
+    let error: ErrorResponse = test::read_body_json(service_response).await;
+    assert_eq!("PreconditionRequired", error.error);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "\"1\", \"2\""))
+        .set_json(json!({ "name": "Hardware" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::BAD_REQUEST);
+
+    // Adding an item changes the list, so the version it was created at is stale.
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/items", uri))
+        .insert_header(("Authorization", token.as_str()))
+        .set_json(json!({ "name": "Apples", "source": "grocer" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::CREATED);
+
+    for if_match in ["\"1\"", "W/\"2\"", "\"latest\""] {
+        let req = test::TestRequest::patch()
+            .uri(&uri)
+            .insert_header(("Authorization", token.as_str()))
+            .insert_header(("If-Match", if_match))
+            .set_json(json!({ "name": "Hardware" }))
+            .to_request();
+        let service_response = test::call_service(&app, req).await;
+        assert_eq!(service_response.status(), StatusCode::PRECONDITION_FAILED, "{}", if_match);
+        let error: ErrorResponse = test::read_body_json(service_response).await;
+        assert_eq!("PreconditionFailed", error.error);
+    }
+    let req = test::TestRequest::delete()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "\"1\""))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let req = test::TestRequest::get()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    let etag = service_response.headers().get("ETag").unwrap().clone();
+    assert_eq!("\"2\"", etag);
+    let retrieved: ItemList = test::read_body_json(service_response).await;
+    assert_eq!("Groceries", retrieved.name);
+    assert_eq!(2, retrieved.version);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", etag))
+        .set_json(json!({ "name": "Hardware" }))
+        .to_request();
+    let service_response = test::call_service(&app, req).await;
+    assert_eq!(service_response.status(), StatusCode::OK);
+    assert_eq!("\"3\"", service_response.headers().get("ETag").unwrap());
+
+    let req = test::TestRequest::put()
+        .uri(&uri)
+        .insert_header(("Authorization", token.as_str()))
+        .insert_header(("If-Match", "*"))
+        .set_json(json!({ "name": "Tools" }))
+        .to_request();
+    let replaced: ItemList = test::call_and_read_body_json(&app, req).await;
+    assert_eq!("Tools", replaced.name);
+    assert_eq!(4, replaced.version);
+}
+
+/// Sets up the database and returns a bearer token for the test user.
+fn setup() -> String {
+    setup_logging();
+    setup_db();
+    insert_user("User One", "s1", "s1-1");
+    bearer_token("s1", "s1-1")
+}